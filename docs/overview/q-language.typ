@@ -87,7 +87,7 @@ A: 3x3
 
 To input the number two, execute the algorithm ((R U R' U') (R U R' U')) on the Rubik's Cube. Notice that if you try to execute (R U R' U') six times, the cube will return to its solved state as if you had inputted the number zero. Thus, your input number must not be greater than five, and this is shown with the `max-input 5` syntax.
 
-If a negative input is meaningful to the program you are executing, you can input negative one by performing the inverse of the algorithm. For example, negative two would be inputted as ((U R U' R') (U R U' R')).
+Negative inputs are not accepted, even if they would be meaningful to the program you are executing. The number you input must be between zero and the maximum input.
 
 - `halt <message> [<algorithm> counting-until <positions...>]`
 
@@ -20,7 +20,18 @@ You can choose to use larger register sizes at the cost of requiring more puzzle
 }
 ```
 
-To access the remainder of a register as explained in #link("/overview/what-is-qter.html#label-Multiple numbers")[What is Qter], you can write, for example, `A%3` to access the remainder after division by three.
+To access the remainder of a register as explained in #link("/overview/what-is-qter.html#label-Multiple numbers")[What is Qter], you can write, for example, `A%3` to access the remainder after division by three. Theoretical registers support this too: `solved-goto A%6 label` jumps whenever the value of `A` is divisible by six.
+
+A puzzle can also be declared with several architectures to switch between by wrapping them in parentheses. Every architecture in the group must be on the same puzzle, and the program starts out using the first one.
+
+```janet
+.registers {
+    (
+        A, B <- 3x3 builtin (90, 90)
+        C <- 3x3 builtin (1260)
+    )
+}
+```
 
 The `.registers` statement is also used to declare memory tapes, which help facilitate local variables, call stacks, and heap memory. This idea will be expanded upon in #link("/overview/memory-tapes.html")[Memory Tapes].
 
@@ -41,22 +52,32 @@ infinite_loop:
     goto infinite_loop
 ```
 
-- `solved-goto <variable> <label>`
+- `solved-goto <variable>... <label>`
 
-Jump to a label if the specified variable is zero.
+Jump to a label if the specified variable is zero. If several variables are given, they must all be on the same puzzle and the jump only happens if every one of them is zero, which the puzzle can check all at once.
 
 - `input <message> <variable>`
 
 Ask the user for numeric input, which will be added to the given variable.
 
-- `print <message> [<variable>]`
+- `print [<message> [<variable>]]`
 
 Output a message, optionally followed by a variable's value.
 
-- `halt <message> [<variable>]`
+- `halt [<message> [<variable>]]`
 
 Terminate the program with a message, optionally followed by a variable's value.
 
+For both `print` and `halt`, the variable's value is placed wherever `{}` appears in the message instead of at the end, for example `halt "A is {} now" A`. Messages without a variable are printed exactly as written, including any `{}`. Leaving out the message entirely prints an empty one.
+
+- `switch <variable>`
+
+Solve the puzzle that the variable is stored on and start using the architecture that declares the variable. Values can't be carried over from one architecture to another, so every register on the puzzle is zero afterwards. A variable can only be used where its architecture is active, which is decided by the closest `switch` above it, and jumping to a label where a different architecture is active is an error.
+
+- `nop`
+
+Do nothing. This is useful for giving a label something to precede.
+
 === Metaprogramming
 
 As described, QAT is not much higher level than Q... Ideally we need some kind of framework to allow abstraction and code reuse. Due to the fact that Rubik's Cubes have extremely limited memory, we cannot maintain a call stack in the way that a classical computer would. Therefore, we cannot incorporate functions into QAT. Instead, we have a Rust-inspired macro system where invocations of a macro automatically copy/paste the macro definition into the call site.
@@ -79,6 +100,13 @@ $DO_ADDITION
 -- `A` will store the number 16
 ```
 
+Integer constants can also be combined with `+`, `-`, `*`, `/`, and `%`. Multiplication, division, and remainder are evaluated before addition and subtraction.
+
+```janet
+.define TAU $PI * 2
+.define SEVEN $PI + $PI * 2 - 2
+```
+
 However, this is most likely too simple for your use case...
 
 ==== Macros
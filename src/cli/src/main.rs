@@ -18,7 +18,9 @@ use color_eyre::{
     eyre::{OptionExt, eyre},
     owo_colors::OwoColorize,
 };
-use compiler::{Reporter, compile, q_emitter::emit_q};
+use compiler::{
+    OptimizationPipeline, Reporter, compile_with_pipeline, q_emitter::emit_q, q_parser::parse_q,
+};
 use internment::ArcIntern;
 use interpreter::{
     ActionPerformed, ExecutionState, InputRet, Interpreter, PausedState,
@@ -31,7 +33,7 @@ use puzzle_theory::{
 };
 use qter_core::{
     ByPuzzleType, Program,
-    table_encoding::{decode_table, encode_table},
+    table_encoding::{decode_table_from_reader, encode_table},
 };
 
 /// Compiles and interprets qter programs
@@ -42,6 +44,9 @@ enum Commands {
     Compile {
         /// Which file to compile; must be a QAT file
         file: PathBuf,
+        /// Skip the optimization passes so that the Q output matches what the macros expanded to
+        #[arg(long)]
+        no_optimize: bool,
     },
     /// Interpret a QAT or a Q file
     Interpret {
@@ -88,12 +93,13 @@ fn print_reports(reporter: &Reporter, sources: &HashMap<ArcIntern<str>, ArcInter
 
     for (_, report) in reporter.iter() {
         report
+            .report()
             .eprint(&mut cache)
             .unwrap_or_else(|e| eprintln!("Failed to print report: {e}"));
     }
 }
 
-fn compile_qat(file: &Path) -> color_eyre::Result<(Program, File)> {
+fn compile_qat(file: &Path, optimize: bool) -> color_eyre::Result<(Program, File)> {
     let path: ArcIntern<str> = ArcIntern::from(format!("{}", file.display()));
     let contents: ArcIntern<str> = ArcIntern::from(fs::read_to_string(file)?);
     let qat = File::new(path.clone(), contents.clone());
@@ -104,7 +110,13 @@ fn compile_qat(file: &Path) -> color_eyre::Result<(Program, File)> {
 
     let reporter = Reporter::default();
 
-    if let Some((v, _)) = compile(
+    let pipeline = if optimize {
+        OptimizationPipeline::default()
+    } else {
+        OptimizationPipeline::empty()
+    };
+
+    if let Some((v, _)) = compile_with_pipeline(
         &qat,
         {
             let sources = Rc::clone(&sources);
@@ -128,8 +140,11 @@ fn compile_qat(file: &Path) -> color_eyre::Result<(Program, File)> {
                 }
             }
         },
+        &pipeline,
         &reporter,
     ) {
+        // Anything reported for a program that compiled is a warning
+        print_reports(&reporter, &sources.borrow());
         Ok((v, qat))
     } else {
         print_reports(&reporter, &sources.borrow());
@@ -141,12 +156,31 @@ fn compile_qat(file: &Path) -> color_eyre::Result<(Program, File)> {
     }
 }
 
+fn parse_q_file(file: &Path) -> color_eyre::Result<Program> {
+    let path: ArcIntern<str> = ArcIntern::from(format!("{}", file.display()));
+    let contents: ArcIntern<str> = ArcIntern::from(fs::read_to_string(file)?);
+    let q = File::new(path.clone(), contents.clone());
+
+    let reporter = Reporter::default();
+
+    if let Some(program) = parse_q(&q, &reporter) {
+        Ok(program)
+    } else {
+        print_reports(&reporter, &std::iter::once((path, contents)).collect());
+        Err(eyre!(
+            "Could not parse {} due to {} errors.",
+            file.display(),
+            reporter.iter().count()
+        ))
+    }
+}
+
 #[tokio::main]
 async fn main() -> color_eyre::Result<()> {
     let args = Commands::parse();
 
     match args {
-        Commands::Compile { file } => {
+        Commands::Compile { file, no_optimize } => {
             if file.extension().and_then(|v| v.to_str()) != Some("qat") {
                 return Err(eyre!(
                     "The file `{}` does not have an extension of `.qat`.",
@@ -154,7 +188,7 @@ async fn main() -> color_eyre::Result<()> {
                 ));
             }
 
-            let (program, qat) = compile_qat(&file)?;
+            let (program, qat) = compile_qat(&file, !no_optimize)?;
 
             let path = file.with_extension("q");
 
@@ -177,8 +211,8 @@ async fn main() -> color_eyre::Result<()> {
         }
         Commands::Interpret { file, trace_level } => {
             let program = match file.extension().and_then(|v| v.to_str()) {
-                Some("q") => todo!(),
-                Some("qat") => compile_qat(&file)?.0,
+                Some("q") => parse_q_file(&file)?,
+                Some("qat") => compile_qat(&file, true)?.0,
                 _ => {
                     return Err(eyre!(
                         "The file `{}` does not have an extension of `.qat` or `.q`.",
@@ -221,10 +255,7 @@ async fn main() -> color_eyre::Result<()> {
         }
         #[cfg(debug_assertions)]
         Commands::Dump { input } => {
-            let data = fs::read(input)?;
-
-            let decoded =
-                decode_table(&mut data.iter().copied()).ok_or_eyre("Could not decode the table")?;
+            let decoded = decode_table_from_reader(fs::File::open(input)?)?;
 
             for moves in decoded {
                 println!("{}", moves.iter().join(" "));
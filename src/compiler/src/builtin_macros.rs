@@ -1,10 +1,10 @@
-use ariadne::{Report, ReportKind};
+use ariadne::ReportKind;
 use internment::ArcIntern;
 use puzzle_theory::span::{File, Span, WithSpan};
 
 use crate::{
-    BlockID, ExpansionInfo, LabelReference, Macro, Primitive, RegisterReference, Reporter,
-    ResolvedValue, Value,
+    BlockID, CompileError, ExpansionInfo, LabelReference, Macro, Primitive, RegisterReference,
+    Reporter, ResolvedValue, Value,
 };
 
 use std::collections::HashMap;
@@ -17,10 +17,33 @@ fn expect_reg(
 ) -> Option<RegisterReference> {
     match syntax.block_info.resolve_ref(block_id, reg_value) {
         Some(value) => match value.as_reg(syntax) {
-            Some(Ok(reg)) => Some(reg.0.clone()),
+            Some(Ok((reg, info))) => {
+                if reg.modulus.is_some_and(|modulus| modulus.is_zero()) {
+                    r.push(
+                        CompileError::build(ReportKind::Error, reg_value.span().clone())
+                            .with_message("The modulus of a register must not be zero")
+                            .finish(),
+                    );
+                    return None;
+                }
+
+                if !(info.order % info.modulus).is_zero() {
+                    r.push(
+                        CompileError::build(ReportKind::Error, reg_value.span().clone())
+                            .with_message(format!(
+                                "The modulus {} does not divide the order of {}, which is {}",
+                                info.modulus, &**reg.reg_name, info.order
+                            ))
+                            .finish(),
+                    );
+                    return None;
+                }
+
+                Some(reg.clone())
+            }
             Some(Err(reg_name)) => {
                 r.push(
-                    Report::build(ReportKind::Error, reg_value.span().clone())
+                    CompileError::build(ReportKind::Error, reg_value.span().clone())
                         .with_message(format!("The register {} does not exist", &**reg_name))
                         .finish(),
                 );
@@ -28,7 +51,7 @@ fn expect_reg(
             }
             None => {
                 r.push(
-                    Report::build(ReportKind::Error, reg_value.span().clone())
+                    CompileError::build(ReportKind::Error, reg_value.span().clone())
                         .with_message("Expected a register")
                         .finish(),
                 );
@@ -37,7 +60,7 @@ fn expect_reg(
         },
         None => {
             r.push(
-                Report::build(ReportKind::Error, reg_value.span().clone())
+                CompileError::build(ReportKind::Error, reg_value.span().clone())
                     .with_message("Constant not found in this scope")
                     .finish(),
             );
@@ -63,7 +86,7 @@ fn expect_label(
         )),
         Some(_) => {
             r.push(
-                Report::build(ReportKind::Error, label_value.span().clone())
+                CompileError::build(ReportKind::Error, label_value.span().clone())
                     .with_message("Expected a label")
                     .finish(),
             );
@@ -71,7 +94,7 @@ fn expect_label(
         }
         None => {
             r.push(
-                Report::build(ReportKind::Error, label_value.span().clone())
+                CompileError::build(ReportKind::Error, label_value.span().clone())
                     .with_message("Constant not found in this scope")
                     .finish(),
             );
@@ -86,11 +109,11 @@ fn print_like(
     block_id: BlockID,
     r: &Reporter,
 ) -> Option<(Option<RegisterReference>, WithSpan<String>)> {
-    if args.len() > 2 || args.is_empty() {
+    if args.len() > 2 {
         r.push(
-            Report::build(ReportKind::Error, args.span().clone())
+            CompileError::build(ReportKind::Error, args.span().clone())
                 .with_message(format!(
-                    "Expected one or two arguments, found {}",
+                    "Expected at most two arguments, found {}",
                     args.len()
                 ))
                 .finish(),
@@ -98,6 +121,11 @@ fn print_like(
         return None;
     }
 
+    // `halt` and `print` without any arguments just have an empty message
+    if args.is_empty() {
+        return Some((None, WithSpan::new(String::new(), args.span().to_owned())));
+    }
+
     let maybe_reg = if args.len() == 2 {
         Some(expect_reg(
             args.pop().as_ref().unwrap(),
@@ -117,7 +145,7 @@ fn print_like(
         }
         Some(_) => {
             r.push(
-                Report::build(ReportKind::Error, span.clone())
+                CompileError::build(ReportKind::Error, span.clone())
                     .with_message("Expected a message")
                     .finish(),
             );
@@ -125,7 +153,7 @@ fn print_like(
         }
         None => {
             r.push(
-                Report::build(ReportKind::Error, span.clone())
+                CompileError::build(ReportKind::Error, span.clone())
                     .with_message("Constant not found in this scope")
                     .finish(),
             );
@@ -133,6 +161,22 @@ fn print_like(
         }
     };
 
+    // The register's value is substituted into a `{}` placeholder if there is one. Messages without a register are printed as written, so `{}` has no special meaning in them.
+    if maybe_reg.is_some() {
+        let placeholders = message.matches("{}").count();
+
+        if placeholders > 1 {
+            r.push(
+                CompileError::build(ReportKind::Error, message.span().clone())
+                    .with_message(format!(
+                        "Expected at most one `{{}}` placeholder, found {placeholders}"
+                    ))
+                    .finish(),
+            );
+            return None;
+        }
+    }
+
     Some((maybe_reg, message))
 }
 
@@ -151,7 +195,7 @@ pub fn builtin_macros(prelude: &File) -> HashMap<(File, ArcIntern<str>), WithSpa
             Macro::Builtin(|syntax, mut args, block_id, r| {
                 if args.len() != 2 {
                     r.push(
-                        Report::build(ReportKind::Error, args.span().clone())
+                        CompileError::build(ReportKind::Error, args.span().clone())
                             .with_message(format!("Expected two arguments, found {}", args.len()))
                             .finish(),
                     );
@@ -164,7 +208,7 @@ pub fn builtin_macros(prelude: &File) -> HashMap<(File, ArcIntern<str>), WithSpa
                     Some(ResolvedValue::Int(int)) => WithSpan::new(int, span),
                     Some(_) => {
                         r.push(
-                            Report::build(ReportKind::Error, span)
+                            CompileError::build(ReportKind::Error, span)
                                 .with_message("Expected a number")
                                 .finish(),
                         );
@@ -172,7 +216,7 @@ pub fn builtin_macros(prelude: &File) -> HashMap<(File, ArcIntern<str>), WithSpa
                     }
                     None => {
                         r.push(
-                            Report::build(ReportKind::Error, span)
+                            CompileError::build(ReportKind::Error, span)
                                 .with_message("Constant not found in this scope")
                                 .finish(),
                         );
@@ -188,13 +232,32 @@ pub fn builtin_macros(prelude: &File) -> HashMap<(File, ArcIntern<str>), WithSpa
         ),
     );
 
+    // Expands to nothing; useful for giving a label something to precede
+    macros.insert(
+        (prelude.to_owned(), ArcIntern::from("nop")),
+        WithSpan::new(
+            Macro::Builtin(|_, args, _, r| {
+                if !args.is_empty() {
+                    r.push(
+                        CompileError::build(ReportKind::Error, args.span().clone())
+                            .with_message(format!("Expected no arguments, found {}", args.len()))
+                            .finish(),
+                    );
+                }
+
+                None
+            }),
+            dummy_span.clone(),
+        ),
+    );
+
     macros.insert(
         (prelude.to_owned(), ArcIntern::from("goto")),
         WithSpan::new(
             Macro::Builtin(|syntax, mut args, block_id, r| {
                 if args.len() != 1 {
                     r.push(
-                        Report::build(ReportKind::Error, args.span().clone())
+                        CompileError::build(ReportKind::Error, args.span().clone())
                             .with_message(format!("Expected one argument, found {}", args.len()))
                             .finish(),
                     );
@@ -213,19 +276,32 @@ pub fn builtin_macros(prelude: &File) -> HashMap<(File, ArcIntern<str>), WithSpa
         (prelude.to_owned(), ArcIntern::from("solved-goto")),
         WithSpan::new(
             Macro::Builtin(|syntax, mut args, block_id, r| {
-                if args.len() != 2 {
+                if args.len() < 2 {
                     r.push(
-                        Report::build(ReportKind::Error, args.span().clone())
-                            .with_message(format!("Expected two arguments, found {}", args.len()))
+                        CompileError::build(ReportKind::Error, args.span().clone())
+                            .with_message(format!(
+                                "Expected at least two arguments, found {}",
+                                args.len()
+                            ))
                             .finish(),
                     );
                     return None;
                 }
 
                 let label = expect_label(args.pop().as_ref().unwrap(), block_id, syntax, r)?;
-                let register = expect_reg(args.pop().as_ref().unwrap(), block_id, syntax, r)?;
 
-                Some(Primitive::SolvedGoto { register, label })
+                // Every register before the label has to be solved for the jump to happen
+                let mut registers = args
+                    .iter()
+                    .map(|arg| expect_reg(arg, block_id, syntax, r))
+                    .collect::<Option<Vec<_>>>()?;
+
+                if registers.len() == 1 {
+                    let register = registers.pop().unwrap();
+                    Some(Primitive::SolvedGoto { register, label })
+                } else {
+                    Some(Primitive::SolvedGotoAll { registers, label })
+                }
             }),
             dummy_span.clone(),
         ),
@@ -237,7 +313,7 @@ pub fn builtin_macros(prelude: &File) -> HashMap<(File, ArcIntern<str>), WithSpa
             Macro::Builtin(|syntax, mut args, block_id, r| {
                 if args.len() != 2 {
                     r.push(
-                        Report::build(ReportKind::Error, args.span().clone())
+                        CompileError::build(ReportKind::Error, args.span().clone())
                             .with_message(format!("Expected two arguments, found {}", args.len()))
                             .finish(),
                     );
@@ -250,11 +326,11 @@ pub fn builtin_macros(prelude: &File) -> HashMap<(File, ArcIntern<str>), WithSpa
                 let span = second_arg.span().to_owned();
                 let message = match syntax.block_info.resolve(block_id, second_arg.into_inner()) {
                     Some(ResolvedValue::Ident { ident, as_reg: _ }) => {
-                        WithSpan::new(ident.trim_matches('"').to_owned(), span)
+                        WithSpan::new((**ident).to_owned(), span)
                     }
                     Some(_) => {
                         r.push(
-                            Report::build(ReportKind::Error, span)
+                            CompileError::build(ReportKind::Error, span)
                                 .with_message("Expected a message")
                                 .finish(),
                         );
@@ -262,7 +338,7 @@ pub fn builtin_macros(prelude: &File) -> HashMap<(File, ArcIntern<str>), WithSpa
                     }
                     None => {
                         r.push(
-                            Report::build(ReportKind::Error, span)
+                            CompileError::build(ReportKind::Error, span)
                                 .with_message("Constant not found in this scope")
                                 .finish(),
                         );
@@ -300,5 +376,26 @@ pub fn builtin_macros(prelude: &File) -> HashMap<(File, ArcIntern<str>), WithSpa
         ),
     );
 
+    macros.insert(
+        (prelude.to_owned(), ArcIntern::from("switch")),
+        WithSpan::new(
+            Macro::Builtin(|syntax, mut args, block_id, r| {
+                if args.len() != 1 {
+                    r.push(
+                        CompileError::build(ReportKind::Error, args.span().clone())
+                            .with_message(format!("Expected one argument, found {}", args.len()))
+                            .finish(),
+                    );
+                    return None;
+                }
+
+                let register = expect_reg(args.pop().as_ref().unwrap(), block_id, syntax, r)?;
+
+                Some(Primitive::Switch { register })
+            }),
+            dummy_span.clone(),
+        ),
+    );
+
     macros
 }
@@ -0,0 +1,167 @@
+use ariadne::{Config, Label, Report, ReportBuilder, ReportKind, Source};
+use puzzle_theory::span::{File, Span};
+
+/// An error emitted while compiling a QAT program
+#[derive(Debug)]
+pub struct CompileError {
+    message: String,
+    span: Span,
+    report: Report<'static, Span>,
+}
+
+impl CompileError {
+    /// Start building an error pointing at `span`. This mirrors `ariadne::Report::build` but remembers the message and span so that they can be inspected without rendering the report.
+    #[must_use]
+    pub fn build(kind: ReportKind<'static>, span: Span) -> CompileErrorBuilder {
+        CompileErrorBuilder {
+            message: String::new(),
+            span: span.clone(),
+            builder: Report::build(kind, span),
+        }
+    }
+
+    /// The headline message of the error
+    #[must_use]
+    pub fn message(&self) -> &str {
+        &self.message
+    }
+
+    /// The span of source code that the error points at
+    #[must_use]
+    pub fn span(&self) -> &Span {
+        &self.span
+    }
+
+    /// The one-based line and column of the start of the error's span. Columns count characters, not bytes.
+    #[must_use]
+    pub fn line_col(&self) -> (usize, usize) {
+        let source = self.span.source();
+        let before = &source.inner()[..self.span.start()];
+
+        let line = before.matches('\n').count() + 1;
+        let col = before
+            .rsplit_once('\n')
+            .map_or(before, |(_, last_line)| last_line)
+            .chars()
+            .count()
+            + 1;
+
+        (line, col)
+    }
+
+    /// The `ariadne` report describing the error
+    #[must_use]
+    pub fn report(&self) -> &Report<'static, Span> {
+        &self.report
+    }
+
+    /// Take the `ariadne` report describing the error, for example to print it with a custom cache of sources
+    #[must_use]
+    pub fn into_report(self) -> Report<'static, Span> {
+        self.report
+    }
+}
+
+/// A builder for a `CompileError`, created by `CompileError::build`
+pub struct CompileErrorBuilder {
+    message: String,
+    span: Span,
+    builder: ReportBuilder<'static, Span>,
+}
+
+impl CompileErrorBuilder {
+    #[must_use]
+    pub fn with_message(mut self, message: impl ToString) -> Self {
+        self.message = message.to_string();
+        self.builder = self.builder.with_message(&self.message);
+        self
+    }
+
+    #[must_use]
+    pub fn with_label(mut self, label: Label<Span>) -> Self {
+        self.builder = self.builder.with_label(label);
+        self
+    }
+
+    #[must_use]
+    pub fn with_note(mut self, note: impl ToString) -> Self {
+        self.builder = self.builder.with_note(note);
+        self
+    }
+
+    #[must_use]
+    pub fn with_help(mut self, help: impl ToString) -> Self {
+        self.builder = self.builder.with_help(help);
+        self
+    }
+
+    #[must_use]
+    pub fn with_config(mut self, config: Config) -> Self {
+        self.builder = self.builder.with_config(config);
+        self
+    }
+
+    #[must_use]
+    pub fn finish(self) -> CompileError {
+        CompileError {
+            message: self.message,
+            span: self.span,
+            report: self.builder.finish(),
+        }
+    }
+}
+
+/// Every error emitted while compiling a QAT program
+#[derive(Debug)]
+pub struct CompileErrors {
+    errors: Vec<CompileError>,
+}
+
+impl CompileErrors {
+    #[must_use]
+    pub fn errors(&self) -> &[CompileError] {
+        &self.errors
+    }
+
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.errors.len()
+    }
+
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.errors.is_empty()
+    }
+}
+
+impl FromIterator<CompileError> for CompileErrors {
+    fn from_iter<T: IntoIterator<Item = CompileError>>(iter: T) -> Self {
+        CompileErrors {
+            errors: iter.into_iter().collect(),
+        }
+    }
+}
+
+impl IntoIterator for CompileErrors {
+    type Item = CompileError;
+    type IntoIter = std::vec::IntoIter<CompileError>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.errors.into_iter()
+    }
+}
+
+/// Render an error as a human readable diagnostic with a snippet of the offending source code. Every span that the error refers to must be in `source`.
+///
+/// # Panics
+///
+/// Panics if the error refers to a file other than `source`
+#[must_use]
+pub fn render_diagnostic(error: &CompileError, source: &File) -> String {
+    let mut buf = Vec::<u8>::new();
+    error
+        .report
+        .write((source.name(), Source::from(&*source.inner())), &mut buf)
+        .unwrap();
+    String::from_utf8(buf).unwrap()
+}
@@ -0,0 +1,207 @@
+use std::sync::Arc;
+
+use internment::ArcIntern;
+use puzzle_theory::{
+    numbers::{Int, U},
+    span::{Span, WithSpan},
+};
+use qter_core::{Program, architectures::Architecture};
+
+use crate::{CompileError, Puzzle, RegistersDecl};
+
+/// A compiled Q program along with information about the registers that it declared
+#[derive(Debug)]
+pub struct CompiledProgram {
+    program: Program,
+    registers: Vec<RegisterInfo>,
+    declaration: Option<WithSpan<RegistersDecl>>,
+    warnings: Vec<CompileError>,
+}
+
+/// A register declared by a QAT program
+#[derive(Clone, Debug)]
+pub struct RegisterInfo {
+    name: ArcIntern<str>,
+    order: Int<U>,
+    puzzle_idx: usize,
+    puzzle: Option<RegisterPuzzle>,
+}
+
+/// The puzzle that a register is defined on, if the register isn't theoretical
+#[derive(Clone, Debug)]
+pub struct RegisterPuzzle {
+    definition: Span,
+    architecture: Arc<Architecture>,
+    cycles: Vec<RegisterCycle>,
+}
+
+/// A cycle of facelets that contributes to a register's value
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct RegisterCycle {
+    order: Int<U>,
+    facelets: Vec<usize>,
+}
+
+impl CompiledProgram {
+    #[must_use]
+    pub fn new(program: Program, declaration: Option<WithSpan<RegistersDecl>>) -> CompiledProgram {
+        let mut registers = Vec::new();
+
+        for (puzzle_idx, puzzle) in declaration
+            .iter()
+            .flat_map(|decl| decl.puzzles())
+            .enumerate()
+        {
+            match puzzle {
+                Puzzle::Theoretical { name, order } => registers.push(RegisterInfo {
+                    name: ArcIntern::clone(name),
+                    order: **order,
+                    puzzle_idx,
+                    puzzle: None,
+                }),
+                Puzzle::Real { architectures } => {
+                    for (names, arch, definition) in architectures {
+                        for (name, reg) in names.iter().zip(arch.registers()) {
+                            registers.push(RegisterInfo {
+                                name: ArcIntern::clone(name),
+                                order: reg.order(),
+                                puzzle_idx,
+                                puzzle: Some(RegisterPuzzle {
+                                    definition: definition.clone(),
+                                    architecture: Arc::clone(arch),
+                                    cycles: reg
+                                        .unshared_cycles()
+                                        .iter()
+                                        .map(|cycle| RegisterCycle {
+                                            order: cycle.chromatic_order(),
+                                            facelets: cycle.facelet_cycle().to_vec(),
+                                        })
+                                        .collect(),
+                                }),
+                            });
+                        }
+                    }
+                }
+            }
+        }
+
+        CompiledProgram {
+            program,
+            registers,
+            declaration,
+            warnings: Vec::new(),
+        }
+    }
+
+    /// Attach the warnings that were emitted while compiling the program
+    #[must_use]
+    pub fn with_warnings(mut self, warnings: Vec<CompileError>) -> CompiledProgram {
+        self.warnings = warnings;
+        self
+    }
+
+    #[must_use]
+    pub fn program(&self) -> &Program {
+        &self.program
+    }
+
+    #[must_use]
+    pub fn into_program(self) -> Program {
+        self.program
+    }
+
+    /// All of the declared registers, in the order that they were declared
+    #[must_use]
+    pub fn registers(&self) -> &[RegisterInfo] {
+        &self.registers
+    }
+
+    /// Find a register by name
+    #[must_use]
+    pub fn register(&self, name: &str) -> Option<&RegisterInfo> {
+        self.registers.iter().find(|reg| &*reg.name == name)
+    }
+
+    /// The span of the registers declaration, if there was one
+    #[must_use]
+    pub fn declaration_span(&self) -> Option<&Span> {
+        self.declaration.as_ref().map(WithSpan::span)
+    }
+
+    /// The raw registers declaration, if there was one
+    #[must_use]
+    pub fn declaration(&self) -> Option<&WithSpan<RegistersDecl>> {
+        self.declaration.as_ref()
+    }
+
+    /// Every warning emitted while compiling the program
+    #[must_use]
+    pub fn warnings(&self) -> &[CompileError] {
+        &self.warnings
+    }
+}
+
+impl RegisterInfo {
+    #[must_use]
+    pub fn name(&self) -> &ArcIntern<str> {
+        &self.name
+    }
+
+    #[must_use]
+    pub fn order(&self) -> Int<U> {
+        self.order
+    }
+
+    /// The index of the puzzle in the registers declaration that this register lives on
+    #[must_use]
+    pub fn puzzle_idx(&self) -> usize {
+        self.puzzle_idx
+    }
+
+    /// Whether the register is a theoretical register rather than one stored on a puzzle
+    #[must_use]
+    pub fn is_theoretical(&self) -> bool {
+        self.puzzle.is_none()
+    }
+
+    /// The puzzle the register is defined on, or `None` if the register is theoretical
+    #[must_use]
+    pub fn puzzle(&self) -> Option<&RegisterPuzzle> {
+        self.puzzle.as_ref()
+    }
+
+    /// The cycles that make up the register; empty for theoretical registers
+    #[must_use]
+    pub fn cycles(&self) -> &[RegisterCycle] {
+        match &self.puzzle {
+            Some(puzzle) => &puzzle.cycles,
+            None => &[],
+        }
+    }
+}
+
+impl RegisterPuzzle {
+    /// The span of the puzzle definition, for example `3x3`
+    #[must_use]
+    pub fn definition(&self) -> &Span {
+        &self.definition
+    }
+
+    #[must_use]
+    pub fn architecture(&self) -> &Arc<Architecture> {
+        &self.architecture
+    }
+}
+
+impl RegisterCycle {
+    /// The chromatic order of the cycle
+    #[must_use]
+    pub fn order(&self) -> Int<U> {
+        self.order
+    }
+
+    #[must_use]
+    pub fn facelets(&self) -> &[usize] {
+        &self.facelets
+    }
+}
@@ -14,11 +14,11 @@ use std::{
     },
 };
 
-use ariadne::{Report, ReportKind};
+use ariadne::ReportKind;
 use internment::ArcIntern;
 use parsing::parse;
 use puzzle_theory::{
-    numbers::{Int, ParseIntError, U},
+    numbers::{I, Int, ParseIntError, U},
     span::{File, Span, WithSpan},
 };
 use qter_core::{Program, architectures::Architecture};
@@ -26,16 +26,22 @@ use rhai::RhaiMacros;
 use strip_expanded::strip_expanded;
 
 use crate::macro_expansion::expand;
+pub use compile_error::{CompileError, CompileErrorBuilder, CompileErrors, render_diagnostic};
+pub use compiled_program::{CompiledProgram, RegisterCycle, RegisterInfo, RegisterPuzzle};
+pub use optimization::{OptimizationPass, OptimizationPipeline};
 
 mod builtin_macros;
+mod compile_error;
+mod compiled_program;
 mod macro_expansion;
 mod optimization;
 mod parsing;
 pub mod q_emitter;
+pub mod q_parser;
 mod rhai;
 mod strip_expanded;
 
-pub type Reporter = Arc<boxcar::Vec<Report<'static, Span>>>;
+pub type Reporter = Arc<boxcar::Vec<CompileError>>;
 
 /// Compiles a QAT program into a Q program while returning the register architecture used.
 ///
@@ -46,14 +52,113 @@ pub fn compile(
     qat: &File,
     find_import: impl Fn(&str) -> Result<ArcIntern<str>, String> + 'static,
     reporter: &Reporter,
+) -> Option<(Program, Option<WithSpan<RegistersDecl>>)> {
+    compile_with_pipeline(qat, find_import, &OptimizationPipeline::default(), reporter)
+}
+
+/// Compiles a QAT program like `compile`, but without running any optimization passes. This is useful for seeing exactly what macros expanded to.
+///
+/// # Errors
+///
+/// Returns an error if the QAT program is invalid or if the macro expansion fails
+pub fn compile_unoptimized(
+    qat: &File,
+    find_import: impl Fn(&str) -> Result<ArcIntern<str>, String> + 'static,
+    reporter: &Reporter,
+) -> Option<(Program, Option<WithSpan<RegistersDecl>>)> {
+    compile_with_pipeline(qat, find_import, &OptimizationPipeline::empty(), reporter)
+}
+
+/// Compiles a QAT program like `compile`, but optimizes it using the given pipeline of passes.
+///
+/// # Errors
+///
+/// Returns an error if the QAT program is invalid or if the macro expansion fails
+pub fn compile_with_pipeline(
+    qat: &File,
+    find_import: impl Fn(&str) -> Result<ArcIntern<str>, String> + 'static,
+    pipeline: &OptimizationPipeline,
+    reporter: &Reporter,
+) -> Option<(Program, Option<WithSpan<RegistersDecl>>)> {
+    compile_inner(qat, find_import, pipeline, reporter, &mut |_| {})
+}
+
+/// A phase of compilation that `compile_with_progress` is about to start
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CompileProgress {
+    /// The program and all of its imports have been parsed
+    Parsed,
+    /// Macro expansion is starting its `pass`th sweep over the code, counting from zero
+    Expanding { pass: usize },
+    /// The optimizer is starting its `pass`th iteration of the pipeline, counting from zero. The default pipeline streams instructions through every pass at once, so it only reports a single iteration.
+    Optimizing { pass: usize },
+    /// The optimized code is being turned into a `Program`
+    Emitting,
+}
+
+/// Compiles a QAT program like `compile`, but calls `progress` at every phase boundary so that long compilations can give feedback.
+///
+/// # Errors
+///
+/// Returns an error if the QAT program is invalid or if the macro expansion fails
+pub fn compile_with_progress(
+    qat: &File,
+    find_import: impl Fn(&str) -> Result<ArcIntern<str>, String> + 'static,
+    reporter: &Reporter,
+    mut progress: impl FnMut(CompileProgress),
+) -> Option<(Program, Option<WithSpan<RegistersDecl>>)> {
+    compile_inner(
+        qat,
+        find_import,
+        &OptimizationPipeline::default(),
+        reporter,
+        &mut progress,
+    )
+}
+
+fn compile_inner(
+    qat: &File,
+    find_import: impl Fn(&str) -> Result<ArcIntern<str>, String> + 'static,
+    pipeline: &OptimizationPipeline,
+    reporter: &Reporter,
+    progress: &mut dyn FnMut(CompileProgress),
 ) -> Option<(Program, Option<WithSpan<RegistersDecl>>)> {
     let parsed = parse(qat, Rc::new(find_import), false, Arc::clone(reporter))?;
 
+    progress(CompileProgress::Parsed);
+
     let arch = parsed.expansion_info.registers.clone();
 
-    let expanded = expand(parsed.into_inner(), Arc::clone(reporter))?;
+    let expanded = expand(parsed.into_inner(), Arc::clone(reporter), progress)?;
+
+    strip_expanded(expanded, pipeline, reporter, progress).map(|v| (v, arch))
+}
 
-    strip_expanded(expanded, reporter).map(|v| (v, arch))
+/// Compiles a QAT program into a Q program along with its Q code, the span of each instruction in the Q code, and the registers that it declared. Any warnings emitted along the way are available through `CompiledProgram::warnings`.
+///
+/// # Errors
+///
+/// Returns every report emitted if the QAT program is invalid or can't be emitted as Q code
+pub fn compile_to_q(
+    qat: &File,
+    find_import: impl Fn(&str) -> Result<ArcIntern<str>, String> + 'static,
+    q_file_name: ArcIntern<str>,
+) -> Result<(CompiledProgram, File, Box<[Span]>), CompileErrors> {
+    let reporter = Reporter::default();
+
+    let compiled = compile(qat, find_import, &reporter).and_then(|(program, registers)| {
+        let (q, spans) = q_emitter::emit_q(&program, q_file_name, &reporter)?;
+        Some((CompiledProgram::new(program, registers), q, spans))
+    });
+
+    let reports = Arc::try_unwrap(reporter)
+        .expect("reporter should be uniquely owned after compiling")
+        .into_iter();
+
+    match compiled {
+        Some((compiled, q, spans)) => Ok((compiled.with_warnings(reports.collect()), q, spans)),
+        None => Err(reports.collect()),
+    }
 }
 
 #[derive(Clone, Debug, PartialEq, Eq)]
@@ -234,6 +339,11 @@ enum Primitive {
         label: WithSpan<LabelReference>,
         register: RegisterReference,
     },
+    /// Jump if every one of the registers is solved
+    SolvedGotoAll {
+        label: WithSpan<LabelReference>,
+        registers: Vec<RegisterReference>,
+    },
     Input {
         message: WithSpan<String>,
         register: RegisterReference,
@@ -246,6 +356,10 @@ enum Primitive {
         message: WithSpan<String>,
         register: Option<RegisterReference>,
     },
+    /// Solve the puzzle that the register is stored on and make the register's architecture the active one
+    Switch {
+        register: RegisterReference,
+    },
 }
 
 impl Primitive {
@@ -258,6 +372,10 @@ impl Primitive {
                 label: label.map(|v| v.with_branch_key(key)),
                 register,
             },
+            Primitive::SolvedGotoAll { label, registers } => Primitive::SolvedGotoAll {
+                label: label.map(|v| v.with_branch_key(key)),
+                registers,
+            },
             _ => self,
         }
     }
@@ -302,14 +420,14 @@ impl Value {
     }
 }
 
-type RegisterInfo = (RegisterReference, RhaiRegInfo);
+type ResolvedRegister = (RegisterReference, RhaiRegInfo);
 
 #[derive(Clone, Debug)]
 enum ResolvedValue {
     Int(Int<U>),
     Ident {
         ident: WithSpan<ArcIntern<str>>,
-        as_reg: OnceLock<Option<RegisterInfo>>,
+        as_reg: OnceLock<Option<ResolvedRegister>>,
     },
     Block(Block),
 }
@@ -377,6 +495,137 @@ impl RhaiCall {
     }
 }
 
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum ArithmeticOp {
+    Add,
+    Sub,
+    Mul,
+    Div,
+    Rem,
+}
+
+impl ArithmeticOp {
+    fn parse(op: &str) -> Option<ArithmeticOp> {
+        Some(match op {
+            "+" => ArithmeticOp::Add,
+            "-" => ArithmeticOp::Sub,
+            "*" => ArithmeticOp::Mul,
+            "/" => ArithmeticOp::Div,
+            "%" => ArithmeticOp::Rem,
+            _ => return None,
+        })
+    }
+
+    /// Whether the operator is evaluated before `+` and `-`
+    fn binds_tightly(self) -> bool {
+        matches!(self, ArithmeticOp::Mul | ArithmeticOp::Div | ArithmeticOp::Rem)
+    }
+
+    fn apply(
+        self,
+        lhs: &WithSpan<Int<I>>,
+        rhs: &WithSpan<Int<I>>,
+        r: &Reporter,
+    ) -> Option<WithSpan<Int<I>>> {
+        let span = lhs.span().merge(rhs.span());
+
+        let value = match self {
+            ArithmeticOp::Add => *lhs + *rhs,
+            ArithmeticOp::Sub => *lhs - *rhs,
+            ArithmeticOp::Mul => *lhs * *rhs,
+            ArithmeticOp::Div | ArithmeticOp::Rem => {
+                if rhs.is_zero() {
+                    r.push(
+                        CompileError::build(ReportKind::Error, rhs.span().clone())
+                            .with_message("Division by zero")
+                            .finish(),
+                    );
+                    return None;
+                }
+
+                if self == ArithmeticOp::Div {
+                    *lhs / *rhs
+                } else {
+                    Int::<I>::from(*lhs % *rhs)
+                }
+            }
+        };
+
+        Some(span.with(value))
+    }
+}
+
+/// Integer arithmetic in a `.define` statement, like `.define two $one + 1`. Anything more complicated should use a Rhai call.
+#[derive(Clone, Debug)]
+struct Arithmetic {
+    first: WithSpan<Value>,
+    rest: Vec<(WithSpan<ArithmeticOp>, WithSpan<Value>)>,
+}
+
+impl Arithmetic {
+    fn perform(
+        self,
+        span: Span,
+        info: &ExpansionInfo,
+        block_id: BlockID,
+        r: &Reporter,
+    ) -> Option<WithSpan<ResolvedValue>> {
+        let operand = |value: WithSpan<Value>| {
+            let resolved = info.resolve(DefineValue::Value(value), block_id, r)?;
+            let span = resolved.span().clone();
+
+            match resolved.into_inner() {
+                ResolvedValue::Int(int) => Some(span.with(Int::<I>::from(int))),
+                _ => {
+                    r.push(
+                        CompileError::build(ReportKind::Error, span)
+                            .with_message("Expected a number")
+                            .finish(),
+                    );
+                    None
+                }
+            }
+        };
+
+        // Evaluate multiplicative operators first, leaving a chain of additions and subtractions
+        let mut first = operand(self.first)?;
+        let mut terms: Vec<(ArithmeticOp, WithSpan<Int<I>>)> = Vec::new();
+
+        for (op, value) in self.rest {
+            let rhs = operand(value)?;
+
+            if op.binds_tightly() {
+                let lhs = match terms.last_mut() {
+                    Some((_, lhs)) => lhs,
+                    None => &mut first,
+                };
+
+                *lhs = op.apply(lhs, &rhs, r)?;
+            } else {
+                terms.push((*op, rhs));
+            }
+        }
+
+        let mut total = first;
+
+        for (op, rhs) in terms {
+            total = op.apply(&total, &rhs, r)?;
+        }
+
+        // Intermediate values may be negative as long as the result isn't
+        if *total < Int::<I>::zero() {
+            r.push(
+                CompileError::build(ReportKind::Error, span)
+                    .with_message(format!("Integer values must be positive. Found {}", *total))
+                    .finish(),
+            );
+            return None;
+        }
+
+        Some(span.with(ResolvedValue::Int(total.abs())))
+    }
+}
+
 #[derive(Clone, Debug)]
 enum Instruction {
     Label(Label),
@@ -544,6 +793,7 @@ enum Macro {
 enum DefineValue {
     Value(WithSpan<Value>),
     RhaiCall(WithSpan<RhaiCall>),
+    Arithmetic(WithSpan<Arithmetic>),
 }
 
 #[derive(Clone, Debug)]
@@ -765,7 +1015,7 @@ impl ExpansionInfo {
                 let value = v.into_inner();
                 let Some(resolved) = self.block_info.resolve(block_id, value) else {
                     r.push(
-                        Report::build(ReportKind::Error, span)
+                        CompileError::build(ReportKind::Error, span)
                             .with_message("Constant not found in this scope")
                             .finish(),
                     );
@@ -778,6 +1028,10 @@ impl ExpansionInfo {
                 let span = call.span().clone();
                 call.into_inner().perform(span, self, block_id, r)
             }
+            DefineValue::Arithmetic(arithmetic) => {
+                let span = arithmetic.span().clone();
+                arithmetic.into_inner().perform(span, self, block_id, r)
+            }
         }
     }
 
@@ -816,8 +1070,41 @@ mod tests {
 
     use internment::ArcIntern;
     use puzzle_theory::span::File;
+    use qter_core::Program;
+
+    use crate::{
+        CompileProgress, OptimizationPass, OptimizationPipeline, Reporter, compile,
+        compile_to_q, compile_unoptimized, compile_with_pipeline, compile_with_progress,
+        q_emitter::{emit_bytecode, emit_q},
+        q_parser::parse_q,
+        render_diagnostic,
+    };
+
+    /// Unwrap the result of compiling, printing the errors if there were any
+    fn expect_compiled<T>(compiled: Option<(Program, T)>, reporter: &Reporter) -> Program {
+        match compiled {
+            Some((program, _)) => program,
+            None => {
+                for report in reporter.iter() {
+                    println!("{:?}", report.1);
+                }
+                panic!();
+            }
+        }
+    }
+
+    fn compile_ok(code: &str) -> Program {
+        let reporter = Reporter::default();
 
-    use crate::{Reporter, compile, q_emitter::emit_q};
+        expect_compiled(
+            compile(
+                &File::new(ArcIntern::from("code.qat"), ArcIntern::from(code)),
+                |_| unreachable!(),
+                &reporter,
+            ),
+            &reporter,
+        )
+    }
 
     #[test]
     fn test_define() {
@@ -840,21 +1127,66 @@ mod tests {
             $Z
         ";
 
+        let program = compile_ok(code);
         let reporter = Reporter::default();
 
-        let (program, _) = match compile(
-            &File::new(ArcIntern::from("code.qat"), ArcIntern::from(code)),
-            |_| unreachable!(),
-            &reporter,
-        ) {
-            Some(v) => v,
-            None => {
-                for report in reporter.iter() {
-                    println!("{:?}", report.1);
-                }
-                panic!();
+        let q_code = emit_q(&program, "code.q".into(), &reporter).unwrap().0;
+
+        assert_eq!(
+            q_code.inner(),
+            r"Puzzles
+A: 3x3
+
+0 | U'
+"
+        );
+    }
+
+    #[test]
+    fn test_define_arithmetic() {
+        let code = "
+            .registers {
+                A <- 3x3 (U)
             }
-        };
+
+            .define one 1
+            .define two $one + 1
+            .define seven $one + $two * 3
+
+            add A $seven
+        ";
+
+        let program = compile_ok(code);
+        let reporter = Reporter::default();
+
+        let q_code = emit_q(&program, "code.q".into(), &reporter).unwrap().0;
+
+        // Seven quarter turns are the same as one counterclockwise quarter turn
+        assert_eq!(
+            q_code.inner(),
+            r"Puzzles
+A: 3x3
+
+0 | U'
+"
+        );
+    }
+
+    #[test]
+    fn test_define_arithmetic_negative_intermediate() {
+        let code = "
+            .registers {
+                A <- 3x3 (U)
+            }
+
+            .define four 1 - 2 + 5
+            .define three $four - 1
+
+            add A $three
+        ";
+
+        let program = compile_ok(code);
+        let reporter = Reporter::default();
 
         let q_code = emit_q(&program, "code.q".into(), &reporter).unwrap().0;
 
@@ -866,6 +1198,54 @@ A: 3x3
 0 | U'
 "
         );
+
+        let code = "
+            .registers {
+                A <- 3x3 (U)
+            }
+
+            .define x 1 - 2
+
+            add A $x
+        ";
+
+        let reporter = Reporter::default();
+
+        match compile(
+            &File::new(ArcIntern::from("code.qat"), ArcIntern::from(code)),
+            |_| unreachable!(),
+            &reporter,
+        ) {
+            Some(v) => panic!("{v:?}"),
+            None => {
+                assert!(reporter.iter().any(|(_, error)| {
+                    error.message() == "Integer values must be positive. Found -1"
+                }));
+            }
+        }
+    }
+
+    #[test]
+    fn test_bytecode() {
+        let code = "
+            .registers {
+                A <- 3x3 (U)
+            }
+
+            add A 1
+            halt \"Done \\\"now\\\"\"
+        ";
+
+        let program = compile_ok(code);
+        let reporter = Reporter::default();
+
+        assert_eq!(
+            emit_bytecode(&program, &reporter).unwrap(),
+            r#"puzzle 0 3x3
+0 perform puzzle=0 alg=[U]
+1 halt message="Done \"now\""
+"#
+        );
     }
 
     #[test]
@@ -895,4 +1275,725 @@ A: 3x3
             }
         }
     }
+
+    #[test]
+    fn test_modulus_must_divide_order() {
+        let code = "
+            .registers {
+                A, B <- 3x3 builtin (90, 90)
+            }
+
+            loop:
+                add A 1
+                solved-goto A%7 done
+                goto loop
+            done:
+                halt \"Done\"
+        ";
+
+        let reporter = Reporter::default();
+
+        match compile(
+            &File::new(ArcIntern::from("code.qat"), ArcIntern::from(code)),
+            |_| unreachable!(),
+            &reporter,
+        ) {
+            Some(v) => panic!("{v:?}"),
+            None => {
+                assert_eq!(reporter.iter().count(), 1);
+            }
+        }
+    }
+
+    #[test]
+    fn test_compile_errors() {
+        let code = "
+            .registers {
+                A <- 3x3 builtin (90)
+            }
+
+            add B 1
+            halt \"Done\"
+        ";
+
+        let file = File::new(ArcIntern::from("code.qat"), ArcIntern::from(code));
+
+        match compile_to_q(&file, |_| unreachable!(), ArcIntern::from("code.q")) {
+            Ok((program, _, _)) => panic!("{program:?}"),
+            Err(errors) => {
+                assert_eq!(errors.len(), 1);
+
+                let rendered = render_diagnostic(&errors.errors()[0], &file);
+                assert!(rendered.contains("The register B does not exist"));
+                assert!(rendered.contains("code.qat"));
+            }
+        }
+    }
+
+    #[test]
+    fn test_compile_error_accessors() {
+        // The comment puts a multibyte character before the error so columns and bytes disagree
+        let code = ".registers {
+    A <- 3x3 builtin (90)
+}
+
+/* ← */ add B 1
+halt \"Done\"
+";
+
+        let file = File::new(ArcIntern::from("code.qat"), ArcIntern::from(code));
+
+        match compile_to_q(&file, |_| unreachable!(), ArcIntern::from("code.q")) {
+            Ok((program, _, _)) => panic!("{program:?}"),
+            Err(errors) => {
+                assert_eq!(errors.len(), 1);
+
+                let error = &errors.errors()[0];
+                assert_eq!(error.message(), "The register B does not exist");
+                assert_eq!(error.span().slice(), "B");
+                assert_eq!(error.line_col(), (5, 13));
+            }
+        }
+    }
+
+    #[test]
+    fn test_compile_error_columns_count_characters() {
+        // `π` is two bytes and `←` is three, so counting bytes would put `B` at column 15
+        let code = ".registers {
+    A ← 3x3 builtin (90)
+}
+
+halt \"π ←\" B
+";
+
+        let file = File::new(ArcIntern::from("code.qat"), ArcIntern::from(code));
+
+        match compile_to_q(&file, |_| unreachable!(), ArcIntern::from("code.q")) {
+            Ok((program, _, _)) => panic!("{program:?}"),
+            Err(errors) => {
+                let error = &errors.errors()[0];
+                assert_eq!(error.span().slice(), "B");
+                assert_eq!(error.line_col(), (5, 12));
+            }
+        }
+    }
+
+    #[test]
+    fn test_redundant_solves() {
+        let code = "
+            .registers {
+                A, B <- 3x3 builtin (90, 90)
+            }
+
+                add A 20
+                add B 10
+            spot1:
+                solved-goto A spot2
+                add A 1
+                goto spot1
+            spot2:
+                solved-goto B spot3
+                add B 1
+                goto spot2
+            spot3:
+                solved-goto A spot4
+                add A 1
+                goto spot3
+            spot4:
+                solved-goto B spot5
+                add B 1
+                goto spot4
+            spot5:
+                halt \"A=\" A
+        ";
+
+        let program = compile_ok(code);
+        let reporter = Reporter::default();
+
+        let q_code = emit_q(&program, "code.q".into(), &reporter).unwrap().0;
+
+        assert_eq!(
+            q_code.inner().lines().filter(|line| line.ends_with("| solve")).count(),
+            1,
+            "{}",
+            q_code.inner()
+        );
+    }
+
+    #[test]
+    fn test_architecture_switching() {
+        let code = "
+            .registers {
+                (
+                    A <- 3x3 (U)
+                    B <- 3x3 (R)
+                )
+            }
+
+                add A 1
+                switch B
+                add B 1
+        ";
+
+        let program = compile_ok(code);
+        let reporter = Reporter::default();
+
+        assert_eq!(program.puzzles.len(), 1);
+
+        let q_code = emit_q(&program, "code.q".into(), &reporter).unwrap().0;
+
+        assert_eq!(
+            q_code.inner(),
+            r"Puzzles
+A: 3x3
+
+0 | U
+1 | solve
+2 | R
+"
+        );
+    }
+
+    #[test]
+    fn test_inactive_architecture() {
+        let code = "
+            .registers {
+                (
+                    A <- 3x3 (U)
+                    B <- 3x3 (R)
+                )
+            }
+
+                add B 1
+        ";
+
+        let reporter = Reporter::default();
+
+        assert!(
+            compile(
+                &File::new(ArcIntern::from("code.qat"), ArcIntern::from(code)),
+                |_| unreachable!(),
+                &reporter,
+            )
+            .is_none()
+        );
+
+        assert_eq!(reporter.iter().count(), 1);
+        assert_eq!(
+            reporter.iter().next().unwrap().1.message(),
+            "`B` can't be used here because a different architecture is active on its puzzle"
+        );
+    }
+
+    #[test]
+    fn test_jump_across_switch() {
+        let code = "
+            .registers {
+                (
+                    A <- 3x3 (U)
+                    B <- 3x3 (R)
+                )
+            }
+
+            loop:
+                add A 1
+                switch B
+                add B 1
+                goto loop
+        ";
+
+        let reporter = Reporter::default();
+
+        assert!(
+            compile(
+                &File::new(ArcIntern::from("code.qat"), ArcIntern::from(code)),
+                |_| unreachable!(),
+                &reporter,
+            )
+            .is_none()
+        );
+
+        assert_eq!(reporter.iter().count(), 1);
+        assert_eq!(
+            reporter.iter().next().unwrap().1.message(),
+            "This jumps to a label where a different architecture is active"
+        );
+
+        // Switching back first makes the jump fine
+        let code = "
+            .registers {
+                (
+                    A <- 3x3 (U)
+                    B <- 3x3 (R)
+                )
+            }
+
+            loop:
+                add A 1
+                switch B
+                add B 1
+                switch A
+                goto loop
+        ";
+
+        let reporter = Reporter::default();
+
+        assert!(
+            compile(
+                &File::new(ArcIntern::from("code.qat"), ArcIntern::from(code)),
+                |_| unreachable!(),
+                &reporter,
+            )
+            .is_some()
+        );
+    }
+
+    #[test]
+    fn test_pipeline_without_pass() {
+        let code = "
+            .registers {
+                A, B <- 3x3 builtin (90, 90)
+            }
+
+                add A 20
+                add B 10
+            zero_a:
+                solved-goto A zero_b
+                add A 1
+                goto zero_a
+            zero_b:
+                solved-goto B done
+                add B 1
+                goto zero_b
+            done:
+                halt \"A=\" A
+        ";
+
+        let reporter = Reporter::default();
+
+        let pipeline =
+            OptimizationPipeline::default().without_pass(OptimizationPass::TransformSolve);
+
+        let program = expect_compiled(
+            compile_with_pipeline(
+                &File::new(ArcIntern::from("code.qat"), ArcIntern::from(code)),
+                |_| unreachable!(),
+                &pipeline,
+                &reporter,
+            ),
+            &reporter,
+        );
+
+        let q_code = emit_q(&program, "code.q".into(), &reporter).unwrap().0;
+
+        assert!(q_code.inner().contains("repeat until"), "{}", q_code.inner());
+        assert!(!q_code.inner().contains("| solve"), "{}", q_code.inner());
+    }
+
+    #[test]
+    fn test_progress() {
+        let code = "
+            .registers {
+                A, B <- 3x3 builtin (90, 90)
+            }
+
+                inc A
+                halt \"A=\" A
+        ";
+
+        let reporter = Reporter::default();
+        let mut phases = Vec::new();
+
+        let result = compile_with_progress(
+            &File::new(ArcIntern::from("code.qat"), ArcIntern::from(code)),
+            |_| unreachable!(),
+            &reporter,
+            |phase| phases.push(phase),
+        );
+
+        assert!(result.is_some());
+        assert_eq!(phases.first(), Some(&CompileProgress::Parsed));
+        assert_eq!(phases.last(), Some(&CompileProgress::Emitting));
+        assert!(phases.contains(&CompileProgress::Expanding { pass: 1 }));
+        assert!(phases.contains(&CompileProgress::Optimizing { pass: 0 }));
+    }
+
+    #[test]
+    fn test_unoptimized() {
+        let code = "
+            .registers {
+                A, B <- 3x3 builtin (90, 90)
+            }
+
+                add A 20
+                add A 10
+            zero_a:
+                solved-goto A done
+                add A 1
+                goto zero_a
+            done:
+                halt \"A=\" A
+        ";
+
+        let reporter = Reporter::default();
+
+        let program = expect_compiled(
+            compile_unoptimized(
+                &File::new(ArcIntern::from("code.qat"), ArcIntern::from(code)),
+                |_| unreachable!(),
+                &reporter,
+            ),
+            &reporter,
+        );
+
+        let q_code = emit_q(&program, "code.q".into(), &reporter).unwrap().0;
+
+        assert!(!q_code.inner().contains("repeat until"), "{}", q_code.inner());
+        assert!(q_code.inner().contains("solved-goto"), "{}", q_code.inner());
+    }
+
+    #[test]
+    fn test_nop() {
+        let code = "
+            .registers {
+                A, B <- 3x3 builtin (90, 90)
+            }
+
+            top:
+                nop
+                add A 1
+                solved-goto A done
+                goto top
+                nop
+            done:
+                nop
+                halt \"A=\" A
+        ";
+
+        let reporter = Reporter::default();
+
+        let program = expect_compiled(
+            compile_unoptimized(
+                &File::new(ArcIntern::from("code.qat"), ArcIntern::from(code)),
+                |_| unreachable!(),
+                &reporter,
+            ),
+            &reporter,
+        );
+
+        assert_eq!(program.instructions.len(), 4);
+        assert_eq!(reporter.iter().count(), 0);
+
+        assert!(
+            compile(
+                &File::new(ArcIntern::from("code.qat"), ArcIntern::from(code)),
+                |_| unreachable!(),
+                &reporter,
+            )
+            .is_some()
+        );
+    }
+
+    #[test]
+    fn test_no_message() {
+        let code = "
+            .registers {
+                A, B <- 3x3 builtin (90, 90)
+            }
+
+                add A 1
+                print
+                halt
+        ";
+
+        let program = compile_ok(code);
+        let reporter = Reporter::default();
+
+        let q_code = emit_q(&program, "code.q".into(), &reporter).unwrap().0;
+
+        assert!(q_code.inner().contains("print \"\""), "{}", q_code.inner());
+        assert!(q_code.inner().contains("halt \"\""), "{}", q_code.inner());
+
+        let reparsed = parse_q(&q_code, &reporter).unwrap();
+        assert_eq!(reparsed.instructions.len(), program.instructions.len());
+    }
+
+    #[test]
+    fn test_solved_goto_all() {
+        let code = "
+            .registers {
+                A, B <- 3x3 builtin (90, 90)
+            }
+
+                input \"A:\" A
+                input \"B:\" B
+                solved-goto A B both
+                halt \"Not both\"
+            both:
+                halt \"Both\"
+        ";
+
+        let program = compile_ok(code);
+        let reporter = Reporter::default();
+
+        let q_code = emit_q(&program, "code.q".into(), &reporter).unwrap().0;
+
+        // Both registers are checked by a single instruction
+        assert_eq!(
+            q_code.inner().matches("solved-goto").count(),
+            1,
+            "{}",
+            q_code.inner()
+        );
+
+        let reparsed = parse_q(&q_code, &reporter).unwrap();
+        assert_eq!(reparsed.instructions.len(), program.instructions.len());
+    }
+
+    #[test]
+    fn test_repeat_until_before_solve() {
+        let code = "
+            .registers {
+                A, B, C <- 3x3 builtin (30, 30, 30)
+            }
+
+                add A 5
+                add B 5
+                add C 5
+            zero_a:
+                solved-goto A zero_c
+                add A 1
+                goto zero_a
+            zero_c:
+                solved-goto C zero_b
+                add C 1
+                goto zero_c
+            zero_b:
+                solved-goto B zero_c_again
+                add B 1
+                add C 1
+                goto zero_b
+            zero_c_again:
+                solved-goto C done
+                add C 1
+                goto zero_c_again
+            done:
+                halt \"A=\" A
+        ";
+
+        let program = compile_ok(code);
+        let reporter = Reporter::default();
+
+        let q_code = emit_q(&program, "code.q".into(), &reporter).unwrap().0;
+
+        assert!(!q_code.inner().contains("repeat until"), "{}", q_code.inner());
+        assert_eq!(
+            q_code.inner().lines().filter(|line| line.ends_with("| solve")).count(),
+            1,
+            "{}",
+            q_code.inner()
+        );
+    }
+
+    #[test]
+    fn test_dead_add_warning() {
+        let code = "
+            .registers {
+                A, B <- 3x3 builtin (90, 90)
+            }
+
+                add A 20
+                add B 10
+            spot1:
+                solved-goto A spot2
+                add A 1
+                goto spot1
+            spot2:
+                solved-goto B spot3
+                add B 1
+                goto spot2
+            spot3:
+                halt \"Done\"
+        ";
+
+        let reporter = Reporter::default();
+
+        let result = compile(
+            &File::new(ArcIntern::from("code.qat"), ArcIntern::from(code)),
+            |_| unreachable!(),
+            &reporter,
+        );
+
+        // The adds are coalesced into a single instruction that the loops then undo
+        assert!(result.is_some());
+        assert_eq!(reporter.iter().count(), 1);
+    }
+
+    #[test]
+    fn test_cancelling_adds_warning() {
+        let code = "
+            .registers {
+                A, B <- 3x3 builtin (90, 90)
+            }
+
+                add A 50
+                add B 1
+                add A 40
+                halt \"A=\" A
+        ";
+
+        let reporter = Reporter::default();
+
+        let result = compile(
+            &File::new(ArcIntern::from("code.qat"), ArcIntern::from(code)),
+            |_| unreachable!(),
+            &reporter,
+        );
+
+        assert!(result.is_some());
+        assert_eq!(reporter.iter().count(), 1);
+    }
+
+    #[test]
+    fn test_compile_to_q_returns_warnings() {
+        let code = "
+            .registers {
+                A, B <- 3x3 builtin (90, 90)
+            }
+
+                add A 50
+                add B 1
+                add A 40
+                halt \"A=\" A
+        ";
+
+        let file = File::new(ArcIntern::from("code.qat"), ArcIntern::from(code));
+
+        let (compiled, _, _) =
+            compile_to_q(&file, |_| unreachable!(), ArcIntern::from("code.q")).unwrap();
+
+        assert_eq!(compiled.warnings().len(), 1);
+        assert_eq!(compiled.warnings()[0].message(), "These additions cancel each other out");
+    }
+
+    #[test]
+    fn test_program_registers() {
+        let code = "
+            .registers {
+                A, B <- 3x3 builtin (90, 90)
+                C <- theoretical 7
+            }
+
+                halt \"A=\" A
+        ";
+
+        let reporter = Reporter::default();
+
+        let (program, _) = compile(
+            &File::new(ArcIntern::from("code.qat"), ArcIntern::from(code)),
+            |_| unreachable!(),
+            &reporter,
+        )
+        .unwrap();
+
+        let registers = program
+            .registers()
+            .iter()
+            .map(|reg| (&*reg.name, reg.order.to_string(), reg.is_theoretical()))
+            .collect::<Vec<_>>();
+
+        assert_eq!(
+            registers,
+            [
+                ("A", "90".to_owned(), false),
+                ("B", "90".to_owned(), false),
+                ("C", "7".to_owned(), true)
+            ]
+        );
+        assert!(program.register("C").unwrap().is_theoretical());
+        assert!(program.register("D").is_none());
+    }
+
+    #[test]
+    fn test_out_of_range_comparison_warning() {
+        // Adding 80 and then checking for zero compares the input against 10, but the input only goes up to 8
+        let code = "
+            .registers {
+                A, B <- 3x3 builtin (90, 90)
+            }
+
+                input \"Number:\" A%9
+                add A 80
+                solved-goto A equal
+                halt \"Not equal\"
+            equal:
+                halt \"Equal\"
+        ";
+
+        let reporter = Reporter::default();
+
+        let result = compile(
+            &File::new(ArcIntern::from("code.qat"), ArcIntern::from(code)),
+            |_| unreachable!(),
+            &reporter,
+        );
+
+        assert!(result.is_some());
+        let warnings = reporter.iter().map(|v| v.1.message()).collect::<Vec<_>>();
+        assert_eq!(
+            warnings,
+            ["This can never jump because it only jumps when the input to `A` is 10"]
+        );
+
+        // Comparing against 5 is fine, as is comparing a full-range input against anything
+        for (input, amount) in [("A%9", 85), ("A", 80), ("A", 1)] {
+            let code = format!(
+                "
+                .registers {{
+                    A, B <- 3x3 builtin (90, 90)
+                }}
+
+                    input \"Number:\" {input}
+                    add A {amount}
+                    solved-goto A equal
+                    halt \"Not equal\"
+                equal:
+                    halt \"Equal\"
+                "
+            );
+
+            let reporter = Reporter::default();
+
+            let result = compile(
+                &File::new(ArcIntern::from("code.qat"), ArcIntern::from(&*code)),
+                |_| unreachable!(),
+                &reporter,
+            );
+
+            assert!(result.is_some());
+            assert_eq!(reporter.iter().count(), 0, "{input} {amount}");
+        }
+    }
+
+    #[test]
+    fn test_fully_shared_register() {
+        let code = "
+            .registers {
+                A, B <- 3x3 (U, U2)
+            }
+
+            halt \"Unreachable\"
+        ";
+
+        let reporter = Reporter::default();
+
+        let result = compile(
+            &File::new(ArcIntern::from("code.qat"), ArcIntern::from(code)),
+            |_| unreachable!(),
+            &reporter,
+        );
+
+        assert!(result.is_none());
+        assert_eq!(reporter.iter().count(), 1);
+    }
 }
@@ -1,17 +1,21 @@
 use std::{cell::OnceCell, collections::HashMap, mem};
 
-use ariadne::{Report, ReportKind};
+use ariadne::ReportKind;
 use internment::ArcIntern;
 use itertools::Itertools;
 use puzzle_theory::span::{Span, WithSpan};
 
 use crate::{
-    BlockID, Code, Define, ExpandedCode, ExpandedCodeComponent, ExpansionInfo, Instruction, Macro,
-    MacroBranchKey, ParsedSyntax, RegistersDecl, Reporter, ResolvedValue, TaggedInstruction,
-    resolve_just_these_defines, tag_with_key,
+    BlockID, Code, CompileError, CompileProgress, Define, ExpandedCode, ExpandedCodeComponent,
+    ExpansionInfo, Instruction, Macro, MacroBranchKey, ParsedSyntax, RegistersDecl, Reporter,
+    ResolvedValue, TaggedInstruction, resolve_just_these_defines, tag_with_key,
 };
 
-pub fn expand(mut parsed: ParsedSyntax, r: Reporter) -> Option<ExpandedCode> {
+pub fn expand(
+    mut parsed: ParsedSyntax,
+    r: Reporter,
+    progress: &mut dyn FnMut(CompileProgress),
+) -> Option<ExpandedCode> {
     let branch_key_fn = parsed.expansion_info.fresh_branch_key();
 
     for macro_ in &mut parsed.expansion_info.macros {
@@ -30,19 +34,26 @@ pub fn expand(mut parsed: ParsedSyntax, r: Reporter) -> Option<ExpandedCode> {
 
     let before = r.count();
 
+    let mut pass = 0;
+
+    progress(CompileProgress::Expanding { pass });
+
     while let Some(span) =
         expand_block(BlockID(0), &mut parsed.expansion_info, &mut parsed.code, &r)
     {
         limit -= 1;
+        pass += 1;
 
         if limit == 0 {
             r.push(
-                Report::build(ReportKind::Error, span)
+                CompileError::build(ReportKind::Error, span)
                     .with_message("Depth limit reached during macro expansion")
                     .finish(),
             );
             return None;
         }
+
+        progress(CompileProgress::Expanding { pass });
     }
 
     if r.count() - before != 0 {
@@ -127,7 +138,7 @@ fn expand_block(
                 }
                 Instruction::Define(define) => {
                     if block_info.defines.contains_key(&define.name) {
-                        r.push(Report::build(ReportKind::Error, span).with_message("Cannot shadow a `.define` in the same scope!").finish());
+                        r.push(CompileError::build(ReportKind::Error, span).with_message("Cannot shadow a `.define` in the same scope!").finish());
                         return vec![]
                     }
 
@@ -159,14 +170,14 @@ fn expand_block(
                     match expansion_info.block_info.get_define(block_id, &name) {
                         Some(define) => match &*define.value {
                             ResolvedValue::Int(_) => {
-                                r.push(Report::build(ReportKind::Error, span).with_message("Expected a code block, found an integer").finish());
+                                r.push(CompileError::build(ReportKind::Error, span).with_message("Expected a code block, found an integer").finish());
                                 vec![]
                             },
                             ResolvedValue::Ident {
                                 ident: _,
                                 as_reg: _,
                             } => {
-                                r.push(Report::build(ReportKind::Error, span).with_message("Expected a code block, found an identifier").finish());
+                                r.push(CompileError::build(ReportKind::Error, span).with_message("Expected a code block, found an identifier").finish());
                                 vec![]
                             },
                             ResolvedValue::Block(block) => {
@@ -187,7 +198,7 @@ fn expand_block(
                             }
                         },
                         None => {
-                            r.push(Report::build(ReportKind::Error, span).with_message(format!("`{name}` was not found in this scope")).finish());
+                            r.push(CompileError::build(ReportKind::Error, span).with_message(format!("`{name}` was not found in this scope")).finish());
                             vec![]
                         }
                     }
@@ -201,11 +212,11 @@ fn expand_block(
 
                     match value.into_inner() {
                         ResolvedValue::Int(_) => {
-                            r.push(Report::build(ReportKind::Error, span).with_message("Expected the macro to return a code block; actually returned an integer").finish());
+                            r.push(CompileError::build(ReportKind::Error, span).with_message("Expected the macro to return a code block; actually returned an integer").finish());
                             vec![]
                         },
                         ResolvedValue::Ident { ident: _, as_reg: _ } => {
-                            r.push(Report::build(ReportKind::Error, span).with_message("Expected the macro to return a code block; actually returned an identifier").finish());
+                            r.push(CompileError::build(ReportKind::Error, span).with_message("Expected the macro to return a code block; actually returned an identifier").finish());
                             vec![]
                         },
                         ResolvedValue::Block(block) => {
@@ -264,7 +275,7 @@ fn expand_code(
         ArcIntern::clone(&*macro_call.name),
     )) else {
         r.push(
-            Report::build(ReportKind::Error, macro_call.name.span().clone())
+            CompileError::build(ReportKind::Error, macro_call.name.span().clone())
                 .with_message("Macro was not found in this scope")
                 .finish(),
         );
@@ -289,9 +300,12 @@ fn expand_code(
                         Some(v) => Some(span.with(v)),
                         None => {
                             r.push(
-                                Report::build(ReportKind::Error, macro_call.name.span().clone())
-                                    .with_message("Constant was not found in this scope")
-                                    .finish(),
+                                CompileError::build(
+                                    ReportKind::Error,
+                                    macro_call.name.span().clone(),
+                                )
+                                .with_message("Constant was not found in this scope")
+                                .finish(),
                             );
                             None
                         }
@@ -321,7 +335,7 @@ fn expand_code(
             }
 
             r.push(
-                Report::build(ReportKind::Error, args_span)
+                CompileError::build(ReportKind::Error, args_span)
                     .with_message("These arguments did not match any of the patterns of this macro")
                     .finish(),
             );
@@ -381,7 +395,7 @@ mod tests {
         )
         .unwrap();
 
-        let expanded = expand(parsed.into_inner(), Arc::clone(&reporter)).unwrap();
+        let expanded = expand(parsed.into_inner(), Arc::clone(&reporter), &mut |_| {}).unwrap();
 
         let reports = Arc::try_unwrap(reporter).unwrap();
         assert_eq!(reports.count(), 0);
@@ -0,0 +1,166 @@
+use ariadne::{Label, ReportKind};
+use puzzle_theory::{
+    numbers::{Int, U},
+    span::{Span, WithSpan},
+};
+use qter_core::{ByPuzzleType, StateIdx};
+
+use crate::{
+    CompileError, Reporter,
+    optimization::{OptimizingCodeComponent, OptimizingPrimitive},
+    strip_expanded::GlobalRegs,
+};
+
+/// Warn about additions whose effect is entirely erased by a `solve` of the same puzzle before anything could observe it. These are almost always logic errors, but they don't prevent the program from running, so the warnings are non-fatal.
+pub fn warn_dead_adds(instructions: &[WithSpan<OptimizingCodeComponent>], r: &Reporter) {
+    // Additions that haven't been observed since the start of the current basic block
+    let mut pending: Vec<(ByPuzzleType<'static, StateIdx>, Span)> = Vec::new();
+
+    for component in instructions {
+        let OptimizingCodeComponent::Instruction(instr, _) = &**component else {
+            // Anything can jump to a label, so we can't make claims across it
+            pending.clear();
+            continue;
+        };
+
+        match &**instr {
+            OptimizingPrimitive::AddPuzzle { puzzle, .. } => {
+                pending.push((ByPuzzleType::Puzzle(*puzzle), component.span().clone()));
+            }
+            OptimizingPrimitive::AddTheoretical { theoretical, .. } => {
+                pending.push((
+                    ByPuzzleType::Theoretical(*theoretical),
+                    component.span().clone(),
+                ));
+            }
+            OptimizingPrimitive::Solve { puzzle } => {
+                pending.retain(|(added_to, add_span)| {
+                    if added_to != puzzle {
+                        return true;
+                    }
+
+                    r.push(
+                        CompileError::build(ReportKind::Warning, add_span.clone())
+                            .with_message("This addition has no effect")
+                            .with_label(
+                                Label::new(component.span().clone())
+                                    .with_message("because the puzzle is solved here"),
+                            )
+                            .finish(),
+                    );
+
+                    false
+                });
+            }
+            OptimizingPrimitive::Print { register: None, .. } => {}
+            // Everything else either observes a register or leaves the basic block
+            _ => pending.clear(),
+        }
+    }
+}
+
+/// The running total of a run of additions to a single register, as `CoalesceAdds` would merge them
+struct MergedAdd {
+    state: ByPuzzleType<'static, StateIdx>,
+    reg_idx: usize,
+    order: Int<U>,
+    total: Int<U>,
+    span: Span,
+    count: usize,
+}
+
+impl MergedAdd {
+    fn add(merged: &mut Vec<MergedAdd>, new: MergedAdd) {
+        match merged
+            .iter_mut()
+            .find(|v| v.state == new.state && v.reg_idx == new.reg_idx)
+        {
+            Some(existing) => {
+                existing.total += new.total;
+                existing.span = existing.span.merge(&new.span);
+                existing.count += 1;
+            }
+            None => merged.push(new),
+        }
+    }
+}
+
+/// Warn about runs of additions to a register that `CoalesceAdds` would merge into nothing because they add up to a multiple of the register's order. This must be called on code that hasn't been optimized yet, since the individual additions are gone afterwards.
+pub fn warn_cancelling_adds(
+    instructions: &[WithSpan<OptimizingCodeComponent>],
+    global_regs: &GlobalRegs,
+    r: &Reporter,
+) {
+    let mut merged: Vec<MergedAdd> = Vec::new();
+
+    let flush = |merged: &mut Vec<MergedAdd>| {
+        for add in merged.drain(..) {
+            if add.count < 2 || !(add.total % add.order).is_zero() {
+                continue;
+            }
+
+            r.push(
+                CompileError::build(ReportKind::Warning, add.span.clone())
+                    .with_message("These additions cancel each other out")
+                    .with_label(Label::new(add.span).with_message(format!(
+                        "They add up to a multiple of {}, the order of the register",
+                        add.order
+                    )))
+                    .finish(),
+            );
+        }
+    };
+
+    for component in instructions {
+        let OptimizingCodeComponent::Instruction(instr, _) = &**component else {
+            flush(&mut merged);
+            continue;
+        };
+
+        match &**instr {
+            OptimizingPrimitive::AddPuzzle { puzzle, arch, amts } => {
+                for (reg_idx, amt) in amts {
+                    let order = arch.registers()[*reg_idx].order();
+
+                    if (**amt % order).is_zero() {
+                        continue;
+                    }
+
+                    MergedAdd::add(
+                        &mut merged,
+                        MergedAdd {
+                            state: ByPuzzleType::Puzzle(*puzzle),
+                            reg_idx: *reg_idx,
+                            order,
+                            total: **amt,
+                            span: component.span().clone(),
+                            count: 1,
+                        },
+                    );
+                }
+            }
+            OptimizingPrimitive::AddTheoretical { theoretical, amt } => {
+                let order = global_regs.theoretical_order(*theoretical);
+
+                if (**amt % order).is_zero() {
+                    continue;
+                }
+
+                MergedAdd::add(
+                    &mut merged,
+                    MergedAdd {
+                        state: ByPuzzleType::Theoretical(*theoretical),
+                        reg_idx: 0,
+                        order,
+                        total: **amt,
+                        span: component.span().clone(),
+                        count: 1,
+                    },
+                );
+            }
+            _ => flush(&mut merged),
+        }
+    }
+
+    flush(&mut merged);
+}
@@ -42,7 +42,7 @@ impl GlobalRewriter for DeadLabelRemover {
             .collect_vec();
 
         for instruction in &instructions {
-            primitive_match!((OptimizingPrimitive::Goto { label } | OptimizingPrimitive::SolvedGoto { label, .. }) = Some(instruction); else { continue; });
+            primitive_match!((OptimizingPrimitive::Goto { label } | OptimizingPrimitive::SolvedGoto { label, .. } | OptimizingPrimitive::SolvedGotoAll { label, .. }) = Some(instruction); else { continue; });
 
             let Some(is_seen) = label_locations.get_mut(&LabelReference {
                 name: ArcIntern::clone(&label.name),
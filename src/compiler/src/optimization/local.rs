@@ -10,7 +10,7 @@ use puzzle_theory::{
 };
 use qter_core::{
     ByPuzzleType, PuzzleIdx, TheoreticalIdx,
-    architectures::{Architecture, CycleGeneratorSubcycle},
+    architectures::{Architecture, CycleGeneratorSubcycle, effect_cost},
 };
 
 use crate::{
@@ -18,7 +18,7 @@ use crate::{
     optimization::{
         OptimizingPrimitive,
         combinators::{PeepholeRewriter, Rewriter},
-        extend_from_start,
+        extend_from_start, merge_spans,
     },
     primitive_match,
     strip_expanded::GlobalRegs,
@@ -88,6 +88,9 @@ impl PeepholeRewriter for RemoveUselessJumps {
             (OptimizingPrimitive::SolvedGoto {
                 label: jumps_to,
                 ..
+            } | OptimizingPrimitive::SolvedGotoAll {
+                label: jumps_to,
+                ..
             } | OptimizingPrimitive::Goto { label: jumps_to }) = window.front()
         );
 
@@ -97,6 +100,26 @@ impl PeepholeRewriter for RemoveUselessJumps {
     }
 }
 
+/// A `solve` immediately following a `solve` of the same puzzle has no effect and can be removed
+#[derive(Default)]
+pub struct RemoveRedundantSolves;
+
+impl PeepholeRewriter for RemoveRedundantSolves {
+    type Component = WithSpan<OptimizingCodeComponent>;
+    type GlobalData = GlobalRegs;
+
+    const MAX_WINDOW_SIZE: usize = 2;
+
+    fn try_match(window: &mut VecDeque<WithSpan<OptimizingCodeComponent>>, _: &GlobalRegs) {
+        primitive_match!(OptimizingPrimitive::Solve { puzzle: first } = window.front());
+        primitive_match!(OptimizingPrimitive::Solve { puzzle: second } = window.get(1));
+
+        if first == second {
+            window.remove(1).unwrap();
+        }
+    }
+}
+
 #[derive(Default)]
 pub struct CoalesceAdds {
     block_id: Option<BlockID>,
@@ -288,10 +311,7 @@ impl PeepholeRewriter for RepeatUntil1 {
         let mut values = Vec::new();
         values.push(window.pop_front().unwrap());
 
-        let span = window
-            .drain(0..3)
-            .map(|v| v.span().clone())
-            .reduce(|a, v| a.merge(&v))
+        let span = merge_spans(window.drain(0..3).map(|v| v.span().clone()))
             .unwrap();
 
         values.push(span.clone().with(repeat_until));
@@ -385,10 +405,7 @@ impl PeepholeRewriter for RepeatUntil2 {
 
         out.extend(window.drain(0..2 + optional_label));
 
-        let span = window
-            .drain(0..2)
-            .map(|v| v.span().clone())
-            .reduce(|a, v| a.merge(&v))
+        let span = merge_spans(window.drain(0..2).map(|v| v.span().clone()))
             .unwrap();
 
         out.push(span.clone().with(repeat_until));
@@ -513,10 +530,7 @@ impl PeepholeRewriter for RepeatUntil3 {
 
         out.extend(window.drain(0..2 + optional_label));
 
-        let span = window
-            .drain(0..2 + is_alg)
-            .map(|v| v.span().clone())
-            .reduce(|a, v| a.merge(&v))
+        let span = merge_spans(window.drain(0..2 + is_alg).map(|v| v.span().clone()))
             .unwrap();
 
         out.push(span.clone().with(repeat_until));
@@ -600,7 +614,17 @@ impl PeepholeRewriter for VectorizeRepeatUntil {
             .map(CycleGeneratorSubcycle::chromatic_order)
             .filter(|v| modulus != *v && (modulus % *v).is_zero() && !(amt % *v).is_zero())
             .map(|v| (v, lcm(v, amt)))
-            .min_by_key(|v| v.1)
+            // Prefer the scaled algorithm that takes the fewest moves, then the smallest scale
+            .min_by_key(|&(_, new_amt)| {
+                let scale_amt = new_amt / amt;
+
+                let scaled = amts
+                    .iter()
+                    .map(|(idx, v)| (*idx, (**v * scale_amt) % arch.registers()[*idx].order()))
+                    .collect_vec();
+
+                (effect_cost(&arch, &scaled), new_amt)
+            })
         {
             register.modulus = Some(cycle_order);
 
@@ -678,6 +702,17 @@ impl Rewriter for TransformSolve {
             return self.dump_with(component);
         };
 
+        if let OptimizingPrimitive::Solve {
+            puzzle: ByPuzzleType::Puzzle(solved),
+        } = &**instr
+            && self.puzzle_idx == Some(*solved)
+        {
+            // Whatever the buffered repeat-untils zero out is about to be solved anyways
+            self.instrs.clear();
+            self.guaranteed_zeroed = HashMap::new();
+            return vec![component];
+        }
+
         let OptimizingPrimitive::RepeatUntil {
             puzzle,
             arch: _,
@@ -739,11 +774,7 @@ impl Rewriter for TransformSolve {
                 .iter()
                 .all(|(idx, modulus)| arch.registers()[*idx].order() == *modulus)
         {
-            let span = self
-                .instrs
-                .drain(..)
-                .map(|v| v.0.span().clone())
-                .reduce(|a, v| a.merge(&v))
+            let span = merge_spans(self.instrs.drain(..).map(|v| v.0.span().clone()))
                 .unwrap();
 
             self.guaranteed_zeroed = HashMap::new();
@@ -1,27 +1,33 @@
 use std::{collections::VecDeque, ptr, sync::Arc};
 
+use itertools::Either;
 use puzzle_theory::{
     numbers::{Int, U},
-    span::WithSpan,
+    span::{Span, WithSpan},
 };
 use qter_core::{ByPuzzleType, PuzzleIdx, StateIdx, TheoreticalIdx, architectures::Architecture};
 
 use crate::{
-    BlockID, Label, LabelReference, RegisterReference,
+    BlockID, CompileProgress, Label, LabelReference, RegisterReference,
     optimization::{
         combinators::{Global, Peephole, RepeatUntilConvergence, push_to_pull},
         global::DeadLabelRemover,
         local::{
-            CoalesceAdds, RemoveUnreachableCode, RemoveUselessJumps, RepeatUntil1, RepeatUntil2,
-            RepeatUntil3, TransformSolve, VectorizeRepeatUntil,
+            CoalesceAdds, RemoveRedundantSolves, RemoveUnreachableCode, RemoveUselessJumps,
+            RepeatUntil1, RepeatUntil2, RepeatUntil3, TransformSolve, VectorizeRepeatUntil,
         },
     },
     strip_expanded::GlobalRegs,
 };
 
 mod combinators;
+mod diagnostics;
 mod global;
 mod local;
+mod pipeline;
+
+pub use diagnostics::{warn_cancelling_adds, warn_dead_adds};
+pub use pipeline::{OptimizationPass, OptimizationPipeline};
 
 // Remove when https://doc.rust-lang.org/beta/unstable-book/language-features/deref-patterns.html is stable
 #[macro_export]
@@ -45,6 +51,11 @@ where
     }
 }
 
+/// Merge every span into one that covers all of them, or `None` if there are no spans
+fn merge_spans(spans: impl IntoIterator<Item = Span>) -> Option<Span> {
+    spans.into_iter().reduce(|a, v| a.merge(&v))
+}
+
 #[derive(Clone, Debug)]
 pub enum OptimizingPrimitive {
     AddPuzzle {
@@ -64,6 +75,10 @@ pub enum OptimizingPrimitive {
         label: WithSpan<LabelReference>,
         register: RegisterReference,
     },
+    SolvedGotoAll {
+        label: WithSpan<LabelReference>,
+        registers: Vec<RegisterReference>,
+    },
     RepeatUntil {
         puzzle: PuzzleIdx,
         arch: Arc<Architecture>,
@@ -128,6 +143,16 @@ impl PartialEq for OptimizingPrimitive {
                     register: r_register,
                 },
             ) => l_label == r_label && l_register == r_register,
+            (
+                Self::SolvedGotoAll {
+                    label: l_label,
+                    registers: l_registers,
+                },
+                Self::SolvedGotoAll {
+                    label: r_label,
+                    registers: r_registers,
+                },
+            ) => l_label == r_label && l_registers == r_registers,
             (
                 Self::RepeatUntil {
                     puzzle: l_puzzle,
@@ -208,7 +233,10 @@ type OneFullPass = (
                         Peephole<RepeatUntil3>,
                         (
                             Peephole<VectorizeRepeatUntil>,
-                            (TransformSolve, Global<DeadLabelRemover>),
+                            (
+                                TransformSolve,
+                                (Peephole<RemoveRedundantSolves>, Global<DeadLabelRemover>),
+                            ),
                             // TransformSolve,
                             // Global<DeadLabelRemover>,
                         ),
@@ -222,10 +250,23 @@ type OneFullPass = (
 pub fn do_optimization(
     instructions: impl Iterator<Item = WithSpan<OptimizingCodeComponent>> + 'static,
     global_regs: &Arc<GlobalRegs>,
+    pipeline: &OptimizationPipeline,
+    progress: &mut dyn FnMut(CompileProgress),
 ) -> impl Iterator<Item = WithSpan<OptimizingCodeComponent>> {
-    push_to_pull(
-        RepeatUntilConvergence::<OneFullPass>::default(),
-        instructions,
-        Arc::clone(global_regs),
-    )
+    // The default pipeline is known statically, so it can be streamed
+    if pipeline.is_default() {
+        progress(CompileProgress::Optimizing { pass: 0 });
+
+        Either::Left(push_to_pull(
+            RepeatUntilConvergence::<OneFullPass>::default(),
+            instructions,
+            Arc::clone(global_regs),
+        ))
+    } else {
+        Either::Right(
+            pipeline
+                .run(instructions.collect(), global_regs, progress)
+                .into_iter(),
+        )
+    }
 }
@@ -0,0 +1,210 @@
+use puzzle_theory::span::WithSpan;
+
+use crate::{
+    CompileProgress,
+    optimization::{
+        OptimizingCodeComponent,
+        combinators::{Global, Peephole, Rewriter},
+        global::DeadLabelRemover,
+        local::{
+            CoalesceAdds, RemoveRedundantSolves, RemoveUnreachableCode, RemoveUselessJumps,
+            RepeatUntil1, RepeatUntil2, RepeatUntil3, TransformSolve, VectorizeRepeatUntil,
+        },
+    },
+    strip_expanded::GlobalRegs,
+};
+
+/// A single optimization pass that can be placed in an `OptimizationPipeline`
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum OptimizationPass {
+    RemoveUnreachableCode,
+    RemoveUselessJumps,
+    CoalesceAdds,
+    RepeatUntil1,
+    RepeatUntil2,
+    RepeatUntil3,
+    VectorizeRepeatUntil,
+    TransformSolve,
+    RemoveRedundantSolves,
+    DeadLabelRemover,
+}
+
+impl OptimizationPass {
+    /// Every pass, in the order that the compiler runs them by default
+    pub const DEFAULT_ORDER: [OptimizationPass; 10] = [
+        OptimizationPass::RemoveUnreachableCode,
+        OptimizationPass::RemoveUselessJumps,
+        OptimizationPass::CoalesceAdds,
+        OptimizationPass::RepeatUntil1,
+        OptimizationPass::RepeatUntil2,
+        OptimizationPass::RepeatUntil3,
+        OptimizationPass::VectorizeRepeatUntil,
+        OptimizationPass::TransformSolve,
+        OptimizationPass::RemoveRedundantSolves,
+        OptimizationPass::DeadLabelRemover,
+    ];
+
+    fn run(
+        self,
+        instructions: Vec<WithSpan<OptimizingCodeComponent>>,
+        global_regs: &GlobalRegs,
+    ) -> Vec<WithSpan<OptimizingCodeComponent>> {
+        match self {
+            OptimizationPass::RemoveUnreachableCode => {
+                run::<RemoveUnreachableCode>(instructions, global_regs)
+            }
+            OptimizationPass::RemoveUselessJumps => {
+                run::<Peephole<RemoveUselessJumps>>(instructions, global_regs)
+            }
+            OptimizationPass::CoalesceAdds => run::<CoalesceAdds>(instructions, global_regs),
+            OptimizationPass::RepeatUntil1 => {
+                run::<Peephole<RepeatUntil1>>(instructions, global_regs)
+            }
+            OptimizationPass::RepeatUntil2 => {
+                run::<Peephole<RepeatUntil2>>(instructions, global_regs)
+            }
+            OptimizationPass::RepeatUntil3 => {
+                run::<Peephole<RepeatUntil3>>(instructions, global_regs)
+            }
+            OptimizationPass::VectorizeRepeatUntil => {
+                run::<Peephole<VectorizeRepeatUntil>>(instructions, global_regs)
+            }
+            OptimizationPass::TransformSolve => run::<TransformSolve>(instructions, global_regs),
+            OptimizationPass::RemoveRedundantSolves => {
+                run::<Peephole<RemoveRedundantSolves>>(instructions, global_regs)
+            }
+            OptimizationPass::DeadLabelRemover => {
+                run::<Global<DeadLabelRemover>>(instructions, global_regs)
+            }
+        }
+    }
+}
+
+fn run<R: Rewriter<Component = WithSpan<OptimizingCodeComponent>, GlobalData = GlobalRegs>>(
+    instructions: Vec<WithSpan<OptimizingCodeComponent>>,
+    global_regs: &GlobalRegs,
+) -> Vec<WithSpan<OptimizingCodeComponent>> {
+    let mut rewriter = R::default();
+    let mut output = Vec::new();
+
+    for component in instructions {
+        output.extend(rewriter.rewrite(component, global_regs));
+    }
+
+    output.extend(rewriter.eof(global_regs));
+
+    output
+}
+
+/// Configures which optimization passes the compiler runs, in what order, and how many times the whole sequence is repeated while looking for a fixpoint
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct OptimizationPipeline {
+    passes: Vec<OptimizationPass>,
+    max_iterations: Option<usize>,
+}
+
+impl Default for OptimizationPipeline {
+    /// Every pass in `OptimizationPass::DEFAULT_ORDER`, repeated until the code stops changing
+    fn default() -> Self {
+        OptimizationPipeline {
+            passes: OptimizationPass::DEFAULT_ORDER.to_vec(),
+            max_iterations: None,
+        }
+    }
+}
+
+impl OptimizationPipeline {
+    /// A pipeline that doesn't run any passes, so that the Q output maps directly onto the expanded primitives
+    #[must_use]
+    pub fn empty() -> OptimizationPipeline {
+        OptimizationPipeline {
+            passes: Vec::new(),
+            max_iterations: None,
+        }
+    }
+
+    /// Run `pass` after all of the passes added so far
+    #[must_use]
+    pub fn with_pass(mut self, pass: OptimizationPass) -> OptimizationPipeline {
+        self.passes.push(pass);
+        self
+    }
+
+    /// Stop running every occurrence of `pass`
+    #[must_use]
+    pub fn without_pass(mut self, pass: OptimizationPass) -> OptimizationPipeline {
+        self.passes.retain(|v| *v != pass);
+        self
+    }
+
+    /// Replace the passes with `passes`, run in the order given
+    #[must_use]
+    pub fn with_passes(
+        mut self,
+        passes: impl IntoIterator<Item = OptimizationPass>,
+    ) -> OptimizationPipeline {
+        self.passes = passes.into_iter().collect();
+        self
+    }
+
+    /// Run the sequence of passes at most `max_iterations` times instead of until the code stops changing
+    #[must_use]
+    pub fn with_max_iterations(mut self, max_iterations: usize) -> OptimizationPipeline {
+        self.max_iterations = Some(max_iterations);
+        self
+    }
+
+    /// Run the sequence of passes until the code stops changing
+    #[must_use]
+    pub fn until_convergence(mut self) -> OptimizationPipeline {
+        self.max_iterations = None;
+        self
+    }
+
+    #[must_use]
+    pub fn passes(&self) -> &[OptimizationPass] {
+        &self.passes
+    }
+
+    #[must_use]
+    pub fn max_iterations(&self) -> Option<usize> {
+        self.max_iterations
+    }
+
+    pub(crate) fn is_default(&self) -> bool {
+        *self == OptimizationPipeline::default()
+    }
+
+    pub(crate) fn run(
+        &self,
+        instructions: Vec<WithSpan<OptimizingCodeComponent>>,
+        global_regs: &GlobalRegs,
+        progress: &mut dyn FnMut(CompileProgress),
+    ) -> Vec<WithSpan<OptimizingCodeComponent>> {
+        if self.passes.is_empty() {
+            return instructions;
+        }
+
+        let mut current = instructions;
+        let mut iterations = 0;
+
+        while self.max_iterations.is_none_or(|max| iterations < max) {
+            progress(CompileProgress::Optimizing { pass: iterations });
+
+            let next = self
+                .passes
+                .iter()
+                .fold(current.clone(), |instrs, pass| pass.run(instrs, global_regs));
+
+            iterations += 1;
+
+            if next == current {
+                break;
+            }
+
+            current = next;
+        }
+
+        current
+    }
+}
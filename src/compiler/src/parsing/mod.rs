@@ -1,11 +1,11 @@
-use std::rc::Rc;
+use std::{cell::RefCell, collections::HashMap, rc::Rc};
 
-use ariadne::{Label, Report, ReportKind, Source};
+use ariadne::{Label, ReportKind, Source};
 use internment::ArcIntern;
 use puzzle_theory::span::{File, Span, WithSpan};
 
 use crate::{
-    BlockID, ParsedSyntax, Reporter,
+    BlockID, CompileError, ParsedSyntax, Reporter,
     builtin_macros::builtin_macros,
     parsing::tokenizer::{TokenEnclosure, TokenizerState},
 };
@@ -30,7 +30,7 @@ thread_local! {
             reporter.clone(),
         ) else {
             for report in reporter.iter() {
-                report.1.eprint((ArcIntern::from("prelude.qat"), Source::from(prelude.inner()))).unwrap();
+                report.1.report().eprint((ArcIntern::from("prelude.qat"), Source::from(prelude.inner()))).unwrap();
             }
 
             panic!("Failed building the prelude with {} errors", reporter.count())
@@ -57,11 +57,35 @@ pub fn parse(
     find_import: Rc<impl Fn(&str) -> Result<ArcIntern<str>, String> + 'static>,
     is_prelude: bool,
     reporter: Reporter,
+) -> Option<WithSpan<ParsedSyntax>> {
+    parse_importing(
+        qat,
+        find_import,
+        is_prelude,
+        reporter,
+        &[],
+        &ImportCache::default(),
+    )
+}
+
+/// The parse results of every file imported so far, keyed by the file contents, so that a file imported from many places is only parsed once
+type ImportCache = RefCell<HashMap<ArcIntern<str>, Option<WithSpan<ParsedSyntax>>>>;
+
+/// Parse a file that was reached through a chain of imports. Each element of `import_stack` is the name of a file that is currently being parsed, along with the span of its `.import` statement that led to the next file in the chain.
+fn parse_importing(
+    qat: &File,
+    find_import: Rc<impl Fn(&str) -> Result<ArcIntern<str>, String> + 'static>,
+    is_prelude: bool,
+    reporter: Reporter,
+    import_stack: &[(ArcIntern<str>, Span)],
+    import_cache: &ImportCache,
 ) -> Option<WithSpan<ParsedSyntax>> {
     let mut state = TokenizerState::new(qat.clone(), reporter);
     let enclosure = TokenEnclosure::new(&mut state);
 
-    enclosure.parse(|iter| parser::parse(iter, find_import, is_prelude))
+    enclosure.parse(|iter| {
+        parser::parse(iter, find_import, is_prelude, import_stack, import_cache)
+    })
 }
 
 fn merge_files(
@@ -78,7 +102,7 @@ fn merge_files(
         (None, Some(regs)) => importer.expansion_info.registers = Some(regs),
         (Some(regs1), Some(regs2)) => {
             reporter.push(
-                Report::build(ReportKind::Error, span)
+                CompileError::build(ReportKind::Error, span)
                     .with_message("Importing this file introduces a second registers declaration")
                     .with_note("A QAT program may only contain one registers declaration")
                     .with_label(
@@ -223,4 +247,46 @@ pub(crate) mod tests {
             }
         }
     }
+
+    #[test]
+    fn import_cycle() {
+        let reporter = Reporter::default();
+
+        let parsed = parse(
+            &File::new(ArcIntern::from("a.qat"), ArcIntern::from(".import b.qat\n")),
+            Rc::new(|name: &str| match name {
+                "a.qat" => Ok(ArcIntern::from(".import b.qat\n")),
+                "b.qat" => Ok(ArcIntern::from(".import a.qat\n")),
+                _ => unreachable!(),
+            }),
+            false,
+            Arc::clone(&reporter),
+        );
+
+        assert!(parsed.is_some());
+        assert_eq!(reporter.iter().count(), 1);
+    }
+
+    #[test]
+    fn import_cache() {
+        let reporter = Reporter::default();
+
+        // `d.qat` contains an error, so parsing it twice would report the error twice
+        let parsed = parse(
+            &File::new(
+                ArcIntern::from("a.qat"),
+                ArcIntern::from(".import b.qat\n.import c.qat\n"),
+            ),
+            Rc::new(|name: &str| match name {
+                "b.qat" | "c.qat" => Ok(ArcIntern::from(".import d.qat\n")),
+                "d.qat" => Ok(ArcIntern::from(".import e.txt\n")),
+                _ => unreachable!(),
+            }),
+            false,
+            Arc::clone(&reporter),
+        );
+
+        assert!(parsed.is_some());
+        assert_eq!(reporter.iter().count(), 1);
+    }
 }
@@ -5,7 +5,7 @@ use std::{
     sync::{Arc, OnceLock, atomic::AtomicUsize},
 };
 
-use ariadne::{Color, Label, Report, ReportKind};
+use ariadne::{Color, Label, ReportKind};
 use internment::ArcIntern;
 use itertools::Itertools;
 use puzzle_theory::{
@@ -18,20 +18,22 @@ use qter_core::architectures::{Architecture, with_presets};
 use rhai::ParseError;
 
 use crate::{
-    Block, BlockID, BlockInfo, BlockInfoTracker, Code, DefineUnresolved, DefineValue,
-    ExpansionInfo, Instruction, Macro, MacroArgTy, MacroBranch, MacroBranchKey, MacroPattern,
-    MacroPatternComponent, ParsedSyntax, Puzzle, RegistersDecl, Reporter, ResolvedValue, RhaiCall,
-    Value,
+    Arithmetic, ArithmeticOp, Block, BlockID, BlockInfo, BlockInfoTracker, Code, CompileError,
+    DefineUnresolved, DefineValue, ExpansionInfo, Instruction, Macro, MacroArgTy, MacroBranch,
+    MacroBranchKey, MacroPattern, MacroPatternComponent, ParsedSyntax, Puzzle, RegistersDecl,
+    Reporter, ResolvedValue, RhaiCall, Value,
     parsing::tokenizer::{Attempt, Encloser, Symbol, TokenIter, TokenNL, TokenW},
     rhai::RhaiMacros,
 };
 
-use super::tokenizer::Token;
+use super::{ImportCache, tokenizer::Token};
 
 pub fn parse(
     iter: &mut TokenIter,
     find_import: Rc<impl Fn(&str) -> Result<ArcIntern<str>, String> + 'static>,
     is_prelude: bool,
+    import_stack: &[(ArcIntern<str>, Span)],
+    import_cache: &ImportCache,
 ) -> Option<ParsedSyntax> {
     let registers = match registers(iter) {
         Attempt::NotTaken(_) => None,
@@ -103,7 +105,7 @@ pub fn parse(
                     .contains_key(&(iter.file().clone(), ArcIntern::clone(&name)))
                 {
                     iter.report(
-                        Report::build(ReportKind::Error, name.span().clone())
+                        CompileError::build(ReportKind::Error, name.span().clone())
                             .with_message("This macro is already defined.")
                             .finish(),
                     );
@@ -124,18 +126,45 @@ pub fn parse(
 
                 if !filename.ends_with(".qat") {
                     iter.report(
-                        Report::build(ReportKind::Error, filename.span().clone())
+                        CompileError::build(ReportKind::Error, filename.span().clone())
                             .with_message("The file extension must be `.qat`")
                             .finish(),
                     );
                     continue;
                 }
 
+                let mut import_stack = import_stack.to_vec();
+                import_stack.push((iter.file().name(), filename.span().clone()));
+
+                if let Some(cycle_start) = import_stack
+                    .iter()
+                    .position(|(name, _)| *name == *filename)
+                {
+                    let cycle = &import_stack[cycle_start..];
+
+                    let mut report = CompileError::build(ReportKind::Error, filename.span().clone())
+                        .with_message(format!(
+                            "Import cycle: {} -> {}",
+                            cycle.iter().map(|(name, _)| &**name).join(" -> "),
+                            &**filename
+                        ));
+
+                    for (_, import_span) in &cycle[..cycle.len() - 1] {
+                        report = report.with_label(
+                            Label::new(import_span.clone()).with_message("Imported here"),
+                        );
+                    }
+
+                    iter.report(report.finish());
+
+                    continue;
+                }
+
                 let import = match (find_import)(&filename.value) {
                     Ok(v) => v,
                     Err(e) => {
                         iter.report(
-                            Report::build(ReportKind::Error, filename.span().clone())
+                            CompileError::build(ReportKind::Error, filename.span().clone())
                                 .with_message(format!("Unable to find import: {e}"))
                                 .finish(),
                         );
@@ -144,14 +173,27 @@ pub fn parse(
                     }
                 };
 
-                let find_import = Rc::clone(&find_import);
+                let cached = import_cache.borrow().get(&import).cloned();
+
+                let importee = match cached {
+                    Some(importee) => importee,
+                    None => {
+                        let importee = super::parse_importing(
+                            &File::new(filename.value, ArcIntern::clone(&import)),
+                            Rc::clone(&find_import),
+                            is_prelude,
+                            iter.r(),
+                            &import_stack,
+                            import_cache,
+                        );
+
+                        import_cache.borrow_mut().insert(import, importee.clone());
 
-                let Some(importee) = super::parse(
-                    &File::new(filename.value, import),
-                    find_import,
-                    is_prelude,
-                    iter.r(),
-                ) else {
+                        importee
+                    }
+                };
+
+                let Some(importee) = importee else {
                     continue;
                 };
 
@@ -172,7 +214,7 @@ pub fn parse(
                         None => (ident.span().clone(), true),
                     };
 
-                    let mut report = Report::build(ReportKind::Error, span.clone())
+                    let mut report = CompileError::build(ReportKind::Error, span.clone())
                         .with_message(err.to_string())
                         .with_label(Label::new(span).with_color(Color::Red));
 
@@ -226,7 +268,19 @@ fn registers(t: &mut TokenIter) -> Attempt<Option<WithSpan<RegistersDecl>>> {
                     break;
                 }
 
-                decls.push(register_decl(t)?);
+                // A parenthesized group declares architectures that can be switched between
+                let switchable = t.attempt(|t, commit| {
+                    let enclosure = t.next()?.enclosure(Encloser::Paren)?;
+
+                    *commit = true;
+
+                    enclosure.parse(switchable_decl)
+                });
+
+                decls.push(match switchable {
+                    Attempt::Taken(puzzle) => puzzle?.into_inner(),
+                    Attempt::NotTaken(_) => register_decl(t)?,
+                });
             }
 
             Some(decls)
@@ -238,6 +292,74 @@ fn registers(t: &mut TokenIter) -> Attempt<Option<WithSpan<RegistersDecl>>> {
     })
 }
 
+fn switchable_decl(t: &mut TokenIter) -> Option<Puzzle> {
+    let group_start = t.marker();
+
+    let mut architectures = Vec::new();
+
+    loop {
+        let is_empty = matches!(
+            t.attempt(|t, commit| {
+                t.next();
+                *commit = t.is_empty();
+            }),
+            Attempt::Taken(())
+        );
+
+        if is_empty {
+            break;
+        }
+
+        let start = t.marker();
+
+        match register_decl(t)? {
+            Puzzle::Theoretical { .. } => {
+                t.report(
+                    CompileError::build(ReportKind::Error, t.cash_in(start))
+                        .with_message("Theoretical registers can't be switched between")
+                        .finish(),
+                );
+
+                return None;
+            }
+            Puzzle::Real {
+                architectures: more,
+            } => architectures.extend(more),
+        }
+    }
+
+    let Some((_, _, first_def)) = architectures.first() else {
+        t.report(
+            CompileError::build(ReportKind::Error, t.cash_in(group_start))
+                .with_message("Expected at least one architecture to switch between")
+                .finish(),
+        );
+
+        return None;
+    };
+
+    // Every architecture has to number the facelets of the puzzle in the same way
+    for (_, _, def) in &architectures[1..] {
+        if def.slice() != first_def.slice() {
+            t.report(
+                CompileError::build(ReportKind::Error, def.clone())
+                    .with_message(
+                        "Every architecture that can be switched between must be on the same puzzle",
+                    )
+                    .with_label(
+                        Label::new(first_def.clone())
+                            .with_message("The first architecture is on this puzzle"),
+                    )
+                    .finish(),
+            );
+
+            return None;
+        }
+    }
+
+    Some(Puzzle::Real { architectures })
+}
+
 fn register_decl(t: &mut TokenIter) -> Option<Puzzle> {
     let mut names = Vec::new();
 
@@ -265,7 +387,7 @@ fn register_decl(t: &mut TokenIter) -> Option<Puzzle> {
                 })
             } else {
                 t.report(
-                    Report::build(ReportKind::Error, t.cash_in(start))
+                    CompileError::build(ReportKind::Error, t.cash_in(start))
                         .with_message(format!(
                             "Expected one register name whereas {} were provided.",
                             names.len()
@@ -291,7 +413,7 @@ fn register_decl(t: &mut TokenIter) -> Option<Puzzle> {
                 })
             } else {
                 t.report(
-                    Report::build(ReportKind::Error, t.cash_in(start))
+                    CompileError::build(ReportKind::Error, t.cash_in(start))
                         .with_message(format!(
                             "Expected {} names whereas {} were provided.",
                             arch.registers().len(),
@@ -334,7 +456,7 @@ fn register_architecture(t: &mut TokenIter) -> Option<PuzzleUnnamed> {
         Err(errs) => {
             for err in errs {
                 t.report(
-                    Report::build(ReportKind::Error, err.span().clone())
+                    CompileError::build(ReportKind::Error, err.span().clone())
                         .with_config(
                             ariadne::Config::new().with_index_type(ariadne::IndexType::Byte),
                         )
@@ -476,6 +598,18 @@ fn register_architecture(t: &mut TokenIter) -> Option<PuzzleUnnamed> {
         _ => tokenw.unexpected("an algorithm, parenthezised list of algorithms, or `builtin`"),
     };
 
+    if let Some(arch) = &arch
+        && let Err(e) = arch.0.validate()
+    {
+        t.r().push(
+            CompileError::build(ReportKind::Error, arch.span().clone())
+                .with_message(format!("This architecture can't be decoded as written: {e}"))
+                .finish(),
+        );
+
+        return None;
+    }
+
     arch.map(|v| PuzzleUnnamed::Real {
         architecture: v,
         def_span,
@@ -498,7 +632,7 @@ fn try_append(
         }
         Err(_) => {
             r.push(
-                Report::build(ReportKind::Error, span)
+                CompileError::build(ReportKind::Error, span)
                     .with_message("This move is not a member of the specified puzzle.")
                     .with_help(format!(
                         "Valid options are {}",
@@ -654,7 +788,7 @@ fn block(t: &mut TokenIter) -> Option<WithSpan<Block>> {
         code.push(match instruction(t) {
             Attempt::NotTaken(span) => {
                 t.report(
-                    Report::build(ReportKind::Error, span)
+                    CompileError::build(ReportKind::Error, span)
                         .with_message("Could not be parsed as an instruction")
                         .finish(),
                 );
@@ -698,9 +832,14 @@ fn define(t: &mut TokenIter) -> Option<DefineUnresolved> {
 
                 match value(token) {
                     Ok(Some(v)) => {
+                        let value = match arithmetic(t, v)? {
+                            Ok(arithmetic) => DefineValue::Arithmetic(arithmetic),
+                            Err(v) => DefineValue::Value(v),
+                        };
+
                         return Some(DefineUnresolved {
                             name: name?,
-                            value: DefineValue::Value(v),
+                            value,
                         });
                     }
                     Ok(None) => return None,
@@ -712,6 +851,43 @@ fn define(t: &mut TokenIter) -> Option<DefineUnresolved> {
     )
 }
 
+/// Parse any arithmetic operations following the first value of a `.define` statement. If there are none, the value is given back unchanged.
+fn arithmetic(
+    t: &mut TokenIter,
+    first: WithSpan<Value>,
+) -> Option<Result<WithSpan<Arithmetic>, WithSpan<Value>>> {
+    let mut rest = Vec::new();
+
+    while let Attempt::Taken(operation) = t.attempt(|t, commit| {
+        let TokenNL::Token(Token::Ident(op)) = t.next_nl()?.token else {
+            return None;
+        };
+
+        let op = op.span().clone().with(ArithmeticOp::parse(&op)?);
+        *commit = true;
+
+        let TokenW { token, reporter } = t
+            .next_nl()?
+            .token("the right hand side of an arithmetic operation")?;
+
+        match value(token) {
+            Ok(Some(v)) => Some((op, v)),
+            Ok(None) => None,
+            Err(token) => TokenW { token, reporter }.unexpected("a constant or number"),
+        }
+    }) {
+        rest.push(operation?);
+    }
+
+    let Some((_, last)) = rest.last() else {
+        return Some(Err(first));
+    };
+
+    let span = first.span().merge(last.span());
+
+    Some(Ok(span.with(Arithmetic { first, rest })))
+}
+
 fn rhai_call(t: &mut TokenIter) -> Option<RhaiCall> {
     let name = t.next_nl()?.token("a rhai function to call")?.ident()?;
 
@@ -792,7 +968,7 @@ fn macro_branch(
         let subst = match instruction(t) {
             Attempt::NotTaken(span) => {
                 t.report(
-                    Report::build(ReportKind::Error, span)
+                    CompileError::build(ReportKind::Error, span)
                         .with_message("Unable to parse as an instruction")
                         .finish(),
                 );
@@ -832,7 +1008,7 @@ fn macro_pattern_component(t: &mut TokenIter) -> Attempt<Option<WithSpan<MacroPa
 
             if let Some(ws) = t.whitespace() {
                 t.report(
-                    Report::build(ReportKind::Error, ws)
+                    CompileError::build(ReportKind::Error, ws)
                         .with_message("Expected colon, found whitespace")
                         .finish(),
                 );
@@ -3,7 +3,7 @@ use std::{mem, sync::Arc};
 use ariadne::Span as AriadneSpan;
 use polonius_the_crab::{polonius, polonius_return, polonius_try};
 
-use ariadne::{Label, Report, ReportKind};
+use ariadne::{Label, ReportKind};
 use internment::ArcIntern;
 use itertools::Itertools;
 use puzzle_theory::{
@@ -12,7 +12,7 @@ use puzzle_theory::{
 };
 use rhai::Position;
 
-use crate::Reporter;
+use crate::{CompileError, Reporter};
 
 #[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub enum Encloser {
@@ -187,7 +187,7 @@ impl<'a> TokenIter<'a> {
         }
     }
 
-    pub fn report(&self, report: Report<'static, Span>) {
+    pub fn report(&self, report: CompileError) {
         self.state.reporter.push(report);
     }
 
@@ -383,7 +383,7 @@ impl<'a> TokenW<'a> {
         };
 
         self.reporter.push(
-            Report::build(ReportKind::Error, span.clone())
+            CompileError::build(ReportKind::Error, span.clone())
                 .with_message(format!("Expected {expected} but found {found}."))
                 .with_label(Label::new(span).with_message("here"))
                 .finish(),
@@ -417,7 +417,7 @@ impl<'a> TokenNLW<'a> {
         match self.token {
             TokenNL::NewLine(span) => {
                 reporter.push(
-                    Report::build(ReportKind::Error, span)
+                    CompileError::build(ReportKind::Error, span)
                         .with_message(format!("Expected {expected}, found a line break"))
                         .finish(),
                 );
@@ -530,7 +530,7 @@ impl Tokenizer {
         let Some(end) = self.qat().find(".end-rhai") else {
             let span = self.mk_span(spot - 11, spot);
             self.reporter.push(
-                Report::build(ReportKind::Error, span.clone())
+                CompileError::build(ReportKind::Error, span.clone())
                     .with_message("Unterminated Rhai block")
                     .with_label(Label::new(span))
                     .finish(),
@@ -556,7 +556,7 @@ impl Tokenizer {
 
             for (_, char_start, char_end) in &self.enclosers {
                 self.reporter.push(
-                    Report::build(ReportKind::Error, self.mk_span(*char_start, *char_end))
+                    CompileError::build(ReportKind::Error, self.mk_span(*char_start, *char_end))
                         .with_message("Unclosed delimiter")
                         .finish(),
                 );
@@ -588,7 +588,7 @@ impl Tokenizer {
                         ))
                     }
                     v => {
-                        let mut report = Report::build(
+                        let mut report = CompileError::build(
                             ReportKind::Error,
                             self.mk_span(self.spot, self.spot + amt),
                         )
@@ -616,7 +616,7 @@ impl Tokenizer {
                     loop {
                         let eof = || {
                             self.reporter.push(
-                                Report::build(
+                                CompileError::build(
                                     ReportKind::Error,
                                     self.mk_span(quote_start, self.spot),
                                 )
@@ -639,7 +639,23 @@ impl Tokenizer {
                             }
                             Some('\\') => {
                                 match self.peek(1) {
-                                    Some(c) => text.push(c),
+                                    Some('n') => text.push('\n'),
+                                    Some(c @ ('"' | '\\')) => text.push(c),
+                                    Some(c) => {
+                                        let escape_start = self.spot;
+                                        self.advance(2);
+                                        self.reporter.push(
+                                            CompileError::build(
+                                                ReportKind::Error,
+                                                self.mk_span(escape_start, self.spot),
+                                            )
+                                            .with_message(format!(
+                                                "Unknown escape sequence `\\{c}`. The supported escape sequences are `\\\"`, `\\n`, and `\\\\`"
+                                            ))
+                                            .finish(),
+                                        );
+                                        return None;
+                                    }
                                     None => {
                                         eof();
                                         return None;
@@ -679,7 +695,7 @@ impl Tokenizer {
                     loop {
                         let (Some(c1), Some(c2)) = (self.peek(0), self.peek(1)) else {
                             self.reporter.push(
-                                Report::build(
+                                CompileError::build(
                                     ReportKind::Error,
                                     self.mk_span(comment_start, self.spot),
                                 )
@@ -1,4 +1,4 @@
-use ariadne::{Report, ReportKind};
+use ariadne::ReportKind;
 use internment::ArcIntern;
 use itertools::Itertools;
 use puzzle_theory::{
@@ -7,12 +7,12 @@ use puzzle_theory::{
     span::{File, Span, WithSpan},
 };
 use qter_core::{
-    Facelets, Halt, Input, Instruction, PerformAlgorithm, Print, Program, RepeatUntil,
+    Facelets, Halt, Input, Instruction, PerformAlgorithm, Print, Program, PuzzleIdx, RepeatUntil,
     SeparatesByPuzzleType, Solve, SolvedGoto,
 };
 use std::fmt::Write;
 
-use crate::Reporter;
+use crate::{CompileError, Reporter};
 
 const ALG_MAX_CHARS_WIDTH: usize = 50;
 
@@ -40,17 +40,11 @@ pub fn emit_q(
 ) -> Option<(File, Box<[Span]>)> {
     let before = r.count();
 
-    for theoretical in &program.theoretical {
-        r.push(
-            Report::build(ReportKind::Error, theoretical.span().clone())
-                .with_message("Cannot compile a QAT program with theoretical registers")
-                .finish(),
-        );
-    }
+    report_theoretical(program, r);
 
     if program.puzzles.len() > 1 {
         r.push(
-            Report::build(ReportKind::Error, program.puzzles[1].span().clone())
+            CompileError::build(ReportKind::Error, program.puzzles[1].span().clone())
                 .with_message("Compiling with multiple puzzles is unsupported (for now)")
                 .finish(),
         );
@@ -95,33 +89,33 @@ pub fn emit_q(
             QInstruction::Input((input, _, algorithm, facelets)) => {
                 format!(
                     "input \"{}\"\n{}\n{padding}      max-input {}",
-                    input.message,
+                    escape_message(&input.message),
                     stringify_alg(algorithm, padding.len() + 6, true),
                     facelets.order() - Int::<U>::one(),
                 )
             }
             QInstruction::Halt((halt, None)) => {
-                format!("halt \"{}\"", halt.message)
+                format!("halt \"{}\"", escape_message(&halt.message))
             }
             QInstruction::Halt((halt, Some((_, alg, facelets)))) => {
                 let mut inverse_alg = alg.clone();
                 inverse_alg.exponentiate(-Int::<U>::one());
                 format!(
                     "halt \"{}\"\n{}\n{padding}     counting-until {}",
-                    halt.message,
+                    escape_message(&halt.message),
                     stringify_alg(&inverse_alg, padding.len() + 5, true),
                     stringify_facelets(facelets),
                 )
             }
             QInstruction::Print((halt, None)) => {
-                format!("print \"{}\"", halt.message)
+                format!("print \"{}\"", escape_message(&halt.message))
             }
             QInstruction::Print((halt, Some((_, alg, facelets)))) => {
                 let mut inverse_alg = alg.clone();
                 inverse_alg.exponentiate(-Int::<U>::one());
                 format!(
                     "print \"{}\"\n{}\n{padding}      counting-until {}",
-                    halt.message,
+                    escape_message(&halt.message),
                     stringify_alg(&inverse_alg, padding.len() + 6, true),
                     stringify_facelets(facelets),
                 )
@@ -160,6 +154,117 @@ pub fn emit_q(
     Some((file, spans))
 }
 
+/// Convert a `Program` into a bytecode dump meant for other tools to read. Each puzzle is listed as `puzzle <index> <definition>`, followed by one line per instruction of the form `<index> <opcode> <operand>=<value>...`. Instruction targets, puzzle indices, and facelets are written as plain numbers, and algorithms are written as a list of moves. Unlike `emit_q`, the algorithms for `halt` and `print` are the register generators rather than their inverses.
+///
+/// # Errors
+///
+/// Returns `None` and pushes errors to the reporter if `theoretical` registers are present.
+pub fn emit_bytecode(program: &Program, r: &Reporter) -> Option<String> {
+    if report_theoretical(program, r) {
+        return None;
+    }
+
+    let mut out = String::new();
+
+    for (i, puzzle) in program.puzzles.iter().enumerate() {
+        writeln!(&mut out, "puzzle {i} {}", puzzle.span().slice()).unwrap();
+    }
+
+    for (i, instr) in convert_instructions(&program.instructions).iter().enumerate() {
+        let instr = match &**instr {
+            QInstruction::Goto { instruction_idx } => format!("goto target={instruction_idx}"),
+            QInstruction::SolvedGoto((solved_goto, puzzle_idx, facelets)) => format!(
+                "solved-goto target={} puzzle={} facelets={}",
+                solved_goto.instruction_idx,
+                puzzle_idx.0,
+                bytecode_facelets(facelets),
+            ),
+            QInstruction::Input((input, puzzle_idx, alg, facelets)) => format!(
+                "input message=\"{}\" puzzle={} alg={} facelets={} max-input={}",
+                escape_message(&input.message),
+                puzzle_idx.0,
+                bytecode_alg(alg),
+                bytecode_facelets(facelets),
+                facelets.order() - Int::<U>::one(),
+            ),
+            QInstruction::Halt((halt, register)) => {
+                bytecode_message("halt", &halt.message, register.as_ref())
+            }
+            QInstruction::Print((print, register)) => {
+                bytecode_message("print", &print.message, register.as_ref())
+            }
+            QInstruction::PerformAlgorithm((puzzle_idx, alg)) => {
+                format!("perform puzzle={} alg={}", puzzle_idx.0, bytecode_alg(alg))
+            }
+            QInstruction::Solve(puzzle_idx) => format!("solve puzzle={}", puzzle_idx.0),
+            QInstruction::RepeatUntil(RepeatUntil {
+                puzzle_idx,
+                facelets,
+                alg,
+            }) => format!(
+                "repeat-until puzzle={} facelets={} alg={}",
+                puzzle_idx.0,
+                bytecode_facelets(facelets),
+                bytecode_alg(alg),
+            ),
+        };
+
+        writeln!(&mut out, "{i} {instr}").unwrap();
+    }
+
+    Some(out)
+}
+
+/// Report every theoretical register as an error since neither output format supports them. Returns whether any were found.
+fn report_theoretical(program: &Program, r: &Reporter) -> bool {
+    for theoretical in &program.theoretical {
+        r.push(
+            CompileError::build(ReportKind::Error, theoretical.span().clone())
+                .with_message("Cannot compile a QAT program with theoretical registers")
+                .finish(),
+        );
+    }
+
+    !program.theoretical.is_empty()
+}
+
+fn bytecode_message(
+    opcode: &str,
+    message: &str,
+    register: Option<&(PuzzleIdx, Algorithm, Facelets)>,
+) -> String {
+    let mut out = format!("{opcode} message=\"{}\"", escape_message(message));
+
+    if let Some((puzzle_idx, alg, facelets)) = register {
+        write!(
+            &mut out,
+            " puzzle={} alg={} facelets={}",
+            puzzle_idx.0,
+            bytecode_alg(alg),
+            bytecode_facelets(facelets),
+        )
+        .unwrap();
+    }
+
+    out
+}
+
+fn bytecode_alg(alg: &Algorithm) -> String {
+    format!("[{}]", alg.move_seq_iter().map(|v| &**v).join(","))
+}
+
+fn bytecode_facelets(facelets: &Facelets) -> String {
+    format!("[{}]", facelets.facelets().iter().join(","))
+}
+
+/// Escape a message so that it can be written as a string literal
+fn escape_message(message: &str) -> String {
+    message
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
+
 fn stringify_alg(alg: &Algorithm, padding: usize, pad_first: bool) -> String {
     let padding_str = " ".repeat(padding);
     split_strings(
@@ -0,0 +1,550 @@
+use std::sync::Arc;
+
+use ariadne::ReportKind;
+use internment::ArcIntern;
+use itertools::Itertools;
+use puzzle_theory::{
+    numbers::{Int, U, lcm_iter},
+    permutations::{Algorithm, PermutationGroup},
+    puzzle_geometry::PuzzleGeometry,
+    span::{File, Span, WithSpan},
+};
+use qter_core::{
+    ByPuzzleType, Facelets, Halt, Input, Instruction, Print, Program, PuzzleIdx, RepeatUntil,
+    SolvedGoto, architectures::chromatic_orders_by_facelets,
+};
+
+use crate::{CompileError, Reporter};
+
+/// A whitespace separated word or a string literal in an instruction
+struct Token {
+    text: String,
+    quoted: bool,
+    span: Span,
+}
+
+impl Token {
+    fn is(&self, word: &str) -> bool {
+        !self.quoted && self.text == word
+    }
+}
+
+/// Parse Q code, as emitted by `emit_q`, back into a `Program`.
+///
+/// Q names pieces rather than facelets, so the facelets of `solved-goto`, `counting-until`, and `repeat until` are all of the stickers of the pieces listed. The facelets of `input` are the ones that its algorithm moves. Q doesn't record the order of the register that `solved-goto` tests, so its order is zero, and it doesn't record the names of registers, so the program has no register metadata.
+///
+/// # Errors
+///
+/// Returns `None` and pushes errors to the reporter if the Q code is malformed, if it declares more than one puzzle, or if it refers to moves or pieces that the puzzle doesn't have.
+pub fn parse_q(q: &File, r: &Reporter) -> Option<Program> {
+    let before = r.count();
+    let source = q.inner();
+
+    let mut lines = source
+        .split_inclusive('\n')
+        .scan(0, |offset, line| {
+            let start = *offset;
+            *offset += line.len();
+            Some((start, line.trim_end()))
+        })
+        .filter(|(_, line)| !line.trim().is_empty());
+
+    let span_of = |start: usize, end: usize| Span::new(q.clone(), start, end);
+
+    match lines.next() {
+        Some((_, "Puzzles")) => {}
+        Some((start, line)) => {
+            r.push(
+                CompileError::build(ReportKind::Error, span_of(start, start + line.len()))
+                    .with_message("Expected Q code to start with `Puzzles`")
+                    .finish(),
+            );
+            return None;
+        }
+        None => {
+            r.push(
+                CompileError::build(ReportKind::Error, span_of(0, source.len()))
+                    .with_message("Expected Q code to start with `Puzzles`")
+                    .finish(),
+            );
+            return None;
+        }
+    }
+
+    let mut puzzles = Vec::<WithSpan<Arc<PermutationGroup>>>::new();
+    // The line number, the span of the number, and the byte range of the rest of the instruction
+    let mut chunks = Vec::<(usize, Span, usize, usize)>::new();
+
+    for (start, line) in lines {
+        let end = start + line.len();
+
+        if let Some((number, rest)) = line.split_once('|')
+            && let Ok(idx) = number.trim().parse::<usize>()
+        {
+            let number_start = start + (number.len() - number.trim_start().len());
+            let number_span = span_of(number_start, number_start + number.trim().len());
+            chunks.push((idx, number_span, end - rest.len(), end));
+            continue;
+        }
+
+        if let Some(chunk) = chunks.last_mut() {
+            chunk.3 = end;
+            continue;
+        }
+
+        let Some((_, definition)) = line.split_once(':') else {
+            r.push(
+                CompileError::build(ReportKind::Error, span_of(start, end))
+                    .with_message("Expected a puzzle declaration like `A: 3x3`")
+                    .finish(),
+            );
+            continue;
+        };
+
+        let definition_start = end - definition.trim_start().len();
+        let definition_span = span_of(definition_start, end);
+
+        match definition.trim().parse::<PuzzleGeometry>() {
+            Ok(geometry) => puzzles.push(definition_span.with(geometry.permutation_group())),
+            Err(errs) => r.push(
+                CompileError::build(ReportKind::Error, definition_span)
+                    .with_message(format!(
+                        "Invalid puzzle definition: {}",
+                        errs.iter().map(ToString::to_string).join(", ")
+                    ))
+                    .finish(),
+            ),
+        }
+    }
+
+    if puzzles.len() > 1 {
+        r.push(
+            CompileError::build(ReportKind::Error, puzzles[1].span().clone())
+                .with_message("Interpreting Q with multiple puzzles is unsupported (for now)")
+                .finish(),
+        );
+    }
+
+    let group = puzzles.first().map(|v| Arc::clone(v));
+
+    let mut instructions = Vec::new();
+
+    for (i, (idx, number_span, start, end)) in chunks.iter().enumerate() {
+        if *idx != i {
+            r.push(
+                CompileError::build(ReportKind::Error, number_span.clone())
+                    .with_message(format!("Expected instruction number {i}, found {idx}"))
+                    .finish(),
+            );
+        }
+
+        let span = number_span.clone().merge(&span_of(*end, *end));
+
+        let Some(tokens) = tokenize(q, *start, *end, r) else {
+            continue;
+        };
+
+        if let Some(instruction) = parse_instruction(&tokens, &span, group.as_ref(), r) {
+            instructions.push(span.with(instruction));
+        }
+    }
+
+    for instruction in &instructions {
+        if let Instruction::Goto { instruction_idx }
+        | Instruction::SolvedGoto(ByPuzzleType::Puzzle((
+            SolvedGoto { instruction_idx },
+            _,
+            _,
+        ))) = &**instruction
+            && *instruction_idx >= chunks.len()
+        {
+            r.push(
+                CompileError::build(ReportKind::Error, instruction.span().clone())
+                    .with_message(format!("There is no instruction {instruction_idx}"))
+                    .finish(),
+            );
+        }
+    }
+
+    if r.count() - before != 0 {
+        return None;
+    }
+
+    Some(Program {
+        theoretical: Vec::new(),
+        puzzles,
+        register_metadata: Vec::new(),
+        instructions,
+    })
+}
+
+/// Split an instruction into words and string literals
+fn tokenize(q: &File, start: usize, end: usize, r: &Reporter) -> Option<Vec<Token>> {
+    let source = q.inner();
+    let mut chars = source[start..end]
+        .char_indices()
+        .map(|(i, c)| (start + i, c))
+        .peekable();
+
+    let mut tokens = Vec::new();
+
+    while let Some((token_start, c)) = chars.next() {
+        if c.is_whitespace() {
+            continue;
+        }
+
+        let mut text = String::new();
+        let quoted = c == '"';
+
+        if quoted {
+            let mut closed = false;
+
+            while let Some((_, c)) = chars.next() {
+                match c {
+                    '"' => {
+                        closed = true;
+                        break;
+                    }
+                    '\\' => match chars.next() {
+                        Some((_, 'n')) => text.push('\n'),
+                        Some((_, c @ ('"' | '\\'))) => text.push(c),
+                        Some((i, c)) => {
+                            let span = Span::new(q.clone(), i - 1, i + c.len_utf8());
+                            r.push(
+                                CompileError::build(ReportKind::Error, span)
+                                    .with_message(format!("Unknown escape sequence `\\{c}`"))
+                                    .finish(),
+                            );
+                            return None;
+                        }
+                        None => break,
+                    },
+                    c => text.push(c),
+                }
+            }
+
+            if !closed {
+                r.push(
+                    CompileError::build(ReportKind::Error, Span::new(q.clone(), token_start, end))
+                        .with_message("Unterminated string")
+                        .finish(),
+                );
+                return None;
+            }
+        } else {
+            text.push(c);
+
+            while let Some((_, c)) = chars.next_if(|(_, c)| !c.is_whitespace()) {
+                text.push(c);
+            }
+        }
+
+        let token_end = chars.peek().map_or(end, |(i, _)| *i);
+
+        tokens.push(Token {
+            text,
+            quoted,
+            span: Span::new(q.clone(), token_start, token_end),
+        });
+    }
+
+    Some(tokens)
+}
+
+fn parse_instruction(
+    tokens: &[Token],
+    span: &Span,
+    group: Option<&Arc<PermutationGroup>>,
+    r: &Reporter,
+) -> Option<Instruction> {
+    let error = |span: &Span, message: &str| {
+        r.push(
+            CompileError::build(ReportKind::Error, span.clone())
+                .with_message(message)
+                .finish(),
+        );
+    };
+
+    // Every instruction other than `goto` acts on the puzzle
+    let group = || {
+        if group.is_none() {
+            error(span, "This instruction needs a puzzle to act on");
+        }
+
+        group
+    };
+
+    let Some(first) = tokens.first() else {
+        // An empty algorithm does nothing
+        return Some(Instruction::PerformAlgorithm(ByPuzzleType::Puzzle((
+            PuzzleIdx(0),
+            parse_alg(&[], span, group()?, r)?,
+        ))));
+    };
+
+    if first.quoted {
+        error(&first.span, "Expected an instruction");
+        return None;
+    }
+
+    Some(match &*first.text {
+        "goto" => {
+            let [_, target] = tokens else {
+                error(span, "Expected `goto <instruction>`");
+                return None;
+            };
+
+            Instruction::Goto {
+                instruction_idx: parse_number(target, r)?,
+            }
+        }
+        "solved-goto" => {
+            let [_, pieces @ .., target] = tokens else {
+                error(span, "Expected `solved-goto <pieces> <instruction>`");
+                return None;
+            };
+
+            let group = group()?;
+            let instruction_idx = parse_number(target, r)?;
+            let facelets = facelets_of_pieces(pieces, span, group, None, r)?;
+
+            Instruction::SolvedGoto(ByPuzzleType::Puzzle((
+                SolvedGoto { instruction_idx },
+                PuzzleIdx(0),
+                facelets,
+            )))
+        }
+        "input" => {
+            let syntax = "Expected `input \"<message>\" <algorithm> max-input <number>`";
+
+            let [_, message, alg @ .., max_input_kw, max_input] = tokens else {
+                error(span, syntax);
+                return None;
+            };
+
+            if !message.quoted || !max_input_kw.is("max-input") {
+                error(span, syntax);
+                return None;
+            }
+
+            let alg = parse_alg(alg, span, group()?, r)?;
+            let max_input = parse_number::<Int<U>>(max_input, r)?;
+            let facelets = moved_facelets(&alg);
+
+            if facelets.order() != max_input + Int::<U>::one() {
+                error(
+                    &max_input.span,
+                    &format!(
+                        "The algorithm can only count to {}",
+                        facelets.order() - Int::<U>::one()
+                    ),
+                );
+                return None;
+            }
+
+            Instruction::Input(ByPuzzleType::Puzzle((
+                Input {
+                    message: message.text.clone(),
+                },
+                PuzzleIdx(0),
+                alg,
+                facelets,
+            )))
+        }
+        "halt" | "print" => {
+            let Some(message) = tokens.get(1).filter(|v| v.quoted) else {
+                error(span, &format!("Expected `{} \"<message>\"`", first.text));
+                return None;
+            };
+
+            let register = match &tokens[2..] {
+                [] => None,
+                rest => {
+                    let Some(counting_until) = rest.iter().position(|v| v.is("counting-until"))
+                    else {
+                        error(span, "Expected `counting-until <pieces>`");
+                        return None;
+                    };
+
+                    let group = group()?;
+
+                    // Q performs the inverse of the register's generator while counting
+                    let mut alg = parse_alg(&rest[..counting_until], span, group, r)?;
+                    alg.exponentiate(-Int::<U>::one());
+
+                    let pieces = &rest[counting_until + 1..];
+                    let facelets = facelets_of_pieces(pieces, span, group, Some(&alg), r)?;
+
+                    Some((PuzzleIdx(0), alg, facelets))
+                }
+            };
+
+            let message = message.text.clone();
+
+            if first.text == "halt" {
+                Instruction::Halt(ByPuzzleType::Puzzle((Halt { message }, register)))
+            } else {
+                Instruction::Print(ByPuzzleType::Puzzle((Print { message }, register)))
+            }
+        }
+        "solve" => {
+            if tokens.len() != 1 {
+                error(span, "Expected `solve`");
+                return None;
+            }
+
+            group()?;
+
+            Instruction::Solve(ByPuzzleType::Puzzle(PuzzleIdx(0)))
+        }
+        "repeat" => {
+            let solved = tokens.iter().position(|v| v.is("solved"));
+
+            let Some(solved) = solved.filter(|_| tokens.get(1).is_some_and(|v| v.is("until")))
+            else {
+                error(span, "Expected `repeat until <pieces> solved <algorithm>`");
+                return None;
+            };
+
+            let group = group()?;
+            let alg = parse_alg(&tokens[solved + 1..], span, group, r)?;
+            let facelets = facelets_of_pieces(&tokens[2..solved], span, group, Some(&alg), r)?;
+
+            Instruction::RepeatUntil(ByPuzzleType::Puzzle(RepeatUntil {
+                puzzle_idx: PuzzleIdx(0),
+                facelets,
+                alg,
+            }))
+        }
+        _ => Instruction::PerformAlgorithm(ByPuzzleType::Puzzle((
+            PuzzleIdx(0),
+            parse_alg(tokens, span, group()?, r)?,
+        ))),
+    })
+}
+
+fn parse_number<T: std::str::FromStr>(token: &Token, r: &Reporter) -> Option<T> {
+    match token.text.parse::<T>() {
+        Ok(v) if !token.quoted => Some(v),
+        _ => {
+            r.push(
+                CompileError::build(ReportKind::Error, token.span.clone())
+                    .with_message(format!("Expected a number, found `{}`", token.text))
+                    .finish(),
+            );
+            None
+        }
+    }
+}
+
+fn parse_alg(
+    tokens: &[Token],
+    span: &Span,
+    group: &Arc<PermutationGroup>,
+    r: &Reporter,
+) -> Option<Algorithm> {
+    let span = match (tokens.first(), tokens.last()) {
+        (Some(first), Some(last)) => first.span.clone().merge(&last.span),
+        _ => span.clone(),
+    };
+
+    if let Some(token) = tokens.iter().find(|v| v.quoted) {
+        r.push(
+            CompileError::build(ReportKind::Error, token.span.clone())
+                .with_message("Expected a move")
+                .finish(),
+        );
+        return None;
+    }
+
+    match Algorithm::new_from_move_seq(
+        Arc::clone(group),
+        tokens.iter().map(|v| ArcIntern::from(&*v.text)).collect(),
+    ) {
+        Ok(alg) => Some(alg),
+        Err(bad_move) => {
+            r.push(
+                CompileError::build(ReportKind::Error, span)
+                    .with_message(format!("The move `{bad_move}` doesn't exist on this puzzle"))
+                    .finish(),
+            );
+            None
+        }
+    }
+}
+
+/// Every sticker of the pieces listed. The order is how many times `alg` has to be performed to cycle all of the stickers back home, or zero if there's no algorithm.
+fn facelets_of_pieces(
+    pieces: &[Token],
+    span: &Span,
+    group: &Arc<PermutationGroup>,
+    alg: Option<&Algorithm>,
+    r: &Reporter,
+) -> Option<Facelets> {
+    if pieces.is_empty() {
+        r.push(
+            CompileError::build(ReportKind::Error, span.clone())
+                .with_message("Expected at least one piece")
+                .finish(),
+        );
+        return None;
+    }
+
+    let mut facelets = Vec::new();
+
+    for piece in pieces {
+        let before = facelets.len();
+
+        facelets.extend(
+            group
+                .piece_assignments()
+                .iter()
+                .positions(|v| !piece.quoted && **v == *piece.text),
+        );
+
+        if facelets.len() == before {
+            r.push(
+                CompileError::build(ReportKind::Error, piece.span.clone())
+                    .with_message(format!(
+                        "The piece `{}` doesn't exist on this puzzle",
+                        piece.text
+                    ))
+                    .finish(),
+            );
+            return None;
+        }
+    }
+
+    let order = match alg {
+        Some(alg) => {
+            let chromatic_orders = chromatic_orders_by_facelets(alg);
+            lcm_iter(facelets.iter().map(|&i| chromatic_orders[i]))
+        }
+        None => Int::<U>::zero(),
+    };
+
+    Some(Facelets::new(
+        facelets,
+        pieces.iter().map(|v| ArcIntern::from(&*v.text)).collect(),
+        order,
+    ))
+}
+
+/// The stickers that an algorithm moves, along with the pieces that they belong to
+fn moved_facelets(alg: &Algorithm) -> Facelets {
+    let chromatic_orders = chromatic_orders_by_facelets(alg);
+
+    let facelets = (0..chromatic_orders.len())
+        .filter(|&i| chromatic_orders[i] != Int::<U>::one())
+        .collect_vec();
+
+    let pieces = facelets
+        .iter()
+        .map(|&i| ArcIntern::clone(&alg.group().piece_assignments()[i]))
+        .unique()
+        .collect();
+
+    let order = lcm_iter(facelets.iter().map(|&i| chromatic_orders[i]));
+
+    Facelets::new(facelets, pieces, order)
+}
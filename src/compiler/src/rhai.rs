@@ -1,6 +1,6 @@
 use std::{fmt::Debug, sync::OnceLock};
 
-use ariadne::{Report, ReportKind};
+use ariadne::ReportKind;
 use internment::ArcIntern;
 use itertools::Itertools;
 use puzzle_theory::{
@@ -10,8 +10,8 @@ use puzzle_theory::{
 use rhai::{AST, Array, CustomType, Dynamic, Engine, ImmutableString, ParseError, Scope};
 
 use crate::{
-    Block, Code, ExpansionInfo, Instruction, MacroBranchKey, MacroCall, RegisterInfo, Reporter,
-    ResolvedValue, Value,
+    Block, Code, CompileError, ExpansionInfo, Instruction, MacroBranchKey, MacroCall, Reporter,
+    ResolvedRegister, ResolvedValue, Value,
 };
 
 thread_local! {
@@ -67,7 +67,7 @@ fn register_op<T: rhai::Variant + Clone>(
 }
 
 #[derive(Clone, Debug)]
-struct WRegisterInfo(RegisterInfo);
+struct WRegisterInfo(ResolvedRegister);
 
 impl CustomType for WRegisterInfo {
     fn build(mut builder: rhai::TypeBuilder<Self>) {
@@ -128,7 +128,7 @@ impl RhaiMacros {
             Ok(v) => v,
             Err(e) => {
                 r.push(
-                    Report::build(ReportKind::Error, span)
+                    CompileError::build(ReportKind::Error, span)
                         .with_message(e.to_string())
                         .finish(),
                 );
@@ -170,7 +170,7 @@ fn from_rhai(
     if let Ok(int) = value.as_int() {
         let Ok(v) = u64::try_from(int) else {
             r.push(
-                Report::build(ReportKind::Error, span)
+                CompileError::build(ReportKind::Error, span)
                     .with_message(format!("Integer values must be positive. Found {int}"))
                     .finish(),
             );
@@ -184,7 +184,7 @@ fn from_rhai(
         Ok(WInt(int)) => {
             if int < Int::<I>::zero() {
                 r.push(
-                    Report::build(ReportKind::Error, span)
+                    CompileError::build(ReportKind::Error, span)
                         .with_message(format!("Integer values must be positive. Found {int}"))
                         .finish(),
                 );
@@ -239,7 +239,7 @@ fn try_decode_block(
         Ok(v) => v,
         Err(value) => {
             r.push(
-                Report::build(ReportKind::Error, span.clone())
+                CompileError::build(ReportKind::Error, span.clone())
                     .with_message(format!(
                         "Unable to interpret `{value}` as a positive integer, identifier, register, or code block."
                     ))
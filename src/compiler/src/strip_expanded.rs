@@ -1,29 +1,32 @@
 use std::{collections::HashMap, sync::Arc};
 
-use ariadne::{Report, ReportKind};
+use ariadne::{Label, ReportKind};
 use internment::ArcIntern;
 use itertools::Itertools;
 use puzzle_theory::{
-    numbers::{Int, U},
+    numbers::{Int, U, lcm},
     permutations::PermutationGroup,
-    span::WithSpan,
+    span::{Span, WithSpan},
 };
 use qter_core::{
     ByPuzzleType, Facelets, Halt, Input, Instruction, Print, Program, PuzzleIdx, RegisterGenerator,
-    RepeatUntil, SeparatesByPuzzleType, StateIdx, TheoreticalIdx,
+    RegisterMetadata, RepeatUntil, SeparatesByPuzzleType, StateIdx, TheoreticalIdx,
     architectures::{Architecture, CycleGeneratorSubcycle, new_from_effect},
 };
 
 use crate::{
-    ExpandedCode, ExpandedCodeComponent, LabelReference, Primitive, Puzzle, RegisterReference,
-    Reporter,
-    optimization::{OptimizingCodeComponent, OptimizingPrimitive, do_optimization},
+    BlockInfoTracker, CompileError, CompileProgress, ExpandedCode, ExpandedCodeComponent,
+    LabelReference, Primitive, Puzzle, RegisterReference, Reporter,
+    optimization::{
+        OptimizationPipeline, OptimizingCodeComponent, OptimizingPrimitive, do_optimization,
+        warn_cancelling_adds, warn_dead_adds,
+    },
 };
 
 pub(super) struct RegisterIdx;
 
 impl SeparatesByPuzzleType for RegisterIdx {
-    type Theoretical<'s> = ();
+    type Theoretical<'s> = Option<Int<U>>;
 
     type Puzzle<'s> = (usize, Arc<Architecture>, Option<Int<U>>);
 }
@@ -32,6 +35,10 @@ pub struct GlobalRegs {
     register_table: HashMap<ArcIntern<str>, ByPuzzleType<'static, (StateIdx, RegisterIdx)>>,
     theoretical: Vec<WithSpan<Int<U>>>,
     puzzles: Vec<WithSpan<Arc<PermutationGroup>>>,
+    /// The architecture that each puzzle starts out with
+    architectures: Vec<Arc<Architecture>>,
+    /// Every declared register, in the order that they were declared
+    registers: Vec<RegisterMetadata>,
 }
 
 impl GlobalRegs {
@@ -47,14 +54,18 @@ impl GlobalRegs {
 
         if let Some(mod_) = reference.modulus {
             match &mut reg {
-                ByPuzzleType::Theoretical(_) => todo!(),
-                ByPuzzleType::Puzzle((_, (_, _, modulus))) => *modulus = Some(mod_),
+                ByPuzzleType::Theoretical((_, modulus))
+                | ByPuzzleType::Puzzle((_, (_, _, modulus))) => *modulus = Some(mod_),
             }
         }
 
         reg
     }
 
+    pub(super) fn theoretical_order(&self, idx: TheoreticalIdx) -> Int<U> {
+        *self.theoretical[idx.0]
+    }
+
     fn generator(
         &self,
         register: &RegisterReference,
@@ -63,7 +74,7 @@ impl GlobalRegs {
         let reg_info = self.get_reg(register);
 
         match reg_info {
-            ByPuzzleType::Theoretical((theoretical, ())) => {
+            ByPuzzleType::Theoretical((theoretical, _)) => {
                 Some(ByPuzzleType::Theoretical((theoretical, ())))
             }
             ByPuzzleType::Puzzle((puzzle_idx, (idx, arch, modulus))) => {
@@ -86,14 +97,250 @@ impl GlobalRegs {
         let reg_info = self.get_reg(register);
 
         match reg_info {
-            ByPuzzleType::Theoretical((theoretical_idx, ())) => {
-                Some(ByPuzzleType::Theoretical(theoretical_idx))
+            ByPuzzleType::Theoretical((theoretical_idx, modulus)) => {
+                if modulus.is_some_and(|modulus| modulus.is_zero()) {
+                    r.push(
+                        CompileError::build(ReportKind::Error, register.reg_name.span().clone())
+                            .with_message("The modulus of a register must not be zero")
+                            .finish(),
+                    );
+                    return None;
+                }
+
+                Some(ByPuzzleType::Theoretical((theoretical_idx, modulus)))
             }
             ByPuzzleType::Puzzle((puzzle_idx, (idx, arch, modulus))) => Some(ByPuzzleType::Puzzle(
                 (puzzle_idx, get_facelets(idx, &arch, modulus, register, r)?),
             )),
         }
     }
+
+    /// The order of the register as declared, ignoring any modulus in the reference
+    fn declared_order(&self, register: &RegisterReference) -> Int<U> {
+        match self.register_table.get(&register.reg_name).unwrap() {
+            ByPuzzleType::Theoretical((theoretical_idx, _)) => {
+                *self.theoretical[theoretical_idx.0]
+            }
+            ByPuzzleType::Puzzle((_, (idx, arch, _))) => arch.registers()[*idx].order(),
+        }
+    }
+}
+
+/// What is known about a register's value at some point in straight-line code
+#[derive(Clone)]
+enum KnownValue {
+    /// The register holds this value
+    Constant(Int<U>),
+    /// The register holds an input that can be at most `max_input`, plus `offset`
+    Input {
+        max_input: Int<U>,
+        offset: Int<U>,
+        span: Span,
+    },
+}
+
+/// Warn about `solved-goto`s that can never jump because the value that they compare an input against is larger than the input allows. Comparing `A` against `n` is done by adding a constant to `A` and using `solved-goto`, which only jumps if the input was `n`, so if `n` is above the maximum input then the jump is dead.
+///
+/// This only follows straight-line code. Anything that a label could be jumped to from is treated as unknown, as is a register after it is used in a way that this doesn't model.
+fn warn_out_of_range_comparisons(
+    code: &[WithSpan<ExpandedCodeComponent>],
+    global_regs: &GlobalRegs,
+    r: &Reporter,
+) {
+    // Every register starts out at zero; registers missing from the map have unknown values
+    let mut known = global_regs
+        .register_table
+        .keys()
+        .map(|name| (ArcIntern::clone(name), KnownValue::Constant(Int::<U>::zero())))
+        .collect::<HashMap<_, _>>();
+
+    for component in code {
+        let instr = match &**component {
+            ExpandedCodeComponent::Label(_) => {
+                known.clear();
+                continue;
+            }
+            ExpandedCodeComponent::Instruction(instr, _) => instr,
+        };
+
+        match &**instr {
+            Primitive::Input { register, .. } => {
+                let order = global_regs.declared_order(register);
+                let input_order = match global_regs.get_reg(register) {
+                    ByPuzzleType::Theoretical(_) => order,
+                    ByPuzzleType::Puzzle((_, (_, _, modulus))) => modulus.unwrap_or(order),
+                };
+
+                match known.get(&*register.reg_name) {
+                    Some(KnownValue::Constant(offset)) if !input_order.is_zero() => {
+                        let value = KnownValue::Input {
+                            max_input: input_order - Int::<U>::one(),
+                            offset: *offset,
+                            span: component.span().clone(),
+                        };
+                        known.insert(ArcIntern::clone(&register.reg_name), value);
+                    }
+                    _ => {
+                        known.remove(&*register.reg_name);
+                    }
+                }
+            }
+            Primitive::Add { amt, register } => {
+                let order = global_regs.declared_order(register);
+
+                if register.modulus.is_some() || order.is_zero() {
+                    known.remove(&*register.reg_name);
+                    continue;
+                }
+
+                match known.get_mut(&*register.reg_name) {
+                    Some(KnownValue::Constant(value) | KnownValue::Input { offset: value, .. }) => {
+                        *value = (*value + **amt % order) % order;
+                    }
+                    None => {}
+                }
+            }
+            Primitive::SolvedGoto { register, .. } => {
+                let Some(KnownValue::Input {
+                    max_input,
+                    offset,
+                    span,
+                }) = known.get(&*register.reg_name)
+                else {
+                    continue;
+                };
+
+                let modulus = register
+                    .modulus
+                    .unwrap_or_else(|| global_regs.declared_order(register));
+
+                if modulus.is_zero() {
+                    continue;
+                }
+
+                // The smallest input for which `input + offset` is a multiple of the modulus
+                let needed = (modulus - *offset % modulus) % modulus;
+
+                if needed > *max_input {
+                    r.push(
+                        CompileError::build(ReportKind::Warning, component.span().clone())
+                            .with_message(format!(
+                                "This can never jump because it only jumps when the input to `{}` is {needed}",
+                                &**register.reg_name
+                            ))
+                            .with_label(
+                                Label::new(span.clone()).with_message(format!(
+                                    "This input only accepts values up to {max_input}"
+                                )),
+                            )
+                            .finish(),
+                    );
+                }
+            }
+            Primitive::Goto { .. } | Primitive::Halt { .. } | Primitive::Switch { .. } => {
+                known.clear();
+            }
+            Primitive::SolvedGotoAll { .. } | Primitive::Print { .. } => {}
+        }
+    }
+}
+
+/// Check that instructions only use registers of the architecture that is active where they appear. The active architecture of a puzzle is the one that the closest `switch` above switched to, or the first one declared if there is no such `switch`. Since `switch` solves the puzzle, falling through it is always fine, but jumping to a label where a different architecture is active would read the puzzle with the wrong architecture.
+fn check_architecture_switches(
+    code: &[WithSpan<ExpandedCodeComponent>],
+    block_info: &BlockInfoTracker,
+    global_regs: &GlobalRegs,
+    r: &Reporter,
+) {
+    let mut active = global_regs.architectures.clone();
+    let mut label_architectures = HashMap::new();
+    let mut jumps = Vec::new();
+
+    for component in code {
+        let (registers, label) = match &**component {
+            ExpandedCodeComponent::Label(label) => {
+                label_architectures.insert(
+                    LabelReference {
+                        name: ArcIntern::clone(&label.name),
+                        block_id: label.maybe_block_id.unwrap(),
+                        branch_key: label.branch_key,
+                    },
+                    active.clone(),
+                );
+                continue;
+            }
+            ExpandedCodeComponent::Instruction(instr, _) => match &**instr {
+                Primitive::Add { register, .. } | Primitive::Input { register, .. } => {
+                    (vec![register], None)
+                }
+                Primitive::Halt { register, .. } | Primitive::Print { register, .. } => {
+                    (register.iter().collect(), None)
+                }
+                Primitive::Goto { label } => (vec![], Some(label)),
+                Primitive::SolvedGoto { label, register } => (vec![register], Some(label)),
+                Primitive::SolvedGotoAll { label, registers } => {
+                    (registers.iter().collect(), Some(label))
+                }
+                Primitive::Switch { register } => {
+                    if let ByPuzzleType::Puzzle((puzzle_idx, (_, arch, _))) =
+                        global_regs.get_reg(register)
+                    {
+                        active[puzzle_idx.0] = arch;
+                    }
+                    continue;
+                }
+            },
+        };
+
+        for register in registers {
+            let ByPuzzleType::Puzzle((puzzle_idx, (_, arch, _))) = global_regs.get_reg(register)
+            else {
+                continue;
+            };
+
+            if !Arc::ptr_eq(&arch, &active[puzzle_idx.0]) {
+                r.push(
+                    CompileError::build(ReportKind::Error, register.reg_name.span().clone())
+                        .with_message(format!(
+                            "`{}` can't be used here because a different architecture is active on its puzzle",
+                            &**register.reg_name
+                        ))
+                        .with_help(format!(
+                            "Use `switch {}` first, which solves the puzzle",
+                            &**register.reg_name
+                        ))
+                        .finish(),
+                );
+            }
+        }
+
+        if let Some(label) = label
+            && let Some(target) = block_info.label_scope(label)
+        {
+            jumps.push((label.span().clone(), target, active.clone()));
+        }
+    }
+
+    for (span, target, active) in jumps {
+        let Some(target_active) = label_architectures.get(&target) else {
+            continue;
+        };
+
+        if active
+            .iter()
+            .zip(target_active)
+            .any(|(arch, target_arch)| !Arc::ptr_eq(arch, target_arch))
+        {
+            r.push(
+                CompileError::build(ReportKind::Error, span)
+                    .with_message("This jumps to a label where a different architecture is active")
+                    .with_help(
+                        "Switch to the same architectures that are active at the label first",
+                    )
+                    .finish(),
+            );
+        }
+    }
 }
 
 fn get_facelets(
@@ -117,7 +364,7 @@ fn get_facelets(
                     .collect_vec();
 
                 r.push(
-                    Report::build(ReportKind::Error, register.reg_name.span().clone())
+                    CompileError::build(ReportKind::Error, register.reg_name.span().clone())
                         .with_message(format!(
                             "Could not find a set of pieces for solved-goto that encode the given modulus. The available moduli are the LCM of any combination of the following piece subcycles: {}",
                             cycles.into_iter().join(", ")
@@ -131,57 +378,134 @@ fn get_facelets(
     }
 }
 
+/// Combine the facelets of every register so that they can all be checked with one observation of the puzzle. The registers must all be stored on the same puzzle.
+fn combined_facelets(
+    global_regs: &GlobalRegs,
+    registers: &[RegisterReference],
+    r: &Reporter,
+) -> Option<(PuzzleIdx, Facelets)> {
+    let mut puzzle_idx = None;
+    let mut facelets = Vec::new();
+    let mut pieces = Vec::new();
+    let mut order = Int::<U>::one();
+
+    for register in registers {
+        let (idx, register_facelets) = match global_regs.facelets(register, r)? {
+            ByPuzzleType::Theoretical(_) => {
+                r.push(
+                    CompileError::build(ReportKind::Error, register.reg_name.span().clone())
+                        .with_message(
+                            "Theoretical registers can't be checked together with other registers",
+                        )
+                        .finish(),
+                );
+                return None;
+            }
+            ByPuzzleType::Puzzle(v) => v,
+        };
+
+        if puzzle_idx.is_some_and(|puzzle_idx| puzzle_idx != idx) {
+            r.push(
+                CompileError::build(ReportKind::Error, register.reg_name.span().clone())
+                    .with_message("Registers that are checked together must be on the same puzzle")
+                    .finish(),
+            );
+            return None;
+        }
+
+        puzzle_idx = Some(idx);
+        facelets.extend_from_slice(register_facelets.facelets());
+        pieces.extend_from_slice(register_facelets.pieces());
+        order = lcm(order, register_facelets.order());
+    }
+
+    let facelets = facelets.into_iter().unique().collect();
+    let pieces = pieces.into_iter().unique().collect();
+
+    Some((puzzle_idx?, Facelets::new(facelets, pieces, order)))
+}
+
 struct FaceletsInfo;
 
 impl SeparatesByPuzzleType for FaceletsInfo {
-    type Theoretical<'s> = TheoreticalIdx;
+    type Theoretical<'s> = (TheoreticalIdx, Option<Int<U>>);
 
     type Puzzle<'s> = (PuzzleIdx, Facelets);
 }
 
-pub fn strip_expanded(expanded: ExpandedCode, r: &Reporter) -> Option<Program> {
+pub fn strip_expanded(
+    expanded: ExpandedCode,
+    pipeline: &OptimizationPipeline,
+    r: &Reporter,
+    progress: &mut dyn FnMut(CompileProgress),
+) -> Option<Program> {
     let mut global_regs = GlobalRegs {
         register_table: HashMap::new(),
         theoretical: vec![],
         puzzles: vec![],
+        architectures: vec![],
+        registers: vec![],
     };
 
     for puzzle in &expanded.registers.puzzles {
         match puzzle {
             Puzzle::Theoretical { name, order } => {
+                let theoretical_idx = TheoreticalIdx(global_regs.theoretical.len());
+
                 global_regs.register_table.insert(
                     ArcIntern::clone(name),
-                    ByPuzzleType::Theoretical((TheoreticalIdx(global_regs.theoretical.len()), ())),
+                    ByPuzzleType::Theoretical((theoretical_idx, None)),
                 );
+                global_regs.registers.push(RegisterMetadata {
+                    name: ArcIntern::clone(name),
+                    order: **order,
+                    storage: ByPuzzleType::Theoretical(theoretical_idx),
+                });
 
                 global_regs.theoretical.push(order.to_owned());
             }
             Puzzle::Real { architectures } => {
-                // TODO: Support for architecture switching
-                // Just take the first architecture
-                let (names, architecture, puzzle_span) = &architectures[0];
-                for (i, name) in names.iter().enumerate() {
-                    global_regs.register_table.insert(
-                        ArcIntern::clone(name),
-                        ByPuzzleType::Puzzle((
-                            PuzzleIdx(global_regs.puzzles.len()),
-                            (i, Arc::clone(architecture), None),
-                        )),
-                    );
+                let puzzle_idx = PuzzleIdx(global_regs.puzzles.len());
+
+                for (names, architecture, _) in architectures {
+                    for (i, name) in names.iter().enumerate() {
+                        global_regs.register_table.insert(
+                            ArcIntern::clone(name),
+                            ByPuzzleType::Puzzle((puzzle_idx, (i, Arc::clone(architecture), None))),
+                        );
+                        global_regs.registers.push(RegisterMetadata {
+                            name: ArcIntern::clone(name),
+                            order: architecture.registers()[i].order(),
+                            storage: ByPuzzleType::Puzzle(puzzle_idx),
+                        });
+                    }
                 }
 
+                // Programs start out using the first architecture
+                let (_, architecture, puzzle_span) = &architectures[0];
+
                 global_regs
                     .puzzles
                     .push(WithSpan::new(architecture.group_arc(), puzzle_span.clone()));
+                global_regs.architectures.push(Arc::clone(architecture));
             }
         }
     }
 
+    warn_out_of_range_comparisons(&expanded.expanded_code_components, &global_regs, r);
+
     let global_regs = Arc::new(global_regs);
     let global_regs_for_iter = Arc::clone(&global_regs);
 
     let before = r.count();
 
+    check_architecture_switches(
+        &expanded.expanded_code_components,
+        &expanded.block_info,
+        &global_regs,
+        r,
+    );
+
     let instructions_mapped = expanded
         .expanded_code_components
         .into_iter()
@@ -193,7 +517,7 @@ pub fn strip_expanded(expanded: ExpandedCode, r: &Reporter) -> Option<Program> {
                         Box::new(match *primitive {
                             Primitive::Add { amt, register } => {
                                 match global_regs_for_iter.get_reg(&register) {
-                                    ByPuzzleType::Theoretical((theoretical, ())) => {
+                                    ByPuzzleType::Theoretical((theoretical, _)) => {
                                         OptimizingPrimitive::AddTheoretical { theoretical, amt }
                                     }
                                     ByPuzzleType::Puzzle((puzzle, (reg_idx, arch, _))) => {
@@ -209,7 +533,7 @@ pub fn strip_expanded(expanded: ExpandedCode, r: &Reporter) -> Option<Program> {
                                 let span = label.span().clone();
                                 let Some(label) = expanded.block_info.label_scope(&label) else {
                                     r.push(
-                                        Report::build(ReportKind::Error, label.span().clone())
+                                        CompileError::build(ReportKind::Error, label.span().clone())
                                             .with_message("Could not find label in scope")
                                             .finish(),
                                     );
@@ -224,7 +548,7 @@ pub fn strip_expanded(expanded: ExpandedCode, r: &Reporter) -> Option<Program> {
                                 let span = label.span().clone();
                                 let Some(label) = expanded.block_info.label_scope(&label) else {
                                     r.push(
-                                        Report::build(ReportKind::Error, label.span().clone())
+                                        CompileError::build(ReportKind::Error, label.span().clone())
                                             .with_message("Could not find label in scope")
                                             .finish(),
                                     );
@@ -236,6 +560,22 @@ pub fn strip_expanded(expanded: ExpandedCode, r: &Reporter) -> Option<Program> {
                                     register,
                                 }
                             }
+                            Primitive::SolvedGotoAll { label, registers } => {
+                                let span = label.span().clone();
+                                let Some(label) = expanded.block_info.label_scope(&label) else {
+                                    r.push(
+                                        CompileError::build(ReportKind::Error, label.span().clone())
+                                            .with_message("Could not find label in scope")
+                                            .finish(),
+                                    );
+                                    return None;
+                                };
+
+                                OptimizingPrimitive::SolvedGotoAll {
+                                    label: span.with(label),
+                                    registers,
+                                }
+                            }
                             Primitive::Input { message, register } => {
                                 OptimizingPrimitive::Input { message, register }
                             }
@@ -245,6 +585,29 @@ pub fn strip_expanded(expanded: ExpandedCode, r: &Reporter) -> Option<Program> {
                             Primitive::Print { message, register } => {
                                 OptimizingPrimitive::Print { message, register }
                             }
+                            Primitive::Switch { register } => {
+                                match global_regs_for_iter.get_reg(&register) {
+                                    ByPuzzleType::Theoretical(_) => {
+                                        r.push(
+                                            CompileError::build(
+                                                ReportKind::Error,
+                                                register.reg_name.span().clone(),
+                                            )
+                                            .with_message(
+                                                "Theoretical registers don't have architectures to switch between",
+                                            )
+                                            .finish(),
+                                        );
+                                        return None;
+                                    }
+                                    // Values don't carry over, so switching solves the puzzle
+                                    ByPuzzleType::Puzzle((puzzle, _)) => {
+                                        OptimizingPrimitive::Solve {
+                                            puzzle: ByPuzzleType::Puzzle(puzzle),
+                                        }
+                                    }
+                                }
+                            }
                         }),
                         block_id,
                     )
@@ -260,7 +623,15 @@ pub fn strip_expanded(expanded: ExpandedCode, r: &Reporter) -> Option<Program> {
         return None;
     }
 
-    let optimized = do_optimization(instructions_mapped.into_iter(), &global_regs);
+    warn_cancelling_adds(&instructions_mapped, &global_regs, r);
+
+    let optimized =
+        do_optimization(instructions_mapped.into_iter(), &global_regs, pipeline, progress)
+            .collect_vec();
+
+    progress(CompileProgress::Emitting);
+
+    warn_dead_adds(&optimized, r);
 
     let mut program_counter = 0;
 
@@ -325,14 +696,27 @@ pub fn strip_expanded(expanded: ExpandedCode, r: &Reporter) -> Option<Program> {
                     };
 
                     Instruction::SolvedGoto(match facelets {
-                        ByPuzzleType::Theoretical(theoretical_idx) => {
-                            ByPuzzleType::Theoretical((solved_goto, theoretical_idx))
+                        ByPuzzleType::Theoretical((theoretical_idx, modulus)) => {
+                            ByPuzzleType::Theoretical((solved_goto, theoretical_idx, modulus))
                         }
                         ByPuzzleType::Puzzle((puzzle_idx, facelets)) => {
                             ByPuzzleType::Puzzle((solved_goto, puzzle_idx, facelets))
                         }
                     })
                 }
+                OptimizingPrimitive::SolvedGotoAll { registers, label } => {
+                    let (puzzle_idx, facelets) = combined_facelets(&global_regs, &registers, r)?;
+
+                    let solved_goto = qter_core::SolvedGoto {
+                        instruction_idx: *label_locations.get(&label).unwrap(),
+                    };
+
+                    Instruction::SolvedGoto(ByPuzzleType::Puzzle((
+                        solved_goto,
+                        puzzle_idx,
+                        facelets,
+                    )))
+                }
                 OptimizingPrimitive::RepeatUntil {
                     puzzle,
                     arch,
@@ -432,6 +816,7 @@ pub fn strip_expanded(expanded: ExpandedCode, r: &Reporter) -> Option<Program> {
     Some(Program {
         theoretical: global_regs.theoretical,
         puzzles: global_regs.puzzles,
+        register_metadata: global_regs.registers,
         instructions,
     })
 }
@@ -0,0 +1,537 @@
+//! Assemble qter architectures out of the generators found by the cycle
+//! structure solver.
+
+use std::{
+    collections::{BTreeMap, HashMap, HashSet},
+    sync::Arc,
+};
+
+use generativity::make_guard;
+use itertools::Itertools;
+use log::info;
+use puzzle_theory::{
+    numbers::{Int, U},
+    permutations::PermutationGroup,
+    puzzle_geometry::PuzzleGeometry,
+};
+use qter_core::architectures::{ArchValidationError, Architecture};
+use thiserror::Error;
+
+use crate::{
+    pruning::{
+        OrbitPruningTableGenerationError, OrbitPruningTables, OrbitPruningTablesGenerateMeta,
+        PruningTables,
+    },
+    puzzle::{
+        KSolveConversionError, OrbitIdentifier, PuzzleDef, PuzzleState, SortedCycleStructure,
+        SortedCycleStructureCreationError, slice_puzzle::HeapPuzzle,
+    },
+    solver::{CycleStructureSolver, CycleStructureSolverError, SearchStrategy},
+    start, success, working,
+};
+
+#[derive(Error, Debug)]
+pub enum FindArchitectureError {
+    #[error("Failed to convert the puzzle into a puzzle definition: {0}")]
+    KSolveConversion(#[from] KSolveConversionError),
+    #[error("Invalid cycle structure for register {register}: {error}")]
+    InvalidCycleStructure {
+        register: usize,
+        error: SortedCycleStructureCreationError,
+    },
+    #[error("Failed to generate pruning tables for register {register}: {error}")]
+    PruningTableGeneration {
+        register: usize,
+        error: OrbitPruningTableGenerationError,
+    },
+    #[error("Failed to find a generator for register {register}: {error}")]
+    Solver {
+        register: usize,
+        error: CycleStructureSolverError,
+    },
+    #[error("The generator found for register {register} could not be parsed: {moves}")]
+    UnparsableGenerator { register: usize, moves: String },
+    #[error("There is no way to fit registers of orders {orders:?} onto the puzzle")]
+    NoCycleCombination { orders: Vec<u64> },
+    #[error("The generators found do not form a valid architecture: {0}")]
+    Validation(#[from] ArchValidationError),
+    #[error("Register {register} was expected to have order {expected} but has order {actual}")]
+    WrongOrder {
+        register: usize,
+        expected: u64,
+        actual: Int<U>,
+    },
+}
+
+/// Search for an architecture whose registers have the given orders.
+///
+/// The cycle structure of each register is chosen by [`find_cycle_structures`]
+/// and then handed to [`find_architecture_for_cycle_structures`].
+///
+/// # Errors
+///
+/// Returns an error if the orders can't all fit onto the puzzle, if the solver
+/// fails to find a generator, or if the generators found do not form a valid
+/// architecture with the requested orders.
+pub fn find_architecture(
+    puzzle_geometry: &PuzzleGeometry,
+    register_orders: &[u64],
+    max_pruning_table_bytes: usize,
+) -> Result<Architecture, FindArchitectureError> {
+    make_guard!(guard);
+    let puzzle_def = PuzzleDef::<HeapPuzzle>::new(&puzzle_geometry.ksolve(), guard)?;
+
+    let Some(register_cycle_structures) = find_cycle_structures(&puzzle_def, register_orders)
+    else {
+        return Err(FindArchitectureError::NoCycleCombination {
+            orders: register_orders.to_vec(),
+        });
+    };
+
+    let architecture = solve_registers(
+        puzzle_geometry.permutation_group(),
+        puzzle_def,
+        &register_cycle_structures,
+        max_pruning_table_bytes,
+    )?;
+
+    // The orientation of an oriented cycle may be twisted by less than a full
+    // turn, which gives a smaller order than the one that was asked for
+    for (register, (&expected, found)) in
+        register_orders.iter().zip(architecture.registers()).enumerate()
+    {
+        if found.order() != Int::<U>::from(expected) {
+            return Err(FindArchitectureError::WrongOrder {
+                register,
+                expected,
+                actual: found.order(),
+            });
+        }
+    }
+
+    Ok(architecture)
+}
+
+/// Search for an architecture whose registers have the given cycle structures.
+/// Each element of `register_cycle_structures` is the cycle structure of one
+/// register, in the same format accepted by [`SortedCycleStructure::new`]. Use
+/// [`find_architecture`] to search by register orders instead.
+///
+/// Every register generator is the first optimal solution found by the cycle
+/// structure solver, using pruning tables of at most `max_pruning_table_bytes`
+/// bytes. The resulting architecture is validated before it is returned.
+///
+/// # Errors
+///
+/// Returns an error if any register's cycle structure is invalid for the
+/// puzzle, if the solver fails to find a generator, or if the generators found
+/// do not form a valid architecture.
+pub fn find_architecture_for_cycle_structures(
+    puzzle_geometry: &PuzzleGeometry,
+    register_cycle_structures: &[Vec<Vec<(u8, bool)>>],
+    max_pruning_table_bytes: usize,
+) -> Result<Architecture, FindArchitectureError> {
+    make_guard!(guard);
+    let puzzle_def = PuzzleDef::<HeapPuzzle>::new(&puzzle_geometry.ksolve(), guard)?;
+
+    solve_registers(
+        puzzle_geometry.permutation_group(),
+        puzzle_def,
+        register_cycle_structures,
+        max_pruning_table_bytes,
+    )
+}
+
+fn solve_registers(
+    perm_group: Arc<PermutationGroup>,
+    mut puzzle_def: PuzzleDef<'_, HeapPuzzle>,
+    register_cycle_structures: &[Vec<Vec<(u8, bool)>>],
+    max_pruning_table_bytes: usize,
+) -> Result<Architecture, FindArchitectureError> {
+    info!(start!("Searching for an architecture..."));
+
+    let mut algorithms = Vec::with_capacity(register_cycle_structures.len());
+
+    for (register, cycle_structure) in register_cycle_structures.iter().enumerate() {
+        info!(working!("Finding a generator for register {}..."), register);
+
+        let sorted_cycle_structure =
+            SortedCycleStructure::new(cycle_structure, puzzle_def.sorted_orbit_defs_ref())
+                .map_err(|error| FindArchitectureError::InvalidCycleStructure { register, error })?;
+        let generate_meta = OrbitPruningTablesGenerateMeta::new(
+            &puzzle_def,
+            max_pruning_table_bytes,
+            puzzle_def.id(),
+        );
+        let pruning_tables =
+            OrbitPruningTables::try_generate_all(sorted_cycle_structure, generate_meta)
+                .map_err(|error| {
+                    FindArchitectureError::PruningTableGeneration { register, error }
+                })?;
+
+        let solver = CycleStructureSolver::new(
+            puzzle_def,
+            pruning_tables,
+            SearchStrategy::FirstSolution,
+        );
+
+        let (moves, algorithm) = {
+            let mut solutions = solver
+                .solve::<Vec<_>>()
+                .map_err(|error| FindArchitectureError::Solver { register, error })?;
+            if solutions.next().is_none() {
+                return Err(FindArchitectureError::Solver {
+                    register,
+                    error: CycleStructureSolverError::SolutionDoesNotExist,
+                });
+            }
+            let moves = solutions
+                .expanded_solution()
+                .iter()
+                .map(|move_| move_.name())
+                .join(" ");
+            (moves, solutions.expanded_algorithm(Arc::clone(&perm_group)))
+        };
+
+        let algorithm = algorithm.map_err(|_| FindArchitectureError::UnparsableGenerator {
+            register,
+            moves: moves.clone(),
+        })?;
+        info!(success!("Found generator {} for register {}"), moves, register);
+        algorithms.push(algorithm);
+
+        puzzle_def = solver.into_puzzle_def_and_pruning_tables().0;
+    }
+
+    let architecture = Architecture::new(perm_group, algorithms.into_boxed_slice());
+    architecture.validate()?;
+
+    Ok(architecture)
+}
+
+/// What the moves of a puzzle allow within one orbit
+struct OrbitConstraint {
+    piece_count: u8,
+    orientation_count: u8,
+    /// Whether every move keeps the sum of the orientations of the orbit's
+    /// pieces the same, in which case a lone twisted cycle is impossible
+    orientation_sum_preserved: bool,
+}
+
+/// The cheapest cycles within one orbit that give a particular order and
+/// parity
+#[derive(Clone)]
+struct OrbitCycles {
+    cycles: Vec<(u8, bool)>,
+    piece_count: u8,
+    order: u64,
+    odd: bool,
+}
+
+fn gcd(mut a: u64, mut b: u64) -> u64 {
+    while b != 0 {
+        (a, b) = (b, a % b);
+    }
+    a
+}
+
+fn lcm(a: u64, b: u64) -> u64 {
+    a / gcd(a, b) * b
+}
+
+/// Reduce `parity` by a basis of parity vectors, leaving zero exactly when it
+/// is a sum of basis vectors
+fn reduce_parity(basis: &[u64], parity: u64) -> u64 {
+    basis.iter().fold(parity, |parity, &vector| parity.min(parity ^ vector))
+}
+
+/// Find the constraints on each orbit of the puzzle, along with a basis of the
+/// parities that the orbits can have at the same time as a bitmask over the
+/// sorted orbits. Returns `None` if the puzzle has more than 64 orbits.
+fn orbit_constraints<'id, P: PuzzleState<'id>>(
+    puzzle_def: &PuzzleDef<'id, P>,
+) -> Option<(Vec<OrbitConstraint>, Vec<u64>)> {
+    let mut constraints = puzzle_def
+        .sorted_orbit_defs_ref()
+        .branded_copied_iter()
+        .map(|branded_orbit_def| {
+            let orbit_def = branded_orbit_def.inner;
+            OrbitConstraint {
+                piece_count: orbit_def.piece_count.get(),
+                orientation_count: orbit_def.orientation_count.get(),
+                orientation_sum_preserved: true,
+            }
+        })
+        .collect::<Vec<_>>();
+
+    if constraints.len() > 64 {
+        return None;
+    }
+
+    let mut parity_basis = Vec::new();
+
+    for move_ in &puzzle_def.moves {
+        let mut parity = 0;
+        let mut maybe_orbit_identifier: Option<P::OrbitIdentifier> = None;
+        for (orbit_index, branded_orbit_def) in
+            puzzle_def.sorted_orbit_defs_ref().branded_copied_iter().enumerate()
+        {
+            let orbit_identifier = if orbit_index == 0 {
+                P::OrbitIdentifier::first_orbit_identifier(branded_orbit_def)
+            } else {
+                maybe_orbit_identifier
+                    .unwrap()
+                    .next_orbit_identifier(branded_orbit_def)
+            };
+            maybe_orbit_identifier = Some(orbit_identifier);
+
+            let (perm, ori) = move_.puzzle_state().orbit_bytes(orbit_identifier);
+            let (perm, ori) = (perm.as_ref(), ori.as_ref());
+            let constraint = &mut constraints[orbit_index];
+
+            let orientation_sum = ori.iter().map(|&ori| u32::from(ori)).sum::<u32>();
+            if orientation_sum % u32::from(constraint.orientation_count) != 0 {
+                constraint.orientation_sum_preserved = false;
+            }
+
+            let mut seen = vec![false; perm.len()];
+            let cycle_count = (0..perm.len())
+                .filter(|&start| {
+                    if seen[start] {
+                        return false;
+                    }
+                    let mut piece = start;
+                    while !seen[piece] {
+                        seen[piece] = true;
+                        piece = perm[piece] as usize;
+                    }
+                    true
+                })
+                .count();
+            if (perm.len() - cycle_count) % 2 == 1 {
+                parity |= 1 << orbit_index;
+            }
+        }
+
+        let parity = reduce_parity(&parity_basis, parity);
+        if parity != 0 {
+            parity_basis.push(parity);
+        }
+    }
+
+    Some((constraints, parity_basis))
+}
+
+/// Find every order and parity that cycles within one orbit can have while
+/// dividing `target`, keeping only the cycles that use the fewest pieces for
+/// each of them
+fn orbit_cycle_options(constraint: &OrbitConstraint, target: u64) -> Vec<OrbitCycles> {
+    // The number of twisted cycles only matters in whether it is zero, one, or
+    // even, so counts of two or more are collapsed by parity
+    let collapse = |twisted: u8| if twisted >= 2 { 2 + twisted % 2 } else { twisted };
+
+    // (pieces, order, odd, twisted) -> cycles
+    let mut states = HashMap::<(u8, u64, bool, u8), Vec<(u8, bool)>>::new();
+    states.insert((0, 1, false, 0), Vec::new());
+
+    for length in 1..=constraint.piece_count {
+        for oriented in [false, true] {
+            if (length == 1 && !oriented) || (oriented && constraint.orientation_count == 1) {
+                continue;
+            }
+
+            let order = if oriented {
+                u64::from(length) * u64::from(constraint.orientation_count)
+            } else {
+                u64::from(length)
+            };
+            if target % order != 0 {
+                continue;
+            }
+
+            let mut next_states = states.clone();
+            for ((pieces, state_order, odd, twisted), cycles) in &states {
+                let mut cycles = cycles.clone();
+                let mut pieces = *pieces;
+                let mut odd = *odd;
+                let mut twisted = *twisted;
+                while u16::from(pieces) + u16::from(length) <= u16::from(constraint.piece_count) {
+                    pieces += length;
+                    odd ^= length % 2 == 0;
+                    if oriented {
+                        twisted = collapse(twisted + 1);
+                    }
+                    cycles.push((length, oriented));
+                    next_states
+                        .entry((pieces, lcm(*state_order, order), odd, twisted))
+                        .or_insert_with(|| cycles.clone());
+                }
+            }
+            states = next_states;
+        }
+    }
+
+    let mut cheapest = BTreeMap::<(u64, bool), OrbitCycles>::new();
+    for ((piece_count, order, odd, twisted), cycles) in states {
+        let possible = !constraint.orientation_sum_preserved
+            || match constraint.orientation_count {
+                2 => twisted % 2 == 0,
+                _ => twisted != 1,
+            };
+        if !possible {
+            continue;
+        }
+
+        let option = OrbitCycles {
+            cycles,
+            piece_count,
+            order,
+            odd,
+        };
+        cheapest
+            .entry((order, odd))
+            .and_modify(|existing| {
+                if option.piece_count < existing.piece_count {
+                    *existing = option.clone();
+                }
+            })
+            .or_insert(option);
+    }
+
+    cheapest.into_values().collect()
+}
+
+/// Find every cycle structure of order `target` that the puzzle can reach,
+/// as the cycles in each orbit, ordered by the number of pieces they use
+fn register_cycle_options(
+    constraints: &[OrbitConstraint],
+    parity_basis: &[u64],
+    target: u64,
+) -> Vec<Vec<OrbitCycles>> {
+    fn extend(
+        options: &[Vec<OrbitCycles>],
+        chosen: &mut Vec<OrbitCycles>,
+        order: u64,
+        parity: u64,
+        parity_basis: &[u64],
+        target: u64,
+        found: &mut Vec<Vec<OrbitCycles>>,
+    ) {
+        let Some((orbit_options, rest)) = options.split_first() else {
+            if order == target && reduce_parity(parity_basis, parity) == 0 {
+                found.push(chosen.clone());
+            }
+            return;
+        };
+
+        for option in orbit_options {
+            let parity = parity | (u64::from(option.odd) << chosen.len());
+            let order = lcm(order, option.order);
+            chosen.push(option.clone());
+            extend(rest, chosen, order, parity, parity_basis, target, found);
+            chosen.pop();
+        }
+    }
+
+    let options = constraints
+        .iter()
+        .map(|constraint| orbit_cycle_options(constraint, target))
+        .collect::<Vec<_>>();
+
+    let mut found = Vec::new();
+    extend(&options, &mut Vec::new(), 1, 0, parity_basis, target, &mut found);
+    found.sort_by_key(|cycles| {
+        cycles
+            .iter()
+            .map(|orbit_cycles| u32::from(orbit_cycles.piece_count))
+            .sum::<u32>()
+    });
+    found
+}
+
+/// Choose a cycle structure for each register so that the registers have the
+/// given orders and use disjoint pieces. Every cycle structure is reachable
+/// with respect to the parity and orientation constraints of the puzzle's
+/// moves, and registers use as few pieces as possible in order of priority.
+/// Cycle structures are in the format accepted by
+/// [`SortedCycleStructure::new`].
+///
+/// Returns `None` if no such cycle structures exist.
+#[must_use]
+pub fn find_cycle_structures<'id, P: PuzzleState<'id>>(
+    puzzle_def: &PuzzleDef<'id, P>,
+    register_orders: &[u64],
+) -> Option<Vec<Vec<Vec<(u8, bool)>>>> {
+    fn assign(
+        options: &[Vec<Vec<OrbitCycles>>],
+        remaining: &mut [u8],
+        failed: &mut HashSet<(usize, Vec<u8>)>,
+        chosen: &mut Vec<usize>,
+    ) -> bool {
+        let register = chosen.len();
+        if register == options.len() {
+            return true;
+        }
+        if failed.contains(&(register, remaining.to_vec())) {
+            return false;
+        }
+
+        for (i, option) in options[register].iter().enumerate() {
+            if option
+                .iter()
+                .zip(remaining.iter())
+                .any(|(orbit_cycles, &pieces)| orbit_cycles.piece_count > pieces)
+            {
+                continue;
+            }
+
+            for (orbit_cycles, pieces) in option.iter().zip(remaining.iter_mut()) {
+                *pieces -= orbit_cycles.piece_count;
+            }
+            chosen.push(i);
+
+            if assign(options, remaining, failed, chosen) {
+                return true;
+            }
+
+            chosen.pop();
+            for (orbit_cycles, pieces) in option.iter().zip(remaining.iter_mut()) {
+                *pieces += orbit_cycles.piece_count;
+            }
+        }
+
+        failed.insert((register, remaining.to_vec()));
+        false
+    }
+
+    let (constraints, parity_basis) = orbit_constraints(puzzle_def)?;
+
+    let options = register_orders
+        .iter()
+        .map(|&order| register_cycle_options(&constraints, &parity_basis, order))
+        .collect::<Vec<_>>();
+
+    let mut remaining = constraints
+        .iter()
+        .map(|constraint| constraint.piece_count)
+        .collect::<Vec<_>>();
+    let mut chosen = Vec::with_capacity(register_orders.len());
+
+    if !assign(&options, &mut remaining, &mut HashSet::new(), &mut chosen) {
+        return None;
+    }
+
+    Some(
+        chosen
+            .into_iter()
+            .zip(options)
+            .map(|(i, mut register_options)| {
+                register_options
+                    .swap_remove(i)
+                    .into_iter()
+                    .map(|orbit_cycles| orbit_cycles.cycles)
+                    .collect()
+            })
+            .collect(),
+    )
+}
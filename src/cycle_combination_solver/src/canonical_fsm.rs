@@ -225,26 +225,28 @@ mod tests {
         }
     }
 
-    #[test]
-    fn test_canonical_fsm_puzzle_prevents_self_and_antipode() {
-        make_guard!(guard);
-        let cube3_def = PuzzleDef::<Cube3>::new(&puzzle("3x3").ksolve(), guard).unwrap();
-        let canonical_fsm: PuzzleCanonicalFSM<Cube3> = (&cube3_def).into();
+    /// Commuting move classes must be searched in exactly one order, whatever
+    /// the puzzle.
+    fn assert_commuting_move_classes_totally_ordered<'id, P: PuzzleState<'id>>(
+        puzzle_def: &PuzzleDef<'id, P>,
+    ) {
+        let canonical_fsm: PuzzleCanonicalFSM<P> = puzzle_def.into();
 
-        let mut result_1 = cube3_def.new_solved_state();
+        let mut result_1 = puzzle_def.new_solved_state();
         let mut result_2 = result_1.clone();
-        for (move_class_index_1, move_class_1) in cube3_def.move_classes.iter().copied().enumerate()
+        for (move_class_index_1, move_class_1) in
+            puzzle_def.move_classes.iter().copied().enumerate()
         {
-            let move_1 = &cube3_def.moves[move_class_1];
+            let move_1 = &puzzle_def.moves[move_class_1];
             for (move_class_index_2, move_class_2) in
-                cube3_def.move_classes.iter().copied().enumerate()
+                puzzle_def.move_classes.iter().copied().enumerate()
             {
-                let move_2 = &cube3_def.moves[move_class_2];
+                let move_2 = &puzzle_def.moves[move_class_2];
                 if !move_1.commutes_with(
                     move_2,
                     &mut result_1,
                     &mut result_2,
-                    cube3_def.sorted_orbit_defs_ref(),
+                    puzzle_def.sorted_orbit_defs_ref(),
                 ) {
                     continue;
                 }
@@ -288,6 +290,21 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_canonical_fsm_puzzle_prevents_self_and_antipode() {
+        make_guard!(guard);
+        let cube3_def = PuzzleDef::<Cube3>::new(&puzzle("3x3").ksolve(), guard).unwrap();
+        assert_commuting_move_classes_totally_ordered(&cube3_def);
+    }
+
+    #[test]
+    fn test_canonical_fsm_megaminx_prevents_self_and_antipode() {
+        make_guard!(guard);
+        let megaminx_def =
+            PuzzleDef::<HeapPuzzle>::new(&puzzle("megaminx").ksolve(), guard).unwrap();
+        assert_commuting_move_classes_totally_ordered(&megaminx_def);
+    }
+
     #[test]
     #[ignore = "big cube stuff isnt working without puzzle working"]
     fn test_big_cube_prevents_move_class() {
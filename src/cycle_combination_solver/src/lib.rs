@@ -3,6 +3,7 @@
 #![allow(clippy::similar_names, clippy::too_many_lines)]
 
 pub(crate) mod all_topos;
+pub mod architecture;
 pub(crate) mod canonical_fsm;
 pub(crate) mod orbit_puzzle;
 pub(crate) mod permutator;
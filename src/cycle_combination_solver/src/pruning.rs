@@ -10,8 +10,11 @@
 //! vector of the appropriate size.
 
 use std::{
+    fs::File,
+    io::{self, BufReader, BufWriter, Read, Write},
     marker::PhantomData,
     num::{NonZeroU8, NonZeroUsize},
+    path::Path,
     time::Instant,
 };
 
@@ -90,6 +93,33 @@ pub trait StorageBackend<const EXACT: bool>: 'static {
     /// Commit the depth traversed for the pruning table, used to represent the
     /// heuristic for vacant entries.
     fn commit_depth_traversed(&mut self, depth_traversed: u8);
+
+    /// Write the storage backend to `writer`, prefixed with its tag, so that
+    /// it can be restored by `load`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if writing fails or if this storage backend cannot be
+    /// saved.
+    fn save(&self, _writer: &mut dyn Write) -> Result<(), PruningTablesIoError> {
+        Err(PruningTablesIoError::UnsupportedTableType)
+    }
+
+    /// Restore a storage backend written by `save`. The tag has already been
+    /// consumed. `max_entry_count` bounds how much is allocated so that a
+    /// corrupt file cannot exhaust memory.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if reading fails, if the storage backend stores more
+    /// than `max_entry_count` entries, or if this storage backend cannot be
+    /// loaded.
+    fn load(_reader: &mut dyn Read, _max_entry_count: u64) -> Result<Self, PruningTablesIoError>
+    where
+        Self: Sized,
+    {
+        Err(PruningTablesIoError::UnsupportedTableType)
+    }
 }
 
 /// A pruning table acting on a single orbit.
@@ -111,6 +141,17 @@ trait OrbitPruningTable<'id, P: PuzzleState<'id>>: 'id {
     /// is a logic error if this is not the case. Implementors are expected to
     /// have a mechanism to identify the table's target orbit.
     fn admissible_heuristic(&self, puzzle_state: &P) -> u8;
+
+    /// Write the table to `writer`, prefixed with its tag, so that it can be
+    /// restored by `OrbitPruningTables::load`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if writing fails or if this table type cannot be
+    /// saved.
+    fn save(&self, _writer: &mut dyn Write) -> Result<(), PruningTablesIoError> {
+        Err(PruningTablesIoError::UnsupportedTableType)
+    }
 }
 
 // Not completely sure what this trait should look like; Henry change this if
@@ -122,8 +163,23 @@ pub trait UsedSizeBytes {
 pub struct OrbitPruningTables<'id, P: PuzzleState<'id>> {
     orbit_pruning_tables: Box<[Box<dyn OrbitPruningTable<'id, P>>]>,
     sorted_cycle_structure: SortedCycleStructure<'id>,
+    /// `PuzzleDef::fingerprint` of the puzzle the tables were generated for
+    puzzle_def_fingerprint: u64,
 }
 
+/// The magic bytes at the start of a saved pruning tables file.
+const PRUNING_TABLES_MAGIC: &[u8; 8] = b"QTRPRUNE";
+/// The version of the saved pruning tables format. Bump this whenever the
+/// layout changes.
+const PRUNING_TABLES_VERSION: u8 = 1;
+
+const ZERO_ORBIT_TABLE_TAG: u8 = 0;
+const EXACT_ORBIT_TABLE_TAG: u8 = 1;
+const APPROXIMATE_ORBIT_TABLE_TAG: u8 = 2;
+const CYCLE_STRUCTURE_ORBIT_TABLE_TAG: u8 = 3;
+
+const UNCOMPRESSED_STORAGE_BACKEND_TAG: u8 = 0;
+
 #[derive(Debug)]
 struct OrbitPruningTableGenerationMeta<'id, 'a, P: PuzzleState<'id>> {
     puzzle_def: &'a PuzzleDef<'id, P>,
@@ -201,6 +257,28 @@ pub enum OrbitPruningTableGenerationError {
     TooLargeLoadFactor,
 }
 
+#[derive(Error, Debug)]
+pub enum PruningTablesIoError {
+    #[error("I/O error: {0}")]
+    Io(#[from] io::Error),
+    #[error("The file is not a saved pruning table")]
+    InvalidMagic,
+    #[error("Unsupported pruning table file version: expected {expected}, actual {actual}")]
+    UnsupportedVersion { expected: u8, actual: u8 },
+    #[error("The pruning tables were generated for a different puzzle definition")]
+    PuzzleDefMismatch,
+    #[error("The pruning tables were generated for a different cycle structure")]
+    CycleStructureMismatch,
+    #[error("The pruning table for orbit {0} does not match its orbit definition")]
+    OrbitDefMismatch(usize),
+    #[error("The pruning table stores {actual} entries but at most {max} are possible")]
+    TooManyEntries { max: u64, actual: u64 },
+    #[error("This orbit pruning table type cannot be saved or loaded")]
+    UnsupportedTableType,
+    #[error("Unknown orbit pruning table tag: {0}")]
+    UnknownTag(u8),
+}
+
 #[derive(Error, Debug)]
 pub enum TableTypeInstantiationError {
     #[error("Invalid table types length: expected {expected}, actual {actual}")]
@@ -261,6 +339,14 @@ mod private {
         pub fn get_occupied(self) -> Option<u8> {
             if self.is_vacant() { None } else { Some(self.0) }
         }
+
+        pub fn to_raw(self) -> u8 {
+            self.0
+        }
+
+        pub fn from_raw(raw: u8) -> OrbitPruneHeuristic {
+            OrbitPruneHeuristic(raw)
+        }
     }
 }
 
@@ -403,6 +489,7 @@ impl<'id, P: PuzzleState<'id>> PruningTables<'id, P> for OrbitPruningTables<'id,
         let orbit_pruning_tables = OrbitPruningTables {
             orbit_pruning_tables: orbit_pruning_tables.into_boxed_slice(),
             sorted_cycle_structure,
+            puzzle_def_fingerprint: generate_metas.puzzle_def.fingerprint(),
         };
         info!(
             success!("Generated all orbit pruning tables in {}"),
@@ -425,6 +512,239 @@ impl<'id, P: PuzzleState<'id>> PruningTables<'id, P> for OrbitPruningTables<'id,
     }
 }
 
+impl<'id, P: PuzzleState<'id>> OrbitPruningTables<'id, P> {
+    /// Save the pruning tables to `path` so that the expensive table
+    /// generation only has to happen once. The file records the puzzle
+    /// definition fingerprint and the cycle structure so that `load` can
+    /// reject tables generated for something else.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if writing the file fails or if one of the orbit
+    /// pruning table types cannot be saved.
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<(), PruningTablesIoError> {
+        info!(start!("Saving all orbit pruning tables"));
+        let start = Instant::now();
+
+        let mut writer = BufWriter::new(File::create(path)?);
+        writer.write_all(PRUNING_TABLES_MAGIC)?;
+        writer.write_all(&[PRUNING_TABLES_VERSION])?;
+        writer.write_all(&self.puzzle_def_fingerprint.to_le_bytes())?;
+        write_sorted_cycle_structure(&mut writer, &self.sorted_cycle_structure.inner)?;
+        for orbit_pruning_table in &self.orbit_pruning_tables {
+            orbit_pruning_table.save(&mut writer)?;
+        }
+        writer.flush()?;
+
+        info!(
+            success!("Saved all orbit pruning tables in {}"),
+            start.elapsed().human(Truncate::Millis)
+        );
+        Ok(())
+    }
+
+    /// Load pruning tables written by `save`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if reading the file fails, or if the tables were
+    /// generated for a different puzzle definition or cycle structure.
+    pub fn load(
+        path: impl AsRef<Path>,
+        puzzle_def: &PuzzleDef<'id, P>,
+        sorted_cycle_structure: SortedCycleStructure<'id>,
+    ) -> Result<Self, PruningTablesIoError> {
+        info!(start!("Loading all orbit pruning tables"));
+        let start = Instant::now();
+
+        let mut reader = BufReader::new(File::open(path)?);
+
+        let mut magic = [0; PRUNING_TABLES_MAGIC.len()];
+        reader.read_exact(&mut magic)?;
+        if &magic != PRUNING_TABLES_MAGIC {
+            return Err(PruningTablesIoError::InvalidMagic);
+        }
+        let version = read_u8(&mut reader)?;
+        if version != PRUNING_TABLES_VERSION {
+            return Err(PruningTablesIoError::UnsupportedVersion {
+                expected: PRUNING_TABLES_VERSION,
+                actual: version,
+            });
+        }
+        let puzzle_def_fingerprint = read_u64(&mut reader)?;
+        if puzzle_def_fingerprint != puzzle_def.fingerprint() {
+            return Err(PruningTablesIoError::PuzzleDefMismatch);
+        }
+        if read_sorted_cycle_structure(&mut reader)? != sorted_cycle_structure.inner {
+            return Err(PruningTablesIoError::CycleStructureMismatch);
+        }
+
+        let mut orbit_pruning_tables: Vec<Box<dyn OrbitPruningTable<'id, P>>> =
+            Vec::with_capacity(puzzle_def.sorted_orbit_defs.len());
+        let mut maybe_orbit_identifier: Option<P::OrbitIdentifier> = None;
+        for (orbit_index, branded_orbit_def) in puzzle_def
+            .sorted_orbit_defs_ref()
+            .branded_copied_iter()
+            .enumerate()
+        {
+            maybe_orbit_identifier = Some(if orbit_index == 0 {
+                P::OrbitIdentifier::first_orbit_identifier(branded_orbit_def)
+            } else {
+                maybe_orbit_identifier
+                    .unwrap()
+                    .next_orbit_identifier(branded_orbit_def)
+            });
+            let orbit_identifier = maybe_orbit_identifier.unwrap();
+
+            // No table for an orbit needs more entries than the orbit has states
+            let orbit_def = orbit_identifier.orbit_def();
+            let piece_count = orbit_def.piece_count.get();
+            let exact_entry_count = FACT_UNTIL_19
+                .get(piece_count as usize)
+                .and_then(|fact| {
+                    u64::from(orbit_def.orientation_count.get())
+                        .checked_pow(u32::from(piece_count) - 1)?
+                        .checked_mul(*fact)
+                })
+                .unwrap_or(u64::MAX);
+
+            orbit_pruning_tables.push(match read_u8(&mut reader)? {
+                ZERO_ORBIT_TABLE_TAG => Box::new(ZeroOrbitTable),
+                EXACT_ORBIT_TABLE_TAG => {
+                    let storage_backend = match read_u8(&mut reader)? {
+                        UNCOMPRESSED_STORAGE_BACKEND_TAG => {
+                            UncompressedStorageBackend::<true>::load(
+                                &mut reader,
+                                exact_entry_count,
+                            )?
+                        }
+                        tag => return Err(PruningTablesIoError::UnknownTag(tag)),
+                    };
+                    if exact_entry_count != storage_backend.data.len() as u64 {
+                        return Err(PruningTablesIoError::OrbitDefMismatch(orbit_index));
+                    }
+                    Box::new(ExactOrbitPruningTable {
+                        storage_backend,
+                        orbit_identifier,
+                        _id: puzzle_def.id(),
+                    })
+                }
+                APPROXIMATE_ORBIT_TABLE_TAG => {
+                    let storage_backend = match read_u8(&mut reader)? {
+                        UNCOMPRESSED_STORAGE_BACKEND_TAG => {
+                            UncompressedStorageBackend::<false>::load(
+                                &mut reader,
+                                exact_entry_count,
+                            )?
+                        }
+                        tag => return Err(PruningTablesIoError::UnknownTag(tag)),
+                    };
+                    // Approximate tables are indexed modulo their length
+                    if storage_backend.data.is_empty() {
+                        return Err(PruningTablesIoError::OrbitDefMismatch(orbit_index));
+                    }
+                    Box::new(ApproximateOrbitPruningTable {
+                        storage_backend,
+                        orbit_identifier,
+                        _id: puzzle_def.id(),
+                    })
+                }
+                CYCLE_STRUCTURE_ORBIT_TABLE_TAG => Box::new(CycleStructureOrbitPruningTable {
+                    data: read_orbit_prune_heuristics(&mut reader, exact_entry_count)?,
+                    orbit_identifier,
+                    _id: puzzle_def.id(),
+                }),
+                tag => return Err(PruningTablesIoError::UnknownTag(tag)),
+            });
+        }
+
+        info!(
+            success!("Loaded all orbit pruning tables in {}"),
+            start.elapsed().human(Truncate::Millis)
+        );
+        debug!("");
+        Ok(OrbitPruningTables {
+            orbit_pruning_tables: orbit_pruning_tables.into_boxed_slice(),
+            sorted_cycle_structure,
+            puzzle_def_fingerprint,
+        })
+    }
+}
+
+fn write_sorted_cycle_structure(
+    writer: &mut impl Write,
+    sorted_cycle_structure: &[Vec<(NonZeroU8, bool)>],
+) -> io::Result<()> {
+    writer.write_all(&(sorted_cycle_structure.len() as u64).to_le_bytes())?;
+    for orbit in sorted_cycle_structure {
+        writer.write_all(&(orbit.len() as u64).to_le_bytes())?;
+        for &(length, oriented) in orbit {
+            writer.write_all(&[length.get(), u8::from(oriented)])?;
+        }
+    }
+    Ok(())
+}
+
+fn read_sorted_cycle_structure(
+    reader: &mut impl Read,
+) -> Result<Vec<Vec<(NonZeroU8, bool)>>, PruningTablesIoError> {
+    let orbit_count = read_u64(reader)?;
+    let mut sorted_cycle_structure = vec![];
+    for _ in 0..orbit_count {
+        let cycle_count = read_u64(reader)?;
+        let mut orbit = vec![];
+        for _ in 0..cycle_count {
+            let Some(length) = NonZeroU8::new(read_u8(reader)?) else {
+                return Err(PruningTablesIoError::CycleStructureMismatch);
+            };
+            orbit.push((length, read_u8(reader)? != 0));
+        }
+        sorted_cycle_structure.push(orbit);
+    }
+    Ok(sorted_cycle_structure)
+}
+
+fn write_orbit_prune_heuristics(
+    writer: &mut (impl Write + ?Sized),
+    data: &[OrbitPruneHeuristic],
+) -> io::Result<()> {
+    writer.write_all(&(data.len() as u64).to_le_bytes())?;
+    let raw = data
+        .iter()
+        .map(|orbit_prune_heuristic| orbit_prune_heuristic.to_raw())
+        .collect_vec();
+    writer.write_all(&raw)
+}
+
+fn read_orbit_prune_heuristics(
+    reader: &mut (impl Read + ?Sized),
+    max_entry_count: u64,
+) -> Result<Box<[OrbitPruneHeuristic]>, PruningTablesIoError> {
+    let len = read_u64(reader)?;
+    if len > max_entry_count {
+        return Err(PruningTablesIoError::TooManyEntries {
+            max: max_entry_count,
+            actual: len,
+        });
+    }
+    let len = usize::try_from(len).map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))?;
+    let mut raw = vec![0; len];
+    reader.read_exact(&mut raw)?;
+    Ok(raw.into_iter().map(OrbitPruneHeuristic::from_raw).collect())
+}
+
+fn read_u8(reader: &mut (impl Read + ?Sized)) -> io::Result<u8> {
+    let mut buf = [0; 1];
+    reader.read_exact(&mut buf)?;
+    Ok(buf[0])
+}
+
+fn read_u64(reader: &mut (impl Read + ?Sized)) -> io::Result<u64> {
+    let mut buf = [0; 8];
+    reader.read_exact(&mut buf)?;
+    Ok(u64::from_le_bytes(buf))
+}
+
 macro_rules! table_fn {
     ($fn_name:ident, $table:ident, $storage:ident, $exact:ident) => {
         fn $fn_name<'id, 'a, P: PuzzleState<'id>>(
@@ -605,6 +925,20 @@ impl<const EXACT: bool> StorageBackend<EXACT> for UncompressedStorageBackend<EXA
     fn commit_depth_traversed(&mut self, depth_traversed: u8) {
         self.depth_traversed = depth_traversed;
     }
+
+    fn save(&self, writer: &mut dyn Write) -> Result<(), PruningTablesIoError> {
+        writer.write_all(&[UNCOMPRESSED_STORAGE_BACKEND_TAG, self.depth_traversed])?;
+        write_orbit_prune_heuristics(writer, &self.data)?;
+        Ok(())
+    }
+
+    fn load(reader: &mut dyn Read, max_entry_count: u64) -> Result<Self, PruningTablesIoError> {
+        let depth_traversed = read_u8(reader)?;
+        Ok(UncompressedStorageBackend {
+            data: read_orbit_prune_heuristics(reader, max_entry_count)?,
+            depth_traversed,
+        })
+    }
 }
 
 #[allow(unused)]
@@ -714,6 +1048,11 @@ impl<'id, P: PuzzleState<'id>, S: StorageBackend<false>> OrbitPruningTable<'id,
                 &puzzle_state.approximate_hash_orbit(self.orbit_identifier),
             ))
     }
+
+    fn save(&self, writer: &mut dyn Write) -> Result<(), PruningTablesIoError> {
+        writer.write_all(&[APPROXIMATE_ORBIT_TABLE_TAG])?;
+        self.storage_backend.save(writer)
+    }
 }
 
 /// Use Knuth's algorithm M to generate the next orientation vector
@@ -930,6 +1269,11 @@ impl<'id, P: PuzzleState<'id>, S: StorageBackend<true>> OrbitPruningTable<'id, P
         self.storage_backend
             .admissible_heuristic_hash(puzzle_state.exact_hasher_orbit(self.orbit_identifier))
     }
+
+    fn save(&self, writer: &mut dyn Write) -> Result<(), PruningTablesIoError> {
+        writer.write_all(&[EXACT_ORBIT_TABLE_TAG])?;
+        self.storage_backend.save(writer)
+    }
 }
 
 #[allow(unused)]
@@ -954,6 +1298,12 @@ impl<'id, P: PuzzleState<'id>> OrbitPruningTable<'id, P>
     fn admissible_heuristic(&self, puzzle_state: &P) -> u8 {
         0
     }
+
+    fn save(&self, writer: &mut dyn Write) -> Result<(), PruningTablesIoError> {
+        writer.write_all(&[CYCLE_STRUCTURE_ORBIT_TABLE_TAG])?;
+        write_orbit_prune_heuristics(writer, &self.data)?;
+        Ok(())
+    }
 }
 
 impl<'id, P: PuzzleState<'id>> OrbitPruningTable<'id, P> for ZeroOrbitTable {
@@ -972,6 +1322,11 @@ impl<'id, P: PuzzleState<'id>> OrbitPruningTable<'id, P> for ZeroOrbitTable {
     fn admissible_heuristic(&self, _puzzle_state: &P) -> u8 {
         0
     }
+
+    fn save(&self, writer: &mut dyn Write) -> Result<(), PruningTablesIoError> {
+        writer.write_all(&[ZERO_ORBIT_TABLE_TAG])?;
+        Ok(())
+    }
 }
 
 impl<'id, P: PuzzleState<'id>> PruningTables<'id, P> for ZeroTable<'id, P> {
@@ -1094,6 +1449,155 @@ mod tests {
         assert_eq!(orbit_tables.admissible_heuristic(u_move.puzzle_state()), 0);
     }
 
+    #[test_log::test]
+    fn test_save_and_load_orbit_tables() {
+        make_guard!(guard);
+        let cube3_def = PuzzleDef::<Cube3>::new(&puzzle("3x3").ksolve(), guard).unwrap();
+        let u_move = cube3_def.find_move("U").unwrap();
+        let identity_cycle_structure =
+            SortedCycleStructure::new(&[vec![], vec![]], cube3_def.sorted_orbit_defs_ref())
+                .unwrap();
+        let generate_metas = OrbitPruningTablesGenerateMeta::new_with_table_types(
+            &cube3_def,
+            vec![TableTy::Zero, TableTy::Zero],
+            0,
+            cube3_def.id(),
+        )
+        .unwrap();
+        let orbit_tables =
+            OrbitPruningTables::try_generate_all(identity_cycle_structure.clone(), generate_metas)
+                .unwrap();
+
+        let path = unique_temp_path("save_and_load_orbit_tables");
+        orbit_tables.save(&path).unwrap();
+
+        let loaded =
+            OrbitPruningTables::load(&path, &cube3_def, identity_cycle_structure).unwrap();
+        assert_eq!(loaded.orbit_pruning_tables.len(), 2);
+        assert_eq!(loaded.admissible_heuristic(u_move.puzzle_state()), 0);
+
+        let other_cycle_structure = SortedCycleStructure::new(
+            &[vec![(3, false)], vec![]],
+            cube3_def.sorted_orbit_defs_ref(),
+        )
+        .unwrap();
+        assert!(matches!(
+            OrbitPruningTables::load(&path, &cube3_def, other_cycle_structure),
+            Err(PruningTablesIoError::CycleStructureMismatch)
+        ));
+
+        std::fs::remove_file(path).unwrap();
+    }
+
+    /// A path in the temporary directory that no other test or test process
+    /// uses at the same time
+    fn unique_temp_path(name: &str) -> std::path::PathBuf {
+        std::env::temp_dir().join(format!("qter_test_{}_{name}.bin", std::process::id()))
+    }
+
+    #[test_log::test]
+    fn test_save_and_load_exact_uncompressed_storage_backend() {
+        let mut storage =
+            UncompressedStorageBackend::<true>::initialize_from_meta(MaxSizeBytes(100));
+        storage.set_heuristic_hash(0, OrbitPruneHeuristic::occupied(0).unwrap());
+        storage.set_heuristic_hash(5, OrbitPruneHeuristic::occupied(3).unwrap());
+        storage.commit_depth_traversed(4);
+
+        let mut bytes = vec![];
+        storage.save(&mut bytes).unwrap();
+
+        let loaded = UncompressedStorageBackend::<true>::load(&mut &bytes[1..], 100).unwrap();
+        assert_eq!(loaded.depth_traversed, storage.depth_traversed);
+        for hash in 0..100 {
+            assert_eq!(
+                loaded.admissible_heuristic_hash(hash),
+                storage.admissible_heuristic_hash(hash)
+            );
+        }
+
+        assert!(matches!(
+            UncompressedStorageBackend::<true>::load(&mut &bytes[1..], 99),
+            Err(PruningTablesIoError::TooManyEntries {
+                max: 99,
+                actual: 100
+            })
+        ));
+    }
+
+    #[test_log::test]
+    fn test_save_and_load_exact_orbit_tables() {
+        make_guard!(guard);
+        let cube3_def = PuzzleDef::<Cube3>::new(&puzzle("3x3").ksolve(), guard).unwrap();
+        let identity_cycle_structure =
+            SortedCycleStructure::new(&[vec![], vec![]], cube3_def.sorted_orbit_defs_ref())
+                .unwrap();
+
+        // A whole table for the corners would be tens of megabytes, so the
+        // table is truncated. The entries are round tripped by the storage
+        // backend test, so this only checks the checks made while loading.
+        let orbit_identifier = <Cube3 as PuzzleState>::OrbitIdentifier::first_orbit_identifier(
+            cube3_def
+                .sorted_orbit_defs_ref()
+                .branded_copied_iter()
+                .next()
+                .unwrap(),
+        );
+        let storage_backend =
+            UncompressedStorageBackend::<true>::initialize_from_meta(MaxSizeBytes(100));
+
+        let orbit_tables = OrbitPruningTables {
+            orbit_pruning_tables: vec![
+                Box::new(ExactOrbitPruningTable {
+                    storage_backend,
+                    orbit_identifier,
+                    _id: cube3_def.id(),
+                }) as Box<dyn OrbitPruningTable<_>>,
+                Box::new(ZeroOrbitTable),
+            ]
+            .into_boxed_slice(),
+            sorted_cycle_structure: identity_cycle_structure.clone(),
+            puzzle_def_fingerprint: cube3_def.fingerprint(),
+        };
+
+        let path = unique_temp_path("save_and_load_exact_orbit_tables");
+        orbit_tables.save(&path).unwrap();
+
+        // An exact table must have an entry for every state of its orbit
+        assert!(matches!(
+            OrbitPruningTables::load(&path, &cube3_def, identity_cycle_structure.clone()),
+            Err(PruningTablesIoError::OrbitDefMismatch(0))
+        ));
+
+        let bytes = std::fs::read(&path).unwrap();
+
+        // The fingerprint comes right after the magic and the version
+        let mut other_puzzle_def = bytes.clone();
+        other_puzzle_def[PRUNING_TABLES_MAGIC.len() + 1] ^= 1;
+        std::fs::write(&path, &other_puzzle_def).unwrap();
+        assert!(matches!(
+            OrbitPruningTables::load(&path, &cube3_def, identity_cycle_structure.clone()),
+            Err(PruningTablesIoError::PuzzleDefMismatch)
+        ));
+
+        // A corrupt length must be rejected before anything is allocated.
+        // The header is the magic, the version, the fingerprint, and the
+        // counts of the cycle structure, which is followed by the table tag,
+        // the storage backend tag, and the depth traversed.
+        let len_offset = PRUNING_TABLES_MAGIC.len() + 1 + 8 + 3 * 8 + 3;
+        let mut corrupt_len = bytes;
+        corrupt_len[len_offset..len_offset + 8].copy_from_slice(&u64::MAX.to_le_bytes());
+        std::fs::write(&path, &corrupt_len).unwrap();
+        assert!(matches!(
+            OrbitPruningTables::load(&path, &cube3_def, identity_cycle_structure),
+            Err(PruningTablesIoError::TooManyEntries {
+                max: 88_179_840,
+                actual: u64::MAX
+            })
+        ));
+
+        std::fs::remove_file(path).unwrap();
+    }
+
     #[test_log::test]
     fn test_zero_table() {
         make_guard!(guard);
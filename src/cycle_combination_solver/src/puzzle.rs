@@ -1,4 +1,9 @@
-use std::{fmt::Debug, hash::Hash, num::NonZeroU8, time::Instant};
+use std::{
+    fmt::Debug,
+    hash::{Hash, Hasher},
+    num::NonZeroU8,
+    time::Instant,
+};
 
 use generativity::{Guard, Id};
 use heuristic_graph_coloring::{VecVecGraph, color_rlf};
@@ -94,10 +99,20 @@ pub struct PuzzleDef<'id, P: PuzzleState<'id>> {
     pub(crate) move_classes: Box<[usize]>,
     pub(crate) symmetries: Box<[Move<'id, P>]>,
     pub(crate) sorted_orbit_defs: Box<[OrbitDef]>,
+    sorted_orbit_names: Box<[String]>,
     name: String,
     id: Id<'id>,
 }
 
+/// A human readable summary of an orbit of a puzzle
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OrbitSummary {
+    /// The name of the orbit's set in the `KSolve` definition
+    pub name: String,
+    pub piece_count: u8,
+    pub orientation_count: u8,
+}
+
 #[derive(Error, Debug)]
 pub enum KSolveConversionError {
     #[error(
@@ -469,6 +484,22 @@ impl<'id, P: PuzzleState<'id>> PuzzleDef<'id, P> {
         }
     }
 
+    /// Summarize every orbit of the puzzle in the same order as
+    /// `sorted_orbit_defs_ref`, which is the order that
+    /// `SortedCycleStructure::new` expects cycle structures to be given in.
+    #[must_use]
+    pub fn describe_orbits(&self) -> Vec<OrbitSummary> {
+        self.sorted_orbit_defs
+            .iter()
+            .zip(&self.sorted_orbit_names)
+            .map(|(orbit_def, name)| OrbitSummary {
+                name: name.clone(),
+                piece_count: orbit_def.piece_count.get(),
+                orientation_count: orbit_def.orientation_count.get(),
+            })
+            .collect()
+    }
+
     #[must_use]
     pub fn name(&self) -> &str {
         &self.name
@@ -479,6 +510,38 @@ impl<'id, P: PuzzleState<'id>> PuzzleDef<'id, P> {
         self.id
     }
 
+    /// A hash of the orbit definitions and the moves of the puzzle. This is
+    /// used to check that data derived from a puzzle definition, such as a
+    /// saved pruning table, is not used with a different one.
+    #[must_use]
+    pub fn fingerprint(&self) -> u64 {
+        let mut hasher = fxhash::FxHasher64::default();
+        for orbit_def in &self.sorted_orbit_defs {
+            orbit_def.piece_count.get().hash(&mut hasher);
+            orbit_def.orientation_count.get().hash(&mut hasher);
+        }
+        for move_ in &self.moves {
+            move_.name.hash(&mut hasher);
+            let mut maybe_orbit_identifier: Option<P::OrbitIdentifier> = None;
+            for (orbit_index, branded_orbit_def) in
+                self.sorted_orbit_defs_ref().branded_copied_iter().enumerate()
+            {
+                let orbit_identifier = if orbit_index == 0 {
+                    P::OrbitIdentifier::first_orbit_identifier(branded_orbit_def)
+                } else {
+                    maybe_orbit_identifier
+                        .unwrap()
+                        .next_orbit_identifier(branded_orbit_def)
+                };
+                maybe_orbit_identifier = Some(orbit_identifier);
+                let (perm, ori) = move_.puzzle_state.orbit_bytes(orbit_identifier);
+                perm.as_ref().hash(&mut hasher);
+                ori.as_ref().hash(&mut hasher);
+            }
+        }
+        hasher.finish()
+    }
+
     /// Create a new `PuzzleDef` from a `KSolve` definition and a generativity
     /// `Guard`.
     ///
@@ -516,6 +579,10 @@ impl<'id, P: PuzzleState<'id>> PuzzleDef<'id, P> {
             .iter()
             .map(|&i| ksolve_orbit_defs[i])
             .collect_vec();
+        let sorted_orbit_names = arg_indicies
+            .iter()
+            .map(|&i| ksolve.sets()[i].name().to_owned())
+            .collect_vec();
 
         let solved: P = solved_state_from_sorted_orbit_defs(&sorted_orbit_defs, id)?;
 
@@ -676,6 +743,7 @@ impl<'id, P: PuzzleState<'id>> PuzzleDef<'id, P> {
             move_classes: move_classes.into_boxed_slice(),
             symmetries: symmetries.into_boxed_slice(),
             sorted_orbit_defs: sorted_orbit_defs.into_boxed_slice(),
+            sorted_orbit_names: sorted_orbit_names.into_boxed_slice(),
             name: ksolve.name().to_owned(),
             id,
         })
@@ -1039,6 +1107,23 @@ mod tests {
         ));
     }
 
+    #[test]
+    fn test_describe_orbits() {
+        make_guard!(guard);
+        let cube3_def = PuzzleDef::<HeapPuzzle>::new(&puzzle("3x3").ksolve(), guard).unwrap();
+
+        let orbits = cube3_def.describe_orbits();
+
+        assert_eq!(
+            orbits
+                .iter()
+                .map(|orbit| (orbit.piece_count, orbit.orientation_count))
+                .collect_vec(),
+            [(8, 3), (12, 2)]
+        );
+        assert!(orbits.iter().all(|orbit| !orbit.name.is_empty()));
+    }
+
     #[test]
     fn test_induces_sorted_cycle_structure_within_cycle() {
         make_guard!(guard);
@@ -1,14 +1,29 @@
-use std::{borrow::Cow, cmp::Ordering, time::Instant, vec::IntoIter};
+use std::{
+    borrow::Cow,
+    cmp::Ordering,
+    mem,
+    ops::{ControlFlow, Range},
+    ptr,
+    sync::Arc,
+    time::Instant,
+    vec::IntoIter,
+};
 
 use humanize_duration::{Truncate, prelude::DurationExt};
+use internment::ArcIntern;
 use itertools::Itertools;
 use log::{Level, debug, info, log_enabled, trace};
+use puzzle_theory::permutations::{Algorithm, PermutationGroup};
+use rayon::iter::{IntoParallelIterator, ParallelIterator};
 use thiserror::Error;
 
 use super::{
     canonical_fsm::{CanonicalFSMState, PuzzleCanonicalFSM},
-    pruning::PruningTables,
-    puzzle::{Move, PuzzleDef, PuzzleState},
+    pruning::{
+        OrbitPruningTableGenerationError, OrbitPruningTables, OrbitPruningTablesGenerateMeta,
+        PruningTables,
+    },
+    puzzle::{Move, PuzzleDef, PuzzleState, slice_puzzle::HeapPuzzle},
     puzzle_state_history::{PuzzleStateHistory, StackedPuzzleStateHistory},
 };
 use crate::{puzzle::AuxMem, start, success, working};
@@ -21,13 +36,51 @@ pub struct CycleStructureSolver<'id, P: PuzzleState<'id>, T: PruningTables<'id,
     search_strategy: SearchStrategy,
 }
 
-struct CycleStructureSolverMutable<'id, P: PuzzleState<'id>, H: PuzzleStateHistory<'id, P>> {
+struct CycleStructureSolverMutable<
+    'id,
+    P: PuzzleState<'id>,
+    H: PuzzleStateHistory<'id, P>,
+    F: FnMut(SolverProgress) -> ControlFlow<()>,
+> {
     puzzle_state_history: StackedPuzzleStateHistory<'id, P, H>,
     aux_mem: AuxMem<'id>,
     solutions: Vec<Vec<usize>>,
     root_canonical_fsm_reversed_state: usize,
+    /// The moves permitted at the root of the search. Parallel workers each
+    /// search a disjoint part of the tree by restricting this.
+    root_moves: Range<usize>,
     traversed: u64,
     tmp: u64,
+    /// The total number of nodes expanded across every depth, used for
+    /// progress reporting. This is only counted when there is a progress
+    /// callback.
+    nodes_expanded: u64,
+    /// The depth limit currently being searched
+    depth: u8,
+    /// `None` when nothing is listening for progress, in which case the
+    /// search skips the bookkeeping for it
+    progress_callback: Option<F>,
+    /// Set when `progress_callback` asks for the search to stop
+    cancelled: bool,
+    /// Whether to stop searching as soon as any solution is found
+    stop_at_first_solution: bool,
+}
+
+/// The number of nodes expanded between each invocation of the progress
+/// callback. This must be a power of two.
+const PROGRESS_CALLBACK_INTERVAL: u64 = 1 << 16;
+
+/// The progress callback type of searches that don't have one
+type NoProgressCallback = fn(SolverProgress) -> ControlFlow<()>;
+
+/// A snapshot of the solver's progress given to the callback of
+/// [`CycleStructureSolver::solve_with_callback`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SolverProgress {
+    /// The total number of nodes expanded so far across every depth limit
+    pub nodes_expanded: u64,
+    /// The depth limit currently being searched
+    pub depth: u8,
 }
 
 #[derive(Error, Debug)]
@@ -38,6 +91,16 @@ pub enum CycleStructureSolverError {
     MaxSolutionLengthExceeded,
     #[error("Time limit exceeded")]
     TimeLimitExceeded,
+    #[error("The search was cancelled before any solutions were found")]
+    Cancelled,
+}
+
+#[derive(Error, Debug)]
+pub enum ForMovesError {
+    #[error("The puzzle has no move named {0}")]
+    UnknownMove(String),
+    #[error("Failed to generate the pruning tables: {0}")]
+    PruningTableGeneration(#[from] OrbitPruningTableGenerationError),
 }
 
 /// The return type of the IDA* recursion function. It maintains the
@@ -55,14 +118,38 @@ impl AdmissibleGoalHeuristic {
 pub enum SearchStrategy {
     FirstSolution,
     AllSolutions,
+    /// Like `AllSolutions`, but the expanded solutions are yielded in
+    /// lexicographic order of their move indices in the puzzle definition
+    /// instead of the order the search happens to discover them in. Every
+    /// solution is expanded up front, so this is meant for reproducible tests
+    /// and benchmarks rather than for large solution sets.
+    AllSolutionsSorted,
 }
 
-impl<'id, P: PuzzleState<'id>, H: PuzzleStateHistory<'id, P>>
-    CycleStructureSolverMutable<'id, P, H>
+impl<
+    'id,
+    P: PuzzleState<'id>,
+    H: PuzzleStateHistory<'id, P>,
+    F: FnMut(SolverProgress) -> ControlFlow<()>,
+> CycleStructureSolverMutable<'id, P, H, F>
 {
     fn found_solution(&self) -> bool {
         !self.solutions.is_empty()
     }
+
+    fn report_progress(&mut self) {
+        let Some(progress_callback) = &mut self.progress_callback else {
+            return;
+        };
+        if progress_callback(SolverProgress {
+            nodes_expanded: self.nodes_expanded,
+            depth: self.depth,
+        })
+        .is_break()
+        {
+            self.cancelled = true;
+        }
+    }
 }
 
 #[derive(Debug)]
@@ -73,6 +160,9 @@ pub struct SolutionsIntoIter<'id, 'a, P: PuzzleState<'id>> {
     solutions: IntoIter<Vec<usize>>,
     expanded_count: usize,
     solution_length: usize,
+    /// Whether the search was cancelled before every solution at
+    /// `solution_length` was found
+    cancelled: bool,
     /// The buffer reused
     expanded_solution: Option<Box<[&'a Move<'id, P>]>>,
     /// The current solution from `solutions` being expanded upon
@@ -84,6 +174,9 @@ pub struct SolutionsIntoIter<'id, 'a, P: PuzzleState<'id>> {
     canonical_sequence_expansion_transformation: Vec<usize>,
     /// The state of the sequence symmetry expansion
     sequence_symmetry_expansion: Option<SequenceSymmetryExpansion>,
+    /// Every expanded solution as move indices in sorted order, when the
+    /// search strategy asks for them sorted
+    sorted_solutions: Option<IntoIter<Vec<usize>>>,
 }
 
 #[derive(Debug)]
@@ -97,6 +190,58 @@ struct CanonicalSequenceExpansion {
     expansion_intervals: Vec<(usize, usize)>,
 }
 
+impl<'id> CycleStructureSolver<'id, HeapPuzzle<'id>, OrbitPruningTables<'id, HeapPuzzle<'id>>> {
+    /// Set up a solver that finds every algorithm inducing the same cycle
+    /// structure as the move sequence `moves_str`, in at most as many moves.
+    /// If the move sequence is optimal, the solutions are its equally
+    /// efficient alternatives. The pruning tables use at most
+    /// `max_pruning_table_bytes` bytes.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the puzzle has no move with one of the names in
+    /// `moves_str` or if the pruning tables fail to generate.
+    pub fn for_moves(
+        puzzle_def: PuzzleDef<'id, HeapPuzzle<'id>>,
+        moves_str: &str,
+        max_pruning_table_bytes: usize,
+    ) -> Result<Self, ForMovesError> {
+        let mut result_1 = puzzle_def.new_solved_state();
+        let mut result_2 = result_1.clone();
+        let mut move_count = 0;
+
+        for name in moves_str.split_whitespace() {
+            let move_ = puzzle_def
+                .find_move(name)
+                .ok_or_else(|| ForMovesError::UnknownMove(name.to_owned()))?;
+            result_2.replace_compose(
+                &result_1,
+                move_.puzzle_state(),
+                puzzle_def.sorted_orbit_defs_ref(),
+            );
+            mem::swap(&mut result_1, &mut result_2);
+            move_count += 1;
+        }
+
+        let mut aux_mem = HeapPuzzle::new_aux_mem(puzzle_def.sorted_orbit_defs_ref());
+        let sorted_cycle_structure =
+            result_1.sorted_cycle_structure(puzzle_def.sorted_orbit_defs_ref(), &mut aux_mem);
+
+        let generate_meta = OrbitPruningTablesGenerateMeta::new(
+            &puzzle_def,
+            max_pruning_table_bytes,
+            puzzle_def.id(),
+        );
+        let pruning_tables =
+            OrbitPruningTables::try_generate_all(sorted_cycle_structure, generate_meta)?;
+
+        Ok(
+            Self::new(puzzle_def, pruning_tables, SearchStrategy::AllSolutions)
+                .with_max_solution_length(move_count),
+        )
+    }
+}
+
 impl<'id, P: PuzzleState<'id>, T: PruningTables<'id, P>> CycleStructureSolver<'id, P, T> {
     pub fn new(
         puzzle_def: PuzzleDef<'id, P>,
@@ -142,9 +287,12 @@ impl<'id, P: PuzzleState<'id>, T: PruningTables<'id, P>> CycleStructureSolver<'i
     /// the node is a solution.
     ///
     /// [IDA]: https://en.wikipedia.org/wiki/Iterative_deepening_A*
-    fn search_for_solution<H: PuzzleStateHistory<'id, P>>(
+    fn search_for_solution<
+        H: PuzzleStateHistory<'id, P>,
+        F: FnMut(SolverProgress) -> ControlFlow<()>,
+    >(
         &self,
-        mutable: &mut CycleStructureSolverMutable<'id, P, H>,
+        mutable: &mut CycleStructureSolverMutable<'id, P, H, F>,
         current_fsm_state: CanonicalFSMState,
         entry_index: usize,
         mut permitted_cost: u8,
@@ -152,6 +300,17 @@ impl<'id, P: PuzzleState<'id>, T: PruningTables<'id, P>> CycleStructureSolver<'i
         if log_enabled!(Level::Debug) {
             mutable.traversed += 1;
         }
+        if mutable.progress_callback.is_some() {
+            mutable.nodes_expanded += 1;
+            if mutable.nodes_expanded & (PROGRESS_CALLBACK_INTERVAL - 1) == 0 {
+                mutable.report_progress();
+                if mutable.cancelled {
+                    // The return value is meaningless because every caller
+                    // bails out as soon as it sees the cancellation
+                    return AdmissibleGoalHeuristic(1);
+                }
+            }
+        }
         // SAFETY: This function calls `pop_stack` for every `push_stack` call.
         // Therefore, the `pop_stack` cannot be called more than `push_stack`.
         let last_puzzle_state = unsafe { mutable.puzzle_state_history.last_state_unchecked() };
@@ -259,6 +418,9 @@ impl<'id, P: PuzzleState<'id>, T: PruningTables<'id, P>> CycleStructureSolver<'i
             let is_root = entry_index == 0;
             // This branch should have high predictability
             if is_root {
+                if !mutable.root_moves.contains(&move_index) {
+                    continue;
+                }
                 // Somehow it is faster to have this before the canonical
                 // sequence optimization??
                 mutable.root_canonical_fsm_reversed_state = unsafe {
@@ -358,7 +520,7 @@ impl<'id, P: PuzzleState<'id>, T: PruningTables<'id, P>> CycleStructureSolver<'i
             // If we've found a solution, and our search strategy is to
             // find the first solution, we instantly terminate. No more
             // processing will occur once this returns
-            if self.search_strategy == SearchStrategy::FirstSolution
+            if mutable.stop_at_first_solution
                 && child_admissible_goal_heuristic == AdmissibleGoalHeuristic::SOLVED
             {
                 // We don't care about preserving `mutable.puzzle_state_history`
@@ -366,6 +528,11 @@ impl<'id, P: PuzzleState<'id>, T: PruningTables<'id, P>> CycleStructureSolver<'i
                 return AdmissibleGoalHeuristic::SOLVED;
             }
 
+            // Likewise, no more processing occurs after a cancellation
+            if mutable.cancelled {
+                return child_admissible_goal_heuristic;
+            }
+
             mutable.puzzle_state_history.pop_stack();
 
             // Pathmax optimization. If the child node has a large pruning
@@ -411,9 +578,12 @@ impl<'id, P: PuzzleState<'id>, T: PruningTables<'id, P>> CycleStructureSolver<'i
         )
     }
 
-    fn prepare_next_depth<H: PuzzleStateHistory<'id, P>>(
+    fn prepare_next_depth<
+        H: PuzzleStateHistory<'id, P>,
+        F: FnMut(SolverProgress) -> ControlFlow<()>,
+    >(
         &self,
-        mutable: &mut CycleStructureSolverMutable<'id, P, H>,
+        mutable: &mut CycleStructureSolverMutable<'id, P, H, F>,
         next_depth: u8,
     ) -> Result<(), CycleStructureSolverError> {
         // During pathmax we increment the depth by one, so we ensure it
@@ -428,6 +598,7 @@ impl<'id, P: PuzzleState<'id>, T: PruningTables<'id, P>> CycleStructureSolver<'i
         }
         mutable.traversed = 0;
         mutable.tmp = 0;
+        mutable.depth = next_depth;
         mutable
             .puzzle_state_history
             .resize_if_needed(usize::from(next_depth));
@@ -442,59 +613,109 @@ impl<'id, P: PuzzleState<'id>, T: PruningTables<'id, P>> CycleStructureSolver<'i
     /// `CycleStructureSolverError`.
     pub fn solve<H: PuzzleStateHistory<'id, P>>(
         &self,
+    ) -> Result<SolutionsIntoIter<'id, '_, P>, CycleStructureSolverError> {
+        self.solve_with_optional_callback::<H, NoProgressCallback>(None)
+    }
+
+    /// Run Qter's cycle combination solver, periodically invoking
+    /// `progress_callback` with the number of nodes expanded and the current
+    /// depth limit. The callback is also invoked once before each new depth
+    /// limit is searched.
+    ///
+    /// Returning `ControlFlow::Break` from the callback stops the search. The
+    /// solutions found so far at the current depth limit are returned, and
+    /// [`SolutionsIntoIter::cancelled`] reports that the set may be incomplete.
+    ///
+    /// # Errors
+    ///
+    /// The solver will fail if it cannot find a solution, or if it was
+    /// cancelled before finding any. See `CycleStructureSolverError`.
+    pub fn solve_with_callback<H: PuzzleStateHistory<'id, P>>(
+        &self,
+        progress_callback: impl FnMut(SolverProgress) -> ControlFlow<()>,
+    ) -> Result<SolutionsIntoIter<'id, '_, P>, CycleStructureSolverError> {
+        self.solve_with_optional_callback::<H, _>(Some(progress_callback))
+    }
+
+    fn solve_with_optional_callback<
+        H: PuzzleStateHistory<'id, P>,
+        F: FnMut(SolverProgress) -> ControlFlow<()>,
+    >(
+        &self,
+        progress_callback: Option<F>,
     ) -> Result<SolutionsIntoIter<'id, '_, P>, CycleStructureSolverError> {
         info!(start!(
             "Beginning Cycle Combination Solver solution search..."
         ));
         let start = Instant::now();
 
-        let mut mutable: CycleStructureSolverMutable<P, H> = CycleStructureSolverMutable {
-            puzzle_state_history: (&self.puzzle_def).into(),
-            aux_mem: P::new_aux_mem(self.puzzle_def.sorted_orbit_defs_ref()),
-            solutions: vec![],
-            root_canonical_fsm_reversed_state: 0,
-            traversed: 0,
-            tmp: 0,
-        };
-        // SAFETY: `H::initialize` when puzzle_state_history is created
-        // guarantees that the first entry is bound
-        let last_puzzle_state = unsafe { mutable.puzzle_state_history.last_state_unchecked() };
-        let mut depth = self.pruning_tables.admissible_heuristic(last_puzzle_state);
-        // Manually check depth 0 because the `permitted_cost == 0` check was
-        // moved inside of the main loop in `search_for_solution`.
-        if depth == 0 {
-            debug!(working!("Searching depth limit {}..."), depth);
-            let depth_start = Instant::now();
-            // The return values here don't matter since it's not used in the
-            // below loop so we can get rid of `true` and `false`
-            if last_puzzle_state.induces_sorted_cycle_structure(
-                self.pruning_tables.sorted_cycle_structure_ref(),
-                self.puzzle_def.sorted_orbit_defs_ref(),
-                mutable.aux_mem.as_ref_mut(),
-            ) {
-                mutable
-                    .solutions
-                    .push(mutable.puzzle_state_history.create_move_history());
-            } else {
-                depth = 1;
-                self.prepare_next_depth(&mut mutable, depth)?;
+        let mut mutable: CycleStructureSolverMutable<P, H, _> =
+            self.new_mutable(progress_callback, 0..self.puzzle_def.moves.len());
+        let depth = self.deepen_until_solution(&mut mutable)?;
+
+        if mutable.cancelled {
+            if !mutable.found_solution() {
+                info!("Search cancelled at depth {}", depth);
+                return Err(CycleStructureSolverError::Cancelled);
             }
-            debug!(
-                working!("Traversed {} nodes in {}"),
-                mutable.traversed,
-                depth_start.elapsed().human(Truncate::Millis)
-            );
+            info!("Search cancelled at depth {}, keeping the solutions found so far", depth);
         }
 
+        info!(
+            success!("Found {} raw solutions at depth {} in {}"),
+            mutable.solutions.len(),
+            depth,
+            start.elapsed().human(Truncate::Millis)
+        );
+        debug!("");
+        Ok(self.solutions_into_iter(mutable.solutions, depth, mutable.cancelled))
+    }
+
+    /// Find the length of the optimal solutions without enumerating them. The
+    /// search stops at the first solution regardless of the search strategy,
+    /// so this is much cheaper than `solve` with
+    /// `SearchStrategy::AllSolutions`.
+    ///
+    /// # Errors
+    ///
+    /// The solver will fail if it cannot find a solution. See
+    /// `CycleStructureSolverError`.
+    pub fn optimal_length<H: PuzzleStateHistory<'id, P>>(
+        &self,
+    ) -> Result<usize, CycleStructureSolverError> {
+        let mut mutable: CycleStructureSolverMutable<P, H, _> =
+            self.new_mutable(None::<NoProgressCallback>, 0..self.puzzle_def.moves.len());
+        mutable.stop_at_first_solution = true;
+        let depth = self.deepen_until_solution(&mut mutable)?;
+        info!(success!("Optimal solution length is {}"), depth);
+        Ok(depth.into())
+    }
+
+    /// Iteratively deepen the search until a depth limit with a solution is
+    /// found or the progress callback cancels the search, returning the last
+    /// depth limit searched.
+    fn deepen_until_solution<
+        H: PuzzleStateHistory<'id, P>,
+        F: FnMut(SolverProgress) -> ControlFlow<()>,
+    >(
+        &self,
+        mutable: &mut CycleStructureSolverMutable<'id, P, H, F>,
+    ) -> Result<u8, CycleStructureSolverError> {
+        let mut depth = self.search_depth_zero(mutable)?;
+
         if !mutable.found_solution() {
-            self.prepare_next_depth(&mut mutable, depth)?;
+            self.prepare_next_depth(mutable, depth)?;
             loop {
+                mutable.report_progress();
+                if mutable.cancelled {
+                    break;
+                }
                 debug!(working!("Searching depth limit {}..."), depth);
                 let depth_start = Instant::now();
                 // `entry_index` must be zero here so the root level so sequence
                 // symmetry doesn't access OOB move history entries.
                 self.search_for_solution(
-                    &mut mutable,
+                    mutable,
                     CanonicalFSMState::default(),
                     // Remember that `i` must be initialized to zero for the
                     // sequence symmetry optimization to work.
@@ -507,6 +728,80 @@ impl<'id, P: PuzzleState<'id>, T: PruningTables<'id, P>> CycleStructureSolver<'i
                     depth_start.elapsed().human(Truncate::Millis),
                     mutable.tmp,
                 );
+                if mutable.found_solution() || mutable.cancelled {
+                    break;
+                }
+                depth += 1;
+                self.prepare_next_depth(mutable, depth)?;
+            }
+        }
+
+        Ok(depth)
+    }
+
+    /// Run Qter's cycle combination solver, splitting every depth limit of the
+    /// search across a rayon thread pool by the first move of the sequence.
+    /// Each worker carries its own puzzle state history and auxiliary memory
+    /// while sharing the puzzle definition and pruning tables.
+    ///
+    /// The raw solutions are merged in the order of their first move, so the
+    /// result is identical to that of `solve`.
+    ///
+    /// # Errors
+    ///
+    /// The solver will fail if it cannot find a solution. See
+    /// `CycleStructureSolverError`.
+    pub fn solve_parallel<H: PuzzleStateHistory<'id, P>>(
+        &self,
+    ) -> Result<SolutionsIntoIter<'id, '_, P>, CycleStructureSolverError>
+    where
+        Self: Sync,
+    {
+        info!(start!(
+            "Beginning parallel Cycle Combination Solver solution search..."
+        ));
+        let start = Instant::now();
+
+        let mut mutable: CycleStructureSolverMutable<P, H, _> =
+            self.new_mutable(None::<NoProgressCallback>, 0..self.puzzle_def.moves.len());
+        let mut depth = self.search_depth_zero(&mut mutable)?;
+
+        if !mutable.found_solution() {
+            self.prepare_next_depth(&mut mutable, depth)?;
+            loop {
+                debug!(working!("Searching depth limit {} in parallel..."), depth);
+                let depth_start = Instant::now();
+                let root_results = (0..self.puzzle_def.moves.len())
+                    .into_par_iter()
+                    .map(|root_move_index| {
+                        let mut worker: CycleStructureSolverMutable<P, H, _> = self.new_mutable(
+                            None::<NoProgressCallback>,
+                            root_move_index..root_move_index + 1,
+                        );
+                        worker
+                            .puzzle_state_history
+                            .resize_if_needed(usize::from(depth));
+                        worker.depth = depth;
+                        self.search_for_solution(
+                            &mut worker,
+                            CanonicalFSMState::default(),
+                            0,
+                            depth,
+                        );
+                        (worker.solutions, worker.traversed)
+                    })
+                    // Collecting into a `Vec` preserves the order of the root
+                    // moves regardless of which worker finishes first
+                    .collect::<Vec<_>>();
+                for (solutions, traversed) in root_results {
+                    mutable.solutions.extend(solutions);
+                    mutable.traversed += traversed;
+                }
+                debug!(
+                    working!("Traversed {} nodes in {}"),
+                    mutable.traversed,
+                    depth_start.elapsed().human(Truncate::Millis),
+                );
                 if mutable.found_solution() {
                     break;
                 }
@@ -515,6 +810,10 @@ impl<'id, P: PuzzleState<'id>, T: PruningTables<'id, P>> CycleStructureSolver<'i
             }
         }
 
+        if self.search_strategy == SearchStrategy::FirstSolution {
+            mutable.solutions.truncate(1);
+        }
+
         info!(
             success!("Found {} raw solutions at depth {} in {}"),
             mutable.solutions.len(),
@@ -522,21 +821,199 @@ impl<'id, P: PuzzleState<'id>, T: PruningTables<'id, P>> CycleStructureSolver<'i
             start.elapsed().human(Truncate::Millis)
         );
         debug!("");
+        Ok(self.solutions_into_iter(mutable.solutions, depth, false))
+    }
+
+    /// Run Qter's cycle combination solver at every depth limit up to and
+    /// including `max_depth` instead of stopping at the optimal solution
+    /// length. The solutions that the search finds at each depth that has any
+    /// are returned in order of increasing length. An empty result means that
+    /// there is no solution of length at most `max_depth`.
+    ///
+    /// Each depth is searched exactly like `solve` searches its final depth,
+    /// so this is not every sequence of each length. Sequences that are
+    /// conjugate to a shorter sequence, such as those that begin and end with
+    /// the same move class, are pruned at every depth, which makes the counts
+    /// at longer depths a measure of how many essentially different solutions
+    /// there are. With `SearchStrategy::FirstSolution`, the search stops at
+    /// the first depth that has a solution.
+    ///
+    /// # Errors
+    ///
+    /// The solver will fail if the search cannot begin. See
+    /// `CycleStructureSolverError`.
+    pub fn solve_each_depth_up_to<H: PuzzleStateHistory<'id, P>>(
+        &self,
+        max_depth: usize,
+    ) -> Result<Vec<SolutionsIntoIter<'id, '_, P>>, CycleStructureSolverError> {
+        info!(start!(
+            "Beginning depth-bounded Cycle Combination Solver solution search..."
+        ));
+        let start = Instant::now();
+
+        // `prepare_next_depth` rejects these depths, so we stop just short of
+        // them instead of erroring
+        let mut max_depth = max_depth.min(usize::from(u8::MAX - 1));
+        if let Some(max_solution_length) = self.max_solution_length {
+            max_depth = max_depth.min(max_solution_length);
+        }
+
+        let mut mutable: CycleStructureSolverMutable<P, H, _> =
+            self.new_mutable(None::<NoProgressCallback>, 0..self.puzzle_def.moves.len());
+        let mut all_solutions = vec![];
+        let mut depth = self.search_depth_zero(&mut mutable)?;
+        if mutable.found_solution() {
+            all_solutions.push(self.solutions_into_iter(
+                mem::take(&mut mutable.solutions),
+                0,
+                false,
+            ));
+            depth = 1;
+        }
+
+        while usize::from(depth) <= max_depth
+            && !(self.search_strategy == SearchStrategy::FirstSolution
+                && !all_solutions.is_empty())
+        {
+            self.prepare_next_depth(&mut mutable, depth)?;
+            debug!(working!("Searching depth limit {}..."), depth);
+            let depth_start = Instant::now();
+            self.search_for_solution(&mut mutable, CanonicalFSMState::default(), 0, depth);
+            debug!(
+                working!("Traversed {} nodes in {}"),
+                mutable.traversed,
+                depth_start.elapsed().human(Truncate::Millis),
+            );
+            if mutable.found_solution() {
+                all_solutions.push(self.solutions_into_iter(
+                    mem::take(&mut mutable.solutions),
+                    depth,
+                    false,
+                ));
+            }
+            depth += 1;
+        }
+
+        info!(
+            success!("Found raw solutions at {} depths up to {} in {}"),
+            all_solutions.len(),
+            max_depth,
+            start.elapsed().human(Truncate::Millis)
+        );
+        debug!("");
+        Ok(all_solutions)
+    }
+
+    fn new_mutable<H: PuzzleStateHistory<'id, P>, F: FnMut(SolverProgress) -> ControlFlow<()>>(
+        &self,
+        progress_callback: Option<F>,
+        root_moves: Range<usize>,
+    ) -> CycleStructureSolverMutable<'id, P, H, F> {
+        CycleStructureSolverMutable {
+            puzzle_state_history: (&self.puzzle_def).into(),
+            aux_mem: P::new_aux_mem(self.puzzle_def.sorted_orbit_defs_ref()),
+            solutions: vec![],
+            root_canonical_fsm_reversed_state: 0,
+            root_moves,
+            traversed: 0,
+            tmp: 0,
+            nodes_expanded: 0,
+            depth: 0,
+            progress_callback,
+            cancelled: false,
+            stop_at_first_solution: self.search_strategy == SearchStrategy::FirstSolution,
+        }
+    }
+
+    /// Check whether the solved state is itself a solution and return the
+    /// first depth limit to search.
+    fn search_depth_zero<
+        H: PuzzleStateHistory<'id, P>,
+        F: FnMut(SolverProgress) -> ControlFlow<()>,
+    >(
+        &self,
+        mutable: &mut CycleStructureSolverMutable<'id, P, H, F>,
+    ) -> Result<u8, CycleStructureSolverError> {
+        // SAFETY: `H::initialize` when puzzle_state_history is created
+        // guarantees that the first entry is bound
+        let last_puzzle_state = unsafe { mutable.puzzle_state_history.last_state_unchecked() };
+        let mut depth = self.pruning_tables.admissible_heuristic(last_puzzle_state);
+        // Manually check depth 0 because the `permitted_cost == 0` check was
+        // moved inside of the main loop in `search_for_solution`.
+        if depth == 0 {
+            debug!(working!("Searching depth limit {}..."), depth);
+            let depth_start = Instant::now();
+            // The return values here don't matter since it's not used in the
+            // below loop so we can get rid of `true` and `false`
+            if last_puzzle_state.induces_sorted_cycle_structure(
+                self.pruning_tables.sorted_cycle_structure_ref(),
+                self.puzzle_def.sorted_orbit_defs_ref(),
+                mutable.aux_mem.as_ref_mut(),
+            ) {
+                mutable
+                    .solutions
+                    .push(mutable.puzzle_state_history.create_move_history());
+            } else {
+                depth = 1;
+                self.prepare_next_depth(mutable, depth)?;
+            }
+            debug!(
+                working!("Traversed {} nodes in {}"),
+                mutable.traversed,
+                depth_start.elapsed().human(Truncate::Millis)
+            );
+        }
+        Ok(depth)
+    }
+
+    fn solutions_into_iter(
+        &self,
+        solutions: Vec<Vec<usize>>,
+        depth: u8,
+        cancelled: bool,
+    ) -> SolutionsIntoIter<'id, '_, P> {
         let result_1 = self.puzzle_def.new_solved_state();
         let result_2 = result_1.clone();
-        Ok(SolutionsIntoIter {
+        let mut solutions_into_iter = SolutionsIntoIter {
             puzzle_def: &self.puzzle_def,
             result_1,
             result_2,
-            solutions: mutable.solutions.into_iter(),
+            solutions: solutions.into_iter(),
             expanded_count: 0,
             solution_length: depth.into(),
+            cancelled,
             expanded_solution: None,
             currently_expanding_solution: None,
             canonical_sequence_expansion: None,
             canonical_sequence_expansion_transformation: (0..depth.into()).collect_vec(),
             sequence_symmetry_expansion: None,
-        })
+            sorted_solutions: None,
+        };
+
+        if self.search_strategy == SearchStrategy::AllSolutionsSorted {
+            let mut sorted_solutions = vec![];
+            while solutions_into_iter.next().is_some() {
+                sorted_solutions.push(
+                    solutions_into_iter
+                        .expanded_solution()
+                        .iter()
+                        .map(|&move_| {
+                            self.puzzle_def
+                                .moves
+                                .iter()
+                                .position(|other| ptr::eq(other, move_))
+                                .unwrap()
+                        })
+                        .collect_vec(),
+                );
+            }
+            sorted_solutions.sort_unstable();
+
+            solutions_into_iter.expanded_count = 0;
+            solutions_into_iter.sorted_solutions = Some(sorted_solutions.into_iter());
+        }
+
+        solutions_into_iter
     }
 }
 
@@ -549,6 +1026,19 @@ impl<'id, P: PuzzleState<'id>> Iterator for SolutionsIntoIter<'id, '_, P> {
     // scope) preventing immutable methods from being called on self.
     // See: https://doc.rust-lang.org/nomicon/lifetime-mismatch.html
     fn next(&mut self) -> Option<()> {
+        if let Some(sorted_solutions) = &mut self.sorted_solutions {
+            let sorted_solution = sorted_solutions.next()?;
+            let puzzle_def = self.puzzle_def;
+            self.expanded_solution = Some(
+                sorted_solution
+                    .into_iter()
+                    .map(|move_index| &puzzle_def.moves[move_index])
+                    .collect(),
+            );
+            self.expanded_count += 1;
+            return Some(());
+        }
+
         // The current expansion order:
         //
         // - Canonical sequences
@@ -774,6 +1264,30 @@ impl<'id, 'a, P: PuzzleState<'id>> SolutionsIntoIter<'id, 'a, P> {
         self.expanded_solution.as_ref().unwrap()
     }
 
+    /// Returns the expanded solution as an `Algorithm` on `group` so that it
+    /// can be applied to a puzzle without round-tripping through a string.
+    /// The moves are looked up in `group` by name.
+    ///
+    /// # Errors
+    ///
+    /// Returns the name of the first move that `group` doesn't have
+    ///
+    /// # Panics
+    ///
+    /// Panics if this is called before `.next()`
+    pub fn expanded_algorithm(
+        &self,
+        group: Arc<PermutationGroup>,
+    ) -> Result<Algorithm, ArcIntern<str>> {
+        Algorithm::new_from_move_seq(
+            group,
+            self.expanded_solution()
+                .iter()
+                .map(|move_| ArcIntern::from(move_.name()))
+                .collect(),
+        )
+    }
+
     #[must_use]
     pub fn puzzle_def(&self) -> &PuzzleDef<'id, P> {
         self.puzzle_def
@@ -788,6 +1302,13 @@ impl<'id, 'a, P: PuzzleState<'id>> SolutionsIntoIter<'id, 'a, P> {
     pub fn expanded_count(&self) -> usize {
         self.expanded_count
     }
+
+    /// Whether the search was cancelled by the progress callback before
+    /// every solution of `solution_length` was found.
+    #[must_use]
+    pub fn cancelled(&self) -> bool {
+        self.cancelled
+    }
 }
 
 fn pandita1(perm: &mut [usize]) -> bool {
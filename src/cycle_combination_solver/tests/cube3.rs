@@ -1,4 +1,9 @@
+use std::sync::Arc;
+
 use cycle_combination_solver::{
+    architecture::{
+        find_architecture, find_architecture_for_cycle_structures, find_cycle_structures,
+    },
     make_guard,
     pruning::{
         OrbitPruningTables, OrbitPruningTablesGenerateMeta, PruningTables, StorageBackendTy,
@@ -7,9 +12,13 @@ use cycle_combination_solver::{
     puzzle::{
         PuzzleDef, PuzzleState, SortedCycleStructure, cube3::Cube3, slice_puzzle::HeapPuzzle,
     },
-    solver::{CycleStructureSolver, CycleStructureSolverError, SearchStrategy},
+    solver::{CycleStructureSolver, CycleStructureSolverError, ForMovesError, SearchStrategy},
+};
+use puzzle_theory::{
+    numbers::{Int, U},
+    permutations::Algorithm,
+    puzzle_geometry::parsing::puzzle,
 };
-use puzzle_theory::puzzle_geometry::parsing::puzzle;
 
 use crate::common::OptimalCycleStructureTest;
 
@@ -121,6 +130,68 @@ fn test_single_half_turn() {
     assert_eq!(solutions.expanded_count(), 6);
 }
 
+#[test_log::test]
+fn test_sorted_solutions() {
+    make_guard!(guard);
+    let cube3_def = PuzzleDef::<Cube3>::new(&puzzle("3x3").ksolve(), guard).unwrap();
+    let sorted_cycle_structure = SortedCycleStructure::new(
+        &[vec![(1, true), (5, true)], vec![(1, true), (7, true)]],
+        cube3_def.sorted_orbit_defs_ref(),
+    )
+    .unwrap();
+    let solver: CycleStructureSolver<Cube3, _> = CycleStructureSolver::new(
+        cube3_def,
+        ZeroTable::try_generate_all(sorted_cycle_structure, ()).unwrap(),
+        SearchStrategy::AllSolutionsSorted,
+    );
+
+    let mut solutions = solver.solve::<[Cube3; 21]>().unwrap();
+    let mut move_indices = vec![];
+    while solutions.next().is_some() {
+        move_indices.push(
+            solutions
+                .expanded_solution()
+                .iter()
+                .map(|move_| {
+                    solutions
+                        .puzzle_def()
+                        .moves
+                        .iter()
+                        .position(|other| other.name() == move_.name())
+                        .unwrap()
+                })
+                .collect::<Vec<_>>(),
+        );
+    }
+
+    assert_eq!(solutions.expanded_count(), 480);
+    assert!(move_indices.is_sorted());
+}
+
+#[test_log::test]
+fn test_expanded_algorithm() {
+    make_guard!(guard);
+    let cube3_def = PuzzleDef::<Cube3>::new(&puzzle("3x3").ksolve(), guard).unwrap();
+    let sorted_cycle_structure = SortedCycleStructure::new(
+        &[vec![(2, false), (2, false)], vec![(2, false), (2, false)]],
+        cube3_def.sorted_orbit_defs_ref(),
+    )
+    .unwrap();
+    let solver: CycleStructureSolver<Cube3, _> = CycleStructureSolver::new(
+        cube3_def,
+        ZeroTable::try_generate_all(sorted_cycle_structure, ()).unwrap(),
+        SearchStrategy::AllSolutions,
+    );
+    let group = puzzle("3x3").permutation_group();
+    let mut solutions = solver.solve::<[Cube3; 21]>().unwrap();
+    while solutions.next().is_some() {
+        let name = solutions.expanded_solution()[0].name();
+        let algorithm = solutions.expanded_algorithm(Arc::clone(&group)).unwrap();
+        let expected = Algorithm::parse_from_string(Arc::clone(&group), name).unwrap();
+        assert_eq!(algorithm.permutation(), expected.permutation());
+    }
+}
+
 #[test_log::test]
 fn test_optimal_subgroup_cycle() {
     make_guard!(guard);
@@ -176,6 +247,8 @@ fn test_210_optimal_cycle() {
         SearchStrategy::AllSolutions,
     );
 
+    assert_eq!(solver.optimal_length::<[Cube3; 21]>().unwrap(), 5);
+
     let mut solutions = solver.solve::<[Cube3; 21]>().unwrap();
     assert_eq!(solutions.solution_length(), 5);
     while solutions.next().is_some() {}
@@ -601,3 +674,99 @@ fn test_many_optimal_cycles() {
         cube3_def = solver.into_puzzle_def_and_pruning_tables().0;
     }
 }
+
+#[test_log::test]
+fn test_find_architecture_for_cycle_structures() {
+    let architecture = find_architecture_for_cycle_structures(
+        &puzzle("3x3"),
+        &[vec![vec![(4, false)], vec![(4, false)]]],
+        1_000_000,
+    )
+    .unwrap();
+
+    assert_eq!(architecture.registers().len(), 1);
+    assert_eq!(architecture.registers()[0].order(), Int::<U>::from(4_u32));
+}
+
+#[test_log::test]
+fn test_find_architecture() {
+    let architecture = find_architecture(&puzzle("3x3"), &[4], 1_000_000).unwrap();
+
+    assert_eq!(architecture.registers().len(), 1);
+    assert_eq!(architecture.registers()[0].order(), Int::<U>::from(4_u32));
+}
+
+#[test_log::test]
+fn test_find_cycle_structures() {
+    make_guard!(guard);
+    let cube3_def = PuzzleDef::<HeapPuzzle>::new(&puzzle("3x3").ksolve(), guard).unwrap();
+    let orbit_defs = cube3_def.describe_orbits();
+
+    let cycle_structures = find_cycle_structures(&cube3_def, &[90, 90]).unwrap();
+    assert_eq!(cycle_structures.len(), 2);
+
+    for cycle_structure in &cycle_structures {
+        assert!(
+            SortedCycleStructure::new(cycle_structure, cube3_def.sorted_orbit_defs_ref()).is_ok()
+        );
+
+        let order = cycle_structure
+            .iter()
+            .zip(&orbit_defs)
+            .flat_map(|(cycles, orbit_def)| {
+                cycles.iter().map(|&(length, oriented)| {
+                    u64::from(length)
+                        * if oriented {
+                            u64::from(orbit_def.orientation_count)
+                        } else {
+                            1
+                        }
+                })
+            })
+            .fold(1, |order, cycle_order| {
+                let (mut a, mut b) = (order, cycle_order);
+                while b != 0 {
+                    (a, b) = (b, a % b);
+                }
+                order / a * cycle_order
+            });
+        assert_eq!(order, 90);
+    }
+
+    // The registers can't share pieces
+    for (orbit_index, orbit_def) in orbit_defs.iter().enumerate() {
+        let pieces = cycle_structures
+            .iter()
+            .flat_map(|cycle_structure| &cycle_structure[orbit_index])
+            .map(|&(length, _)| length)
+            .sum::<u8>();
+        assert!(pieces <= orbit_def.piece_count);
+    }
+
+    // A 1260 cycle takes up nearly the whole cube
+    assert!(find_cycle_structures(&cube3_def, &[1260]).is_some());
+    assert!(find_cycle_structures(&cube3_def, &[1260, 1260]).is_none());
+}
+
+#[test_log::test]
+fn test_for_moves() {
+    make_guard!(guard);
+    let cube3_def = PuzzleDef::<HeapPuzzle>::new(&puzzle("3x3").ksolve(), guard).unwrap();
+
+    let solver = CycleStructureSolver::for_moves(cube3_def, "R U", 1_000_000).unwrap();
+
+    let mut solutions = solver.solve::<Vec<_>>().unwrap();
+    assert_eq!(solutions.solution_length(), 2);
+    assert!(solutions.next().is_some());
+}
+
+#[test_log::test]
+fn test_for_moves_unknown_move() {
+    make_guard!(guard);
+    let cube3_def = PuzzleDef::<HeapPuzzle>::new(&puzzle("3x3").ksolve(), guard).unwrap();
+
+    assert!(matches!(
+        CycleStructureSolver::for_moves(cube3_def, "R Q", 1_000_000),
+        Err(ForMovesError::UnknownMove(name)) if name == "Q"
+    ));
+}
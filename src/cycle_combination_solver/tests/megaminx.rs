@@ -1,10 +1,30 @@
 use cycle_combination_solver::{
     pruning::{PruningTables, ZeroTable},
     puzzle::{PuzzleDef, PuzzleState, SortedCycleStructure, apply_moves, slice_puzzle::HeapPuzzle},
-    solver::{CycleStructureSolver, SearchStrategy},
+    solver::{CycleStructureSolver, CycleStructureSolverError, SearchStrategy, SolutionsIntoIter},
 };
 use generativity::make_guard;
 use puzzle_theory::puzzle_geometry::parsing::puzzle;
+use std::ops::ControlFlow;
+
+/// Every expanded solution as a list of move names, sorted so that
+/// searches that find solutions in different orders can be compared
+fn all_solutions<'id, P: PuzzleState<'id>>(
+    solutions: &mut SolutionsIntoIter<'id, '_, P>,
+) -> Vec<Vec<String>> {
+    let mut all = Vec::new();
+    while solutions.next().is_some() {
+        all.push(
+            solutions
+                .expanded_solution()
+                .iter()
+                .map(|move_| move_.name().to_owned())
+                .collect(),
+        );
+    }
+    all.sort();
+    all
+}
 
 #[test_log::test]
 fn test_move_powers() {
@@ -101,3 +121,123 @@ fn test_random3() {
     while solutions.next().is_some() {}
     assert_eq!(solutions.expanded_count(), 720);
 }
+
+#[test_log::test]
+fn test_progress_callback() {
+    make_guard!(guard);
+    let megaminx_def = PuzzleDef::<HeapPuzzle>::new(&puzzle("megaminx").ksolve(), guard).unwrap();
+    let sorted_cycle_structure = SortedCycleStructure::new(
+        &[
+            vec![(1, true), (1, true), (5, false), (9, true)],
+            vec![(5, false), (13, false)],
+        ],
+        megaminx_def.sorted_orbit_defs_ref(),
+    )
+    .unwrap();
+    let solver: CycleStructureSolver<HeapPuzzle, _> = CycleStructureSolver::new(
+        megaminx_def,
+        ZeroTable::try_generate_all(sorted_cycle_structure, ()).unwrap(),
+        SearchStrategy::AllSolutions,
+    );
+
+    let mut max_depth = 0;
+    let mut solutions = solver
+        .solve_with_callback::<Vec<_>>(|progress| {
+            max_depth = max_depth.max(progress.depth);
+            ControlFlow::Continue(())
+        })
+        .unwrap();
+    assert_eq!(max_depth, 4);
+    assert!(!solutions.cancelled());
+
+    // Reporting progress must not change what is found
+    let mut expected = solver.solve::<Vec<_>>().unwrap();
+    assert_eq!(solutions.solution_length(), expected.solution_length());
+    assert_eq!(all_solutions(&mut solutions), all_solutions(&mut expected));
+    assert_eq!(solutions.expanded_count(), expected.expanded_count());
+
+    assert!(matches!(
+        solver.solve_with_callback::<Vec<_>>(|_| ControlFlow::Break(())),
+        Err(CycleStructureSolverError::Cancelled)
+    ));
+}
+
+#[test_log::test]
+fn test_parallel() {
+    make_guard!(guard);
+    let megaminx_def = PuzzleDef::<HeapPuzzle>::new(&puzzle("megaminx").ksolve(), guard).unwrap();
+    let sorted_cycle_structure = SortedCycleStructure::new(
+        &[
+            vec![(2, true), (14, true)],
+            vec![(5, true), (6, false), (10, true)],
+        ],
+        megaminx_def.sorted_orbit_defs_ref(),
+    )
+    .unwrap();
+    let solver: CycleStructureSolver<HeapPuzzle, _> = CycleStructureSolver::new(
+        megaminx_def,
+        ZeroTable::try_generate_all(sorted_cycle_structure, ()).unwrap(),
+        SearchStrategy::AllSolutions,
+    );
+
+    let mut solutions = solver.solve_parallel::<Vec<_>>().unwrap();
+    let mut expected = solver.solve::<Vec<_>>().unwrap();
+    assert_eq!(solutions.solution_length(), expected.solution_length());
+    assert_eq!(all_solutions(&mut solutions), all_solutions(&mut expected));
+    assert_eq!(solutions.expanded_count(), expected.expanded_count());
+}
+
+#[test_log::test]
+fn test_each_depth_up_to() {
+    make_guard!(guard);
+    let megaminx_def = PuzzleDef::<HeapPuzzle>::new(&puzzle("megaminx").ksolve(), guard).unwrap();
+    let sorted_cycle_structure = SortedCycleStructure::new(
+        &[vec![(1, true), (1, true), (9, true)], vec![(13, false)]],
+        megaminx_def.sorted_orbit_defs_ref(),
+    )
+    .unwrap();
+    let solver: CycleStructureSolver<HeapPuzzle, _> = CycleStructureSolver::new(
+        megaminx_def,
+        ZeroTable::try_generate_all(sorted_cycle_structure.clone(), ()).unwrap(),
+        SearchStrategy::AllSolutions,
+    );
+
+    assert!(solver.solve_each_depth_up_to::<Vec<_>>(2).unwrap().is_empty());
+
+    // The optimal length is 3, so this goes one past it
+    let mut by_depth = solver.solve_each_depth_up_to::<Vec<_>>(4).unwrap();
+    assert_eq!(
+        by_depth
+            .iter()
+            .map(|solutions| solutions.solution_length())
+            .collect::<Vec<_>>(),
+        [3, 4]
+    );
+
+    // The optimal depth matches `solve`
+    let mut optimal = solver.solve::<Vec<_>>().unwrap();
+    assert_eq!(all_solutions(&mut by_depth[0]), all_solutions(&mut optimal));
+    assert_eq!(by_depth[0].expanded_count(), 720);
+
+    // Every solution past the optimal length is distinct and actually has the
+    // cycle structure
+    let mut longer_solutions = all_solutions(&mut by_depth[1]);
+    let puzzle_def = by_depth[1].puzzle_def();
+    let sorted_orbit_defs = puzzle_def.sorted_orbit_defs_ref();
+    let mut aux_mem = HeapPuzzle::new_aux_mem(sorted_orbit_defs);
+    let solved = puzzle_def.new_solved_state();
+
+    assert!(!longer_solutions.is_empty());
+    for solution in &longer_solutions {
+        assert_eq!(solution.len(), 4);
+        let state = apply_moves(puzzle_def, &solved, &solution.join(" "), 1);
+        assert!(state.induces_sorted_cycle_structure(
+            sorted_cycle_structure.as_ref(),
+            sorted_orbit_defs,
+            aux_mem.as_ref_mut(),
+        ));
+    }
+    let count = longer_solutions.len();
+    longer_solutions.dedup();
+    assert_eq!(longer_solutions.len(), count);
+}
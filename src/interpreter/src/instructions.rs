@@ -36,7 +36,14 @@ impl PuzzleInstructionImpl for SolvedGoto {
         instr: &'a Self::Theoretical<'static>,
         state: &mut InterpreterState<P>,
     ) -> ActionPerformed<'a> {
-        if Int::is_zero(&state.puzzle_states.theoretical_state(instr.1).value()) {
+        let value = state.puzzle_states.theoretical_state(instr.1).value();
+
+        let solved = match instr.2 {
+            Some(modulus) => (value % modulus).is_zero(),
+            None => value.is_zero(),
+        };
+
+        if solved {
             state.program_counter = instr.0.instruction_idx;
 
             ActionPerformed::SucceededSolvedGoto(ByPuzzleType::Theoretical((
@@ -113,11 +120,12 @@ impl PuzzleInstructionImpl for Input {
         instr: &'a Self::Puzzle<'static>,
         state: &mut InterpreterState<P>,
     ) -> Result<ActionPerformed<'a>, P::Error> {
+        let chromatic_orders = chromatic_orders_by_facelets(&instr.2);
         let order = instr
             .3
             .facelets()
             .iter()
-            .map(|facelet| chromatic_orders_by_facelets(&instr.2)[*facelet])
+            .map(|facelet| chromatic_orders[*facelet])
             .fold(Int::<U>::one(), lcm);
 
         Ok(input_impl(
@@ -130,6 +138,15 @@ impl PuzzleInstructionImpl for Input {
     }
 }
 
+/// Substitute the decoded register value into the message's `{}` placeholder, or append it if the message doesn't have one
+fn format_message(message: &str, decoded: Int<U>) -> String {
+    if message.contains("{}") {
+        message.replacen("{}", &decoded.to_string(), 1)
+    } else {
+        format!("{message} {decoded}")
+    }
+}
+
 fn perform_halt<'a, P: PuzzleState>(
     maybe_decoded: Option<(Int<U>, ByPuzzleType<'static, PuzzleAndRegister>)>,
     instr: &'a Halt,
@@ -140,7 +157,7 @@ fn perform_halt<'a, P: PuzzleState>(
             maybe_puzzle_idx_and_register: Some(puzzle_idx_and_register),
         });
 
-        format!("{} {decoded}", instr.message)
+        format_message(&instr.message, decoded)
     } else {
         state.execution_state = ExecutionState::Paused(PausedState::Halt {
             maybe_puzzle_idx_and_register: None,
@@ -205,9 +222,7 @@ fn perform_print<'a, P: PuzzleState>(
     state.execution_state = ExecutionState::Running;
 
     let full_message = match maybe_decoded {
-        Some(decoded) => {
-            format!("{} {decoded}", instr.message)
-        }
+        Some(decoded) => format_message(&instr.message, decoded),
         None => instr.message.clone(),
     };
     state.messages.push_back(full_message);
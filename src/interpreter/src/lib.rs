@@ -4,17 +4,22 @@
 mod instructions;
 pub mod puzzle_states;
 
-use std::{collections::VecDeque, mem, sync::Arc};
+use std::{
+    collections::{BTreeSet, VecDeque},
+    mem,
+    sync::Arc,
+};
 
 use instructions::do_instr;
-use puzzle_states::{PuzzleState, PuzzleStates};
+use puzzle_states::{PuzzleState, PuzzleStates, TheoreticalState};
 use puzzle_theory::{
     numbers::{I, Int, U},
-    permutations::Algorithm,
+    permutations::{Algorithm, Permutation},
 };
 use qter_core::{
-    ByPuzzleType, Facelets, Instruction, Program, PuzzleIdx, SeparatesByPuzzleType, StateIdx,
-    TheoreticalIdx,
+    ByPuzzleType, Facelets, Instruction, Program, PuzzleIdx, RepeatUntil, SeparatesByPuzzleType,
+    StateIdx, TheoreticalIdx,
+    architectures::{Architecture, new_from_effect},
 };
 
 pub struct PuzzleAndRegister;
@@ -44,17 +49,63 @@ pub enum ExecutionState {
     Paused(PausedState),
 }
 
+/// How to revert the effect that an instruction had on the registers
+enum Undo {
+    Nothing,
+    SetTheoretical {
+        idx: TheoreticalIdx,
+        value: Int<U>,
+    },
+    ComposePuzzle {
+        idx: PuzzleIdx,
+        inverse: Algorithm,
+    },
+    RestorePuzzle {
+        idx: PuzzleIdx,
+        state: Permutation,
+    },
+    Unsupported,
+}
+
+/// A record of an executed instruction that allows it to be undone
+struct HistoryEntry {
+    program_counter: usize,
+    undo: Undo,
+}
+
+/// The reason why the interpreter failed to step backwards
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StepBackError {
+    /// No instructions have been executed yet
+    NoHistory,
+    /// The puzzle state cannot restore its state from before the instruction was executed
+    Unsupported,
+}
+
+/// How far a register has counted towards its order, for displaying progress
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct RegisterProgress {
+    /// The value of the register, or `None` if the puzzle is in a state that the register can't be decoded from
+    pub value: Option<Int<U>>,
+    pub order: Int<U>,
+}
+
 pub struct InterpreterState<P: PuzzleState> {
     puzzle_states: PuzzleStates<P>,
     program_counter: usize,
     messages: VecDeque<String>,
     execution_state: ExecutionState,
+    history: VecDeque<HistoryEntry>,
+    /// The maximum number of entries kept in `history`, where zero means that no history is recorded
+    history_limit: usize,
+    input_queue: VecDeque<Int<I>>,
 }
 
 /// An interpreter for a qter program
 pub struct Interpreter<P: PuzzleState> {
     state: InterpreterState<P>,
     program: Arc<Program>,
+    breakpoints: BTreeSet<usize>,
 }
 
 pub struct FaceletsByType;
@@ -128,6 +179,145 @@ impl<P: PuzzleState> InterpreterState<P> {
         &mut self.messages
     }
 
+    /// Get the state of a theoretical register
+    #[must_use]
+    pub fn theoretical_state(&self, idx: TheoreticalIdx) -> &TheoreticalState {
+        self.puzzle_states.theoretical_state(idx)
+    }
+
+    /// Get the state of a puzzle
+    #[must_use]
+    pub fn puzzle_state(&self, idx: PuzzleIdx) -> &P {
+        self.puzzle_states.puzzle_state(idx)
+    }
+
+    /// Get the state of a puzzle mutably. Changes made through this are not recorded in the execution history.
+    pub fn puzzle_state_mut(&mut self, idx: PuzzleIdx) -> &mut P {
+        self.puzzle_states.puzzle_state_mut(idx)
+    }
+
+    /// Whether there is an executed instruction that can be undone with `step_back`
+    #[must_use]
+    pub fn can_step_back(&self) -> bool {
+        self.history
+            .back()
+            .is_some_and(|entry| !matches!(entry.undo, Undo::Unsupported))
+    }
+
+    /// Forget the execution history, freeing the memory used by it
+    pub fn clear_history(&mut self) {
+        self.history.clear();
+    }
+
+    /// Start recording executed instructions so that they can be undone with `step_back`, remembering at most the `max_entries` most recent ones. History is disabled by default because undoing `solve`, `halt`, and `repeat-until` requires a snapshot of the whole puzzle. Passing zero disables history.
+    pub fn enable_history(&mut self, max_entries: usize) {
+        self.history_limit = max_entries;
+
+        while self.history.len() > max_entries {
+            self.history.pop_front();
+        }
+    }
+
+    /// Stop recording executed instructions and forget the execution history
+    pub fn disable_history(&mut self) {
+        self.enable_history(0);
+    }
+
+    fn record_history(&mut self, program_counter: usize, undo: Option<Undo>) {
+        let Some(undo) = undo.filter(|_| self.history_limit > 0) else {
+            return;
+        };
+
+        if self.history.len() == self.history_limit {
+            self.history.pop_front();
+        }
+
+        self.history.push_back(HistoryEntry {
+            program_counter,
+            undo,
+        });
+    }
+
+    fn snapshot_puzzle(&mut self, idx: PuzzleIdx) -> Undo {
+        match self.puzzle_states.puzzle_state_mut(idx).exact_state_mut() {
+            Some(state) => Undo::RestorePuzzle {
+                idx,
+                state: state.clone(),
+            },
+            None => Undo::Unsupported,
+        }
+    }
+
+    async fn give_input(
+        &mut self,
+        value: Int<I>,
+    ) -> Result<Result<ByPuzzleType<'static, InputRet>, String>, P::Error> {
+        let &ExecutionState::Paused(PausedState::Input { max_input, data: _ }) =
+            &self.execution_state
+        else {
+            panic!("The interpreter isn't in an input state");
+        };
+
+        if value > max_input {
+            return Ok(Err(format!(
+                "Your input must not be greater than {max_input}."
+            )));
+        }
+        if value < Int::<I>::zero() {
+            return Ok(Err("Your input must not be negative.".to_owned()));
+        }
+
+        // The code is weird to appease the borrow checker
+
+        let ExecutionState::Paused(PausedState::Input { max_input: _, data }) =
+            mem::replace(&mut self.execution_state, ExecutionState::Running)
+        else {
+            unreachable!("Checked before")
+        };
+
+        let (ret, undo) = match data {
+            ByPuzzleType::Theoretical(idx) => {
+                let state = self.puzzle_states.theoretical_state_mut(idx);
+                let previous = state.value();
+                state.add_to_i(value);
+
+                (
+                    ByPuzzleType::Theoretical(idx),
+                    Undo::SetTheoretical {
+                        idx,
+                        value: previous,
+                    },
+                )
+            }
+            ByPuzzleType::Puzzle((idx, mut algorithm, _)) => {
+                let puzzle = self.puzzle_states.puzzle_state_mut(idx);
+                algorithm.exponentiate(value);
+
+                puzzle.compose_into(&algorithm).await?;
+
+                let mut inverse = algorithm.clone();
+                inverse.exponentiate(-Int::<I>::one());
+
+                (
+                    ByPuzzleType::Puzzle((idx, algorithm)),
+                    Undo::ComposePuzzle { idx, inverse },
+                )
+            }
+        };
+
+        // Stepping back over the `input` instruction should undo the input as well
+        if let Some(entry) = self.history.back_mut()
+            && entry.program_counter == self.program_counter
+        {
+            entry.undo = undo;
+        }
+
+        self.execution_state = ExecutionState::Running;
+        self.program_counter += 1;
+
+        Ok(Ok(ret))
+    }
+
     fn panic<'x>(&mut self, message: &str) -> ActionPerformed<'x> {
         self.execution_state = ExecutionState::Paused(PausedState::Panicked);
         self.messages.push_back(format!("Panicked: {message}"));
@@ -168,9 +358,16 @@ impl<P: PuzzleState> Interpreter<P> {
             program_counter: 0,
             messages: VecDeque::new(),
             execution_state: ExecutionState::Running,
+            history: VecDeque::new(),
+            history_limit: 0,
+            input_queue: VecDeque::new(),
         };
 
-        Ok(Interpreter { state, program })
+        Ok(Interpreter {
+            state,
+            program,
+            breakpoints: BTreeSet::new(),
+        })
     }
 
     /// Create a new interpreter from a program, while assuming that the program only contains one puzzle.
@@ -187,9 +384,16 @@ impl<P: PuzzleState> Interpreter<P> {
             program_counter: 0,
             messages: VecDeque::new(),
             execution_state: ExecutionState::Running,
+            history: VecDeque::new(),
+            history_limit: 0,
+            input_queue: VecDeque::new(),
         };
 
-        Ok(Interpreter { state, program })
+        Ok(Interpreter {
+            state,
+            program,
+            breakpoints: BTreeSet::new(),
+        })
     }
 
     /// Execute one instruction
@@ -201,27 +405,214 @@ impl<P: PuzzleState> Interpreter<P> {
         if let ExecutionState::Paused(_) = self.state.execution_state() {
             return Ok(ActionPerformed::Paused);
         }
-        let Some(instruction) = self.program.instructions.get(self.state.program_counter) else {
+        let program_counter = self.state.program_counter;
+        let Some(instruction) = self.program.instructions.get(program_counter) else {
+            self.state.record_history(program_counter, Some(Undo::Nothing));
             return Ok(self.state.panic(
                 "Execution fell through the end of the program without reaching a halt instruction!"
             ));
         };
 
-        match &**instruction {
+        // Only pay for snapshotting the puzzle when the history is actually recorded
+        let undo = if self.state.history_limit == 0 {
+            None
+        } else {
+            Some(match &**instruction {
+                Instruction::PerformAlgorithm(ByPuzzleType::Theoretical((idx, _)))
+                | Instruction::Solve(ByPuzzleType::Theoretical(idx)) => Undo::SetTheoretical {
+                    idx: *idx,
+                    value: self.state.puzzle_states.theoretical_state(*idx).value(),
+                },
+                Instruction::PerformAlgorithm(ByPuzzleType::Puzzle((idx, alg))) => {
+                    let mut inverse = alg.to_owned();
+                    inverse.exponentiate(-Int::<I>::one());
+                    Undo::ComposePuzzle { idx: *idx, inverse }
+                }
+                Instruction::Solve(ByPuzzleType::Puzzle(idx))
+                | Instruction::Halt(ByPuzzleType::Puzzle((_, Some((idx, _, _)))))
+                | Instruction::RepeatUntil(ByPuzzleType::Puzzle(RepeatUntil {
+                    puzzle_idx: idx,
+                    ..
+                })) => self.state.snapshot_puzzle(*idx),
+                _ => Undo::Nothing,
+            })
+        };
+
+        // The history entry is only recorded once the instruction succeeded so that a failed instruction can't be "undone"
+        let action = match &**instruction {
             &Instruction::Goto { instruction_idx } => {
                 self.state.program_counter = instruction_idx;
                 self.state.execution_state = ExecutionState::Running;
 
-                Ok(ActionPerformed::Goto { instruction_idx })
+                ActionPerformed::Goto { instruction_idx }
+            }
+            Instruction::SolvedGoto(instr) => do_instr(instr, &mut self.state).await?,
+            Instruction::Input(instr) => {
+                let action = do_instr(instr, &mut self.state).await?;
+                self.state.record_history(program_counter, undo);
+
+                let paused_for_input = matches!(
+                    self.state.execution_state,
+                    ExecutionState::Paused(PausedState::Input { .. })
+                );
+
+                if paused_for_input && let Some(value) = self.state.input_queue.pop_front() {
+                    if let Err(message) = self.state.give_input(value).await? {
+                        return Ok(self.state.panic(&message));
+                    }
+
+                    return Ok(ActionPerformed::None);
+                }
+
+                return Ok(action);
+            }
+            Instruction::Halt(instr) => do_instr(instr, &mut self.state).await?,
+            Instruction::Print(instr) => do_instr(instr, &mut self.state).await?,
+            Instruction::PerformAlgorithm(instr) => do_instr(instr, &mut self.state).await?,
+            Instruction::Solve(instr) => do_instr(instr, &mut self.state).await?,
+            Instruction::RepeatUntil(instr) => do_instr(instr, &mut self.state).await?,
+        };
+
+        self.state.record_history(program_counter, undo);
+
+        Ok(action)
+    }
+
+    /// Undo the most recently executed instruction, restoring the program counter and registers to their values from before it was executed. Undoing an `input` instruction also undoes the input given to it. Messages that were already pushed are not retracted.
+    ///
+    /// Nothing can be undone unless history was enabled with `InterpreterState::enable_history` before the instruction was executed. Instructions that cannot be inverted algebraically, such as `solve`, can only be undone if the puzzle state exposes its exact state through `PuzzleState::exact_state_mut`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the puzzle state fails to undo the instruction.
+    pub async fn step_back(&mut self) -> Result<Result<(), StepBackError>, P::Error> {
+        let Some(entry) = self.state.history.pop_back() else {
+            return Ok(Err(StepBackError::NoHistory));
+        };
+
+        match entry.undo {
+            Undo::Nothing => {}
+            Undo::SetTheoretical { idx, value } => {
+                let state = self.state.puzzle_states.theoretical_state_mut(idx);
+                state.zero_out();
+                state.add_to(value);
+            }
+            Undo::ComposePuzzle { idx, inverse } => {
+                self.state
+                    .puzzle_states
+                    .puzzle_state_mut(idx)
+                    .compose_into(&inverse)
+                    .await?;
+            }
+            Undo::RestorePuzzle { idx, state } => {
+                *self
+                    .state
+                    .puzzle_states
+                    .puzzle_state_mut(idx)
+                    .exact_state_mut()
+                    .expect("The puzzle state was exact when the snapshot was taken") = state;
+            }
+            Undo::Unsupported => {
+                self.state.history.push_back(entry);
+                return Ok(Err(StepBackError::Unsupported));
+            }
+        }
+
+        self.state.program_counter = entry.program_counter;
+        self.state.execution_state = ExecutionState::Running;
+
+        Ok(Ok(()))
+    }
+
+    /// Bring the interpreter back to the state it was in when it was created, so that the program can be run again. Every puzzle is solved, every theoretical register is zeroed out, and the message queue, input queue, and execution history are cleared. Breakpoints are kept.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if a puzzle state fails to solve its puzzle.
+    pub async fn reset(&mut self) -> Result<(), P::Error> {
+        self.state.puzzle_states.reset().await?;
+        self.state.program_counter = 0;
+        self.state.messages.clear();
+        self.state.execution_state = ExecutionState::Running;
+        self.state.history.clear();
+        self.state.input_queue.clear();
+
+        Ok(())
+    }
+
+    /// Leave the paused state of a `halt` instruction so that the next step executes the instruction after it. Normally a `halt` ends the program, so this is only meaningful for programs whose author intends for execution to continue past a `halt`, for example to report intermediate results from a loop. Returns `false` without doing anything if the interpreter isn't paused at a `halt`.
+    ///
+    /// Puzzle states are allowed to destroy the value of the register that a `halt` decodes, so that register may be zero after resuming.
+    pub fn resume(&mut self) -> bool {
+        if !matches!(
+            self.state.execution_state,
+            ExecutionState::Paused(PausedState::Halt { .. })
+        ) {
+            return false;
+        }
+
+        self.state.execution_state = ExecutionState::Running;
+        self.state.program_counter += 1;
+
+        true
+    }
+
+    /// Get the instruction indices that execution stops at in `run_until_breakpoint`
+    #[must_use]
+    pub fn breakpoints(&self) -> &BTreeSet<usize> {
+        &self.breakpoints
+    }
+
+    /// Stop execution before the instruction at `instruction_idx` is executed. Returns `false` if the breakpoint was already set.
+    pub fn set_breakpoint(&mut self, instruction_idx: usize) -> bool {
+        self.breakpoints.insert(instruction_idx)
+    }
+
+    /// Remove a breakpoint. Returns `false` if there was no breakpoint at `instruction_idx`.
+    pub fn clear_breakpoint(&mut self, instruction_idx: usize) -> bool {
+        self.breakpoints.remove(&instruction_idx)
+    }
+
+    /// Execute instructions until the program counter reaches a breakpoint or the interpreter is paused. At least one instruction is executed, so calling this while stopped at a breakpoint continues execution.
+    ///
+    /// Returns the execution state reached, which is `ExecutionState::Running` if a breakpoint was hit.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error when a puzzle state fails to perform any instruction. In that case, the interpreter will stop early.
+    pub async fn run_until_breakpoint(&mut self) -> Result<&ExecutionState, P::Error> {
+        self.run_until(None).await
+    }
+
+    /// Execute instructions until the program counter reaches `instruction_idx`, a breakpoint, or the interpreter is paused. At least one instruction is executed.
+    ///
+    /// Returns the execution state reached, which is `ExecutionState::Running` if the instruction or a breakpoint was reached.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error when a puzzle state fails to perform any instruction. In that case, the interpreter will stop early.
+    pub async fn run_to(&mut self, instruction_idx: usize) -> Result<&ExecutionState, P::Error> {
+        self.run_until(Some(instruction_idx)).await
+    }
+
+    async fn run_until(
+        &mut self,
+        instruction_idx: Option<usize>,
+    ) -> Result<&ExecutionState, P::Error> {
+        loop {
+            if let ActionPerformed::Paused | ActionPerformed::Panicked = self.step().await? {
+                break;
+            }
+
+            let program_counter = self.state.program_counter;
+            if self.breakpoints.contains(&program_counter)
+                || instruction_idx == Some(program_counter)
+            {
+                break;
             }
-            Instruction::SolvedGoto(instr) => do_instr(instr, &mut self.state).await,
-            Instruction::Input(instr) => do_instr(instr, &mut self.state).await,
-            Instruction::Halt(instr) => do_instr(instr, &mut self.state).await,
-            Instruction::Print(instr) => do_instr(instr, &mut self.state).await,
-            Instruction::PerformAlgorithm(instr) => do_instr(instr, &mut self.state).await,
-            Instruction::Solve(instr) => do_instr(instr, &mut self.state).await,
-            Instruction::RepeatUntil(instr) => do_instr(instr, &mut self.state).await,
         }
+
+        Ok(self.state.execution_state())
     }
 
     /// Execute instructions until an input or halt instruction is reached
@@ -248,68 +639,160 @@ impl<P: PuzzleState> Interpreter<P> {
         }
     }
 
-    /// Give an input to the interpreter, returning the puzzle index and the algorithm performed `value` times if applicable
+    /// Decode the value of every register along with its order, so that a UI can show how close each one is to wrapping around. Theoretical registers come first in order, followed by the registers of each puzzle in the order of `architectures`, which must give the architecture of every puzzle in the program.
+    ///
+    /// Puzzle registers are decoded from their signature facelets using `PuzzleState::print`, so the puzzle states are left unchanged.
     ///
     /// # Errors
     ///
-    /// Returns an error if the input is out of bounds
+    /// Returns an error if a puzzle state fails to decode a register.
     ///
     /// # Panics
     ///
-    /// Panics if the interpreter is not executing an `input` instruction
-    pub async fn give_input(
+    /// Panics if the number of architectures doesn't match the number of puzzles in the program.
+    pub async fn register_progress(
         &mut self,
-        value: Int<I>,
-    ) -> Result<Result<ByPuzzleType<'static, InputRet>, String>, P::Error> {
-        let &ExecutionState::Paused(PausedState::Input { max_input, data: _ }) =
-            &self.state.execution_state
-        else {
-            panic!("The interpreter isn't in an input state");
-        };
+        architectures: &[Arc<Architecture>],
+    ) -> Result<Vec<RegisterProgress>, P::Error> {
+        assert_eq!(
+            architectures.len(),
+            self.program.puzzles.len(),
+            "Expected an architecture for every puzzle"
+        );
 
-        if value > max_input {
-            return Ok(Err(format!(
-                "Your input must not be greater than {max_input}."
-            )));
-        }
-        if value < -max_input {
-            return Ok(Err(format!(
-                "Your input must not be less than {}.",
-                -max_input
-            )));
+        let mut progress = (0..self.program.theoretical.len())
+            .map(|i| {
+                let state = self.state.theoretical_state(TheoreticalIdx(i));
+
+                RegisterProgress {
+                    value: Some(state.value()),
+                    order: state.order(),
+                }
+            })
+            .collect::<Vec<_>>();
+
+        for (i, arch) in architectures.iter().enumerate() {
+            let puzzle = self.state.puzzle_states.puzzle_state_mut(PuzzleIdx(i));
+
+            for register in arch.registers() {
+                progress.push(RegisterProgress {
+                    value: puzzle
+                        .print(register.signature_facelets().facelets(), register.algorithm())
+                        .await?,
+                    order: register.order(),
+                });
+            }
         }
 
-        // The code is weird to appease the borrow checker
+        Ok(progress)
+    }
 
-        let ExecutionState::Paused(PausedState::Input { max_input: _, data }) =
-            mem::replace(&mut self.state.execution_state, ExecutionState::Running)
-        else {
-            unreachable!("Checked before")
-        };
+    /// Start the registers with the given values rather than zero, which is useful for testing specific code paths. `values` pairs register indices with their starting values, where the registers are indexed the same way as in `register_progress`, and `architectures` must give the architecture of every puzzle in the program. Registers that aren't given a value start at zero.
+    ///
+    /// This brings the interpreter back to its initial state like `reset` before setting the values. Theoretical registers are set directly, while each puzzle is solved and then has the algorithm created by `new_from_effect` applied. For robot backends this physically moves the puzzle, so the robot must be set up before calling this.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if a puzzle state fails to solve its puzzle or to perform the algorithm.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the number of architectures doesn't match the number of puzzles in the program or if a register index is out of bounds.
+    pub async fn set_initial_values(
+        &mut self,
+        architectures: &[Arc<Architecture>],
+        values: &[(usize, Int<U>)],
+    ) -> Result<(), P::Error> {
+        assert_eq!(
+            architectures.len(),
+            self.program.puzzles.len(),
+            "Expected an architecture for every puzzle"
+        );
 
-        let ret = match data {
-            ByPuzzleType::Theoretical(idx) => {
+        self.reset().await?;
+
+        let theoretical_count = self.program.theoretical.len();
+        let mut effects = vec![Vec::new(); architectures.len()];
+
+        for &(idx, value) in values {
+            if idx < theoretical_count {
                 self.state
                     .puzzle_states
-                    .theoretical_state_mut(idx)
-                    .add_to_i(value);
-
-                ByPuzzleType::Theoretical(idx)
+                    .theoretical_state_mut(TheoreticalIdx(idx))
+                    .add_to(value);
+                continue;
             }
-            ByPuzzleType::Puzzle((idx, mut algorithm, _)) => {
-                let puzzle = self.state.puzzle_states.puzzle_state_mut(idx);
-                algorithm.exponentiate(value);
 
-                puzzle.compose_into(&algorithm).await?;
+            let mut register = idx - theoretical_count;
+            let puzzle = architectures
+                .iter()
+                .position(|arch| {
+                    if register < arch.registers().len() {
+                        true
+                    } else {
+                        register -= arch.registers().len();
+                        false
+                    }
+                })
+                .expect("Register index out of bounds");
+
+            effects[puzzle].push((register, value));
+        }
 
-                ByPuzzleType::Puzzle((idx, algorithm))
+        for (i, (arch, effect)) in architectures.iter().zip(effects).enumerate() {
+            if effect.is_empty() {
+                continue;
             }
-        };
 
-        self.state.execution_state = ExecutionState::Running;
-        self.state.program_counter += 1;
+            let alg = new_from_effect(arch, effect);
 
-        Ok(Ok(ret))
+            self.state
+                .puzzle_states
+                .puzzle_state_mut(PuzzleIdx(i))
+                .compose_into(&alg)
+                .await?;
+        }
+
+        Ok(())
+    }
+
+    /// Queue an input to be given automatically the next time an `input` instruction is executed, rather than pausing execution. Queued inputs are consumed in the order that they were queued. If a queued input is out of bounds, the interpreter panics.
+    pub fn queue_input(&mut self, value: Int<I>) {
+        self.state.input_queue.push_back(value);
+    }
+
+    /// Give an input to the interpreter, returning the puzzle index and the algorithm performed `value` times if applicable
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the input is negative or greater than `max_input`. Negative inputs are rejected rather than performed as the inverse algorithm.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the interpreter is not executing an `input` instruction
+    pub async fn give_input(
+        &mut self,
+        value: Int<I>,
+    ) -> Result<Result<ByPuzzleType<'static, InputRet>, String>, P::Error> {
+        self.state.give_input(value).await
+    }
+}
+
+#[cfg(feature = "remote_robot")]
+impl<C: puzzle_states::Connection>
+    Interpreter<puzzle_states::RobotState<puzzle_states::RemoteRobot<C>>>
+{
+    /// Create a new interpreter that drives a robot server over the given connection, without reconnecting or limiting decoding iterations. Since there is only one robot, the program may contain at most one puzzle.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the handshake with the robot server fails.
+    pub async fn new_over_connection(
+        program: Arc<Program>,
+        conn: C,
+    ) -> Result<Self, puzzle_states::RobotStateError<std::io::Error>> {
+        Self::new_only_one_puzzle(program, (None, (conn, puzzle_states::ReconnectPolicy::none())))
+            .await
     }
 }
 
@@ -324,13 +807,16 @@ impl SeparatesByPuzzleType for InputRet {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::{Interpreter, PausedState, puzzle_states::SimulatedPuzzle};
-    use compiler::{Reporter, compile, q_emitter::emit_q};
+    use crate::{
+        Interpreter, PausedState,
+        puzzle_states::{RobotState, RobotStateError, SimulatedPuzzle, facelet_diff},
+    };
+    use compiler::{Reporter, compile, q_emitter::emit_q, q_parser::parse_q};
     use internment::ArcIntern;
     use itertools::Itertools;
     use pretty_assertions::{assert_eq, assert_str_eq};
     use puzzle_theory::{permutations::Permutation, puzzle_geometry::parsing::puzzle, span::File};
-    use qter_core::architectures::{new_from_effect, with_presets};
+    use qter_core::architectures::{decode, new_from_effect, with_presets};
     use std::sync::Arc;
 
     #[tokio::test]
@@ -353,31 +839,89 @@ mod tests {
         assert!(cube.facelets_solved(&[0, 12, 15, 7, 40]).await.unwrap());
 
         assert!(!cube.facelets_solved(&[1, 12, 15, 7, 24]).await.unwrap());
-    }
 
-    #[tokio::test]
-    async fn complicated_solved_decode_test() {
-        let perm_group = with_presets(puzzle("3x3").permutation_group());
+        // The U face stickers moved, even though they still have the colors of the U face
+        cube.assume_distinct_colors(true);
+        assert!(!cube.facelets_solved(&[0, 12, 15, 7, 40]).await.unwrap());
 
-        let (arch, swizzle) = perm_group
-            .get_preset(&[Int::from(210_u64), Int::from(24_u64)])
+        let unmoved = (0..perm_group.perm_group.facelet_count())
+            .find(|&facelet| cube.state.mapping().get(facelet) == facelet)
             .unwrap();
+        assert!(cube.facelets_solved(&[unmoved]).await.unwrap());
+    }
 
-        assert_eq!(swizzle, Permutation::identity());
+    #[tokio::test]
+    async fn solve_to() {
+        let perm_group = puzzle("3x3").permutation_group();
 
-        let a_facelets = arch.registers()[0].signature_facelets();
-        let b_facelets = arch.registers()[1].signature_facelets();
+        let alg = |moves: &str| {
+            Algorithm::parse_from_string(Arc::clone(&perm_group), moves).unwrap()
+        };
 
-        let a_permutation = new_from_effect(&arch, vec![(0, Int::one())]);
-        let b_permutation = new_from_effect(&arch, vec![(1, Int::one())]);
+        let target = alg("R U F'").permutation().clone();
 
-        let mut cube: SimulatedPuzzle =
-            SimulatedPuzzle::initialize(Arc::clone(&perm_group.perm_group), ())
+        let mut cube: SimulatedPuzzle = SimulatedPuzzle::initialize(Arc::clone(&perm_group), ())
+            .await
+            .unwrap();
+        cube.compose_into(&alg("D2 L")).await.unwrap();
+        cube.solve_to(&target).await.unwrap();
+        assert_eq!(cube.puzzle_state(), &target);
+
+        let mut robot: RobotState<SimulatedPuzzle> =
+            RobotState::initialize(Arc::clone(&perm_group), (None, ()))
+                .await
+                .unwrap();
+        robot.compose_into(&alg("B' U2")).await.unwrap();
+        robot.solve_to(&target).await.unwrap();
+        assert_eq!(robot.robot_mut().puzzle_state(), &target);
+    }
+
+    #[tokio::test]
+    async fn facelets_solved_matches_colors() {
+        let perm_group = puzzle("3x3").permutation_group();
+
+        let mut cube: SimulatedPuzzle = SimulatedPuzzle::initialize(Arc::clone(&perm_group), ())
+            .await
+            .unwrap();
+
+        let moves: [ArcIntern<str>; 3] = ["R", "U", "F'"].map(ArcIntern::from);
+
+        perm_group
+            .compose_generators_into(&mut cube.state, moves.iter())
+            .unwrap();
+
+        for facelet in 0..perm_group.facelet_count() {
+            let maps_to = cube.state.mapping().get(facelet);
+            let expected =
+                perm_group.facelet_colors()[maps_to] == perm_group.facelet_colors()[facelet];
+
+            assert_eq!(cube.facelets_solved(&[facelet]).await.unwrap(), expected);
+        }
+    }
+
+    #[tokio::test]
+    async fn complicated_solved_decode_test() {
+        let perm_group = with_presets(puzzle("3x3").permutation_group());
+
+        let (arch, swizzle) = perm_group
+            .get_preset(&[Int::from(210_u64), Int::from(24_u64)])
+            .unwrap();
+
+        assert_eq!(swizzle, Permutation::identity());
+
+        let a_facelets = arch.registers()[0].signature_facelets();
+        let b_facelets = arch.registers()[1].signature_facelets();
+
+        let a_permutation = new_from_effect(&arch, vec![(0, Int::one())]);
+        let b_permutation = new_from_effect(&arch, vec![(1, Int::one())]);
+
+        let mut cube: SimulatedPuzzle =
+            SimulatedPuzzle::initialize(Arc::clone(&perm_group.perm_group), ())
                 .await
                 .unwrap();
 
         for i in 1..=23 {
-            cube.state.compose_into(b_permutation.permutation());
+            cube.compose_into(&b_permutation).await.unwrap();
             assert_eq!(
                 cube.print(b_facelets.facelets(), &b_permutation)
                     .await
@@ -388,7 +932,7 @@ mod tests {
             assert!(!cube.facelets_solved(b_facelets.facelets()).await.unwrap());
         }
 
-        cube.state.compose_into(b_permutation.permutation());
+        cube.compose_into(&b_permutation).await.unwrap();
         assert!(cube.facelets_solved(b_facelets.facelets()).await.unwrap());
         assert_eq!(
             cube.print(b_facelets.facelets(), &b_permutation)
@@ -416,13 +960,242 @@ mod tests {
                     Int::from(j)
                 );
 
-                cube.state.compose_into(a_permutation.permutation());
+                cube.compose_into(&a_permutation).await.unwrap();
+            }
+
+            cube.compose_into(&b_permutation).await.unwrap();
+        }
+    }
+
+    #[tokio::test]
+    async fn incremental_decoding_matches_full_decode() {
+        let perm_group = with_presets(puzzle("3x3").permutation_group());
+
+        let (arch, _) = perm_group
+            .get_preset(&[Int::from(90_u64), Int::from(90_u64)])
+            .unwrap();
+
+        let registers = arch
+            .registers()
+            .iter()
+            .map(|register| {
+                (
+                    register.signature_facelets().facelets().to_vec(),
+                    register.algorithm().to_owned(),
+                )
+            })
+            .collect_vec();
+
+        let a_permutation = new_from_effect(&arch, vec![(0, Int::one())]);
+        let b_permutation = new_from_effect(&arch, vec![(1, Int::from(7_u64))]);
+        let r = Algorithm::parse_from_string(Arc::clone(&perm_group.perm_group), "R").unwrap();
+
+        let mut cube: SimulatedPuzzle =
+            SimulatedPuzzle::initialize(Arc::clone(&perm_group.perm_group), ())
+                .await
+                .unwrap();
+
+        for i in 0..300 {
+            let alg = match i % 7 {
+                3 => &b_permutation,
+                5 if i % 35 == 5 => &r,
+                _ => &a_permutation,
+            };
+            cube.compose_into(alg).await.unwrap();
+
+            if i % 50 == 49 {
+                cube.solve().await.unwrap();
+            }
+
+            if i % 60 == 59 {
+                cube.exact_state_mut()
+                    .unwrap()
+                    .compose_into(b_permutation.permutation());
+            }
+
+            for (facelets, generator) in &registers {
+                assert_eq!(
+                    cube.print(facelets, generator).await.unwrap(),
+                    decode(cube.puzzle_state(), facelets, generator)
+                );
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn decode_iteration_limit() {
+        let perm_group = with_presets(puzzle("3x3").permutation_group());
+
+        let (arch, _) = perm_group
+            .get_preset(&[Int::from(90_u64), Int::from(90_u64)])
+            .unwrap();
+        let facelets = arch.registers()[0].signature_facelets();
+        let generator = arch.registers()[0].algorithm();
+
+        let cases = [(None, Some(2_u64)), (Some(2_u64), Some(2)), (Some(1), None)];
+        for (limit, expected) in cases {
+            let mut cube: RobotState<SimulatedPuzzle> = RobotState::initialize(
+                Arc::clone(&perm_group.perm_group),
+                (limit.map(Int::from), ()),
+            )
+            .await
+            .unwrap();
+
+            cube.compose_into(generator).await.unwrap();
+            cube.compose_into(generator).await.unwrap();
+
+            let decoded = cube.halt(facelets.facelets(), generator).await;
+            match expected {
+                Some(expected) => assert_eq!(decoded.unwrap(), Some(Int::from(expected))),
+                None => assert!(matches!(
+                    decoded,
+                    Err(RobotStateError::DecodeIterationLimitExceeded { .. })
+                )),
             }
+        }
+    }
+
+    #[tokio::test]
+    async fn facelets_solved_many() {
+        let perm_group = with_presets(puzzle("3x3").permutation_group());
+
+        let mut cube: RobotState<SimulatedPuzzle> =
+            RobotState::initialize(Arc::clone(&perm_group.perm_group), (None, ()))
+                .await
+                .unwrap();
+
+        cube.compose_into(
+            &Algorithm::parse_from_string(Arc::clone(&perm_group.perm_group), "U").unwrap(),
+        )
+        .await
+        .unwrap();
+
+        let groups: [&[usize]; 3] = [&[0, 12, 15, 7, 40], &[1, 12, 15, 7, 24], &[]];
+
+        assert_eq!(
+            cube.facelets_solved_many(&groups).await.unwrap(),
+            vec![true, false, true]
+        );
 
-            cube.state.compose_into(b_permutation.permutation());
+        for (facelets, solved) in groups.iter().zip([true, false, true]) {
+            assert_eq!(cube.facelets_solved(facelets).await.unwrap(), solved);
         }
     }
 
+    #[test]
+    fn facelet_diff_finds_moved_facelets() {
+        let perm_group = puzzle("3x3").permutation_group();
+
+        let u = Algorithm::parse_from_string(Arc::clone(&perm_group), "U").unwrap();
+
+        assert_eq!(
+            facelet_diff(u.permutation(), u.permutation(), perm_group.facelet_count()),
+            Vec::<usize>::new()
+        );
+
+        let mut moved = facelet_diff(
+            &Permutation::identity(),
+            u.permutation(),
+            perm_group.facelet_count(),
+        );
+        assert_eq!(moved.len(), 20);
+
+        moved.retain(|&facelet| u.permutation().mapping().get(facelet) == facelet);
+        assert!(moved.is_empty());
+    }
+
+    #[cfg(feature = "remote_robot")]
+    #[tokio::test]
+    async fn interpreter_over_connection() {
+        use crate::puzzle_states::run_robot_server;
+        use tokio::io::BufReader;
+        use tokio_util::io::simplex;
+
+        let code = "
+            .registers {
+                A, B <- 3x3 builtin (90, 90)
+            }
+
+                add A 5
+                halt \"A is\" A
+        ";
+
+        let reporter = Reporter::default();
+        let (program, _) = match compile(&file(code), |_| unreachable!(), Arc::clone(&reporter)) {
+            Some(v) => v,
+            None => panic!("{:?}", reporter.iter().collect::<Vec<_>>()),
+        };
+
+        let (client_tx, server_rx) = simplex::new(1000);
+        let (server_tx, client_rx) = simplex::new(1000);
+
+        let server =
+            run_robot_server::<_, SimulatedPuzzle>((BufReader::new(server_rx), server_tx), ());
+
+        let client = async {
+            let mut interpreter = Interpreter::new_over_connection(
+                Arc::new(program),
+                (BufReader::new(client_rx), client_tx),
+            )
+            .await
+            .unwrap();
+
+            assert!(matches!(
+                interpreter.step_until_halt().await.unwrap(),
+                PausedState::Halt { .. }
+            ));
+            assert_eq!(interpreter.state_mut().messages().pop_front().unwrap(), "A is 5");
+        };
+
+        let (server, ()) = tokio::join!(server, client);
+        server.unwrap();
+    }
+
+    #[tokio::test]
+    async fn recording_robot() {
+        use crate::puzzle_states::{RecordedCommand, RecordingRobot};
+
+        let code = "
+            .registers {
+                A, B <- 3x3 builtin (90, 90)
+            }
+
+                add A 5
+                halt \"A is\" A
+        ";
+
+        let reporter = Reporter::default();
+        let (program, _) = match compile(&file(code), |_| unreachable!(), Arc::clone(&reporter)) {
+            Some(v) => v,
+            None => panic!("{:?}", reporter.iter().collect::<Vec<_>>()),
+        };
+
+        let mut interpreter: Interpreter<RobotState<RecordingRobot>> =
+            Interpreter::new_only_one_puzzle(Arc::new(program), (None, ()))
+                .await
+                .unwrap();
+
+        assert!(matches!(
+            interpreter.step_until_halt().await.unwrap(),
+            PausedState::Halt { .. }
+        ));
+        assert_eq!(interpreter.state_mut().messages().pop_front().unwrap(), "A is 5");
+
+        let robot = interpreter
+            .state_mut()
+            .puzzle_state_mut(PuzzleIdx(0))
+            .robot_mut();
+
+        assert!(matches!(
+            robot.commands().first(),
+            Some(RecordedCommand::ComposeInto(_))
+        ));
+        assert!(robot.commands().contains(&RecordedCommand::TakePicture));
+
+        robot.take_commands();
+        assert!(robot.commands().is_empty());
+    }
+
     pub(crate) fn file(str: &'static str) -> File {
         File::new(ArcIntern::from("<static>"), ArcIntern::from(str))
     }
@@ -679,6 +1452,366 @@ mod tests {
         }
     }
 
+    #[tokio::test]
+    async fn register_progress() {
+        let code = r#"
+            .registers {
+                A, B ← 3x3 builtin (210, 24)
+            }
+
+                add A 17
+                add B 5
+                halt "Done"
+        "#;
+
+        let reporter = Reporter::default();
+        let (program, regs) =
+            match compile(&file(code), |_| unreachable!(), Arc::clone(&reporter)) {
+                Some(v) => v,
+                None => panic!("{:?}", reporter.iter().collect::<Vec<_>>()),
+            };
+
+        let regs = regs.unwrap();
+        let [compiler::Puzzle::Real { architectures }] = regs.puzzles() else {
+            panic!("Expected a single puzzle");
+        };
+        let arch = Arc::clone(&architectures[0].1);
+
+        let mut interpreter: Interpreter<SimulatedPuzzle> =
+            Interpreter::new(Arc::new(program), ()).await.unwrap();
+
+        interpreter.step_until_halt().await.unwrap();
+
+        assert_eq!(
+            interpreter.register_progress(&[arch]).await.unwrap(),
+            [
+                RegisterProgress {
+                    value: Some(Int::from(17_u64)),
+                    order: Int::from(210_u64),
+                },
+                RegisterProgress {
+                    value: Some(Int::from(5_u64)),
+                    order: Int::from(24_u64),
+                },
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn initial_values() {
+        let code = r#"
+            .registers {
+                A, B ← 3x3 builtin (210, 24)
+            }
+
+                add A 3
+                halt "A is" A
+        "#;
+
+        let reporter = Reporter::default();
+        let (program, regs) =
+            match compile(&file(code), |_| unreachable!(), Arc::clone(&reporter)) {
+                Some(v) => v,
+                None => panic!("{:?}", reporter.iter().collect::<Vec<_>>()),
+            };
+
+        let regs = regs.unwrap();
+        let [compiler::Puzzle::Real { architectures }] = regs.puzzles() else {
+            panic!("Expected a single puzzle");
+        };
+        let arch = Arc::clone(&architectures[0].1);
+
+        let mut interpreter: Interpreter<SimulatedPuzzle> =
+            Interpreter::new(Arc::new(program), ()).await.unwrap();
+
+        interpreter
+            .set_initial_values(
+                &[Arc::clone(&arch)],
+                &[(0, Int::from(10_u64)), (1, Int::from(7_u64))],
+            )
+            .await
+            .unwrap();
+
+        interpreter.step_until_halt().await.unwrap();
+        assert_eq!(interpreter.state_mut().messages().pop_front().unwrap(), "A is 13");
+
+        let progress = interpreter.register_progress(&[arch]).await.unwrap();
+        assert_eq!(progress[1].value, Some(Int::from(7_u64)));
+    }
+
+    #[tokio::test]
+    async fn resume_after_halt() {
+        let code = r#"
+            .registers {
+                A ← 3x3 builtin (1260)
+            }
+
+            loop:
+                add A 1
+                halt "A is" A
+                goto loop
+        "#;
+
+        let reporter = Reporter::default();
+        let (program, _) = match compile(&file(code), |_| unreachable!(), Arc::clone(&reporter)) {
+            Some(v) => v,
+            None => panic!("{:?}", reporter.iter().collect::<Vec<_>>()),
+        };
+
+        let mut interpreter: Interpreter<SimulatedPuzzle> =
+            Interpreter::new(Arc::new(program), ()).await.unwrap();
+
+        assert!(!interpreter.resume());
+
+        interpreter.step_until_halt().await.unwrap();
+        assert!(interpreter.resume());
+        assert!(!interpreter.resume());
+
+        interpreter.step_until_halt().await.unwrap();
+
+        assert_eq!(interpreter.state().messages.len(), 2);
+        assert_eq!(interpreter.state().messages[0], "A is 1");
+    }
+
+    #[tokio::test]
+    async fn out_of_range_input() {
+        let code = r#"
+            .registers {
+                A, B ← 3x3 builtin (210, 24)
+            }
+
+                input "A:" A
+                print "A is" A
+                input "B:" B
+                halt "B is" B
+        "#;
+
+        let reporter = Reporter::default();
+        let (program, _) = match compile(&file(code), |_| unreachable!(), Arc::clone(&reporter)) {
+            Some(v) => v,
+            None => panic!("{:?}", reporter.iter().collect::<Vec<_>>()),
+        };
+
+        let mut interpreter: Interpreter<SimulatedPuzzle> =
+            Interpreter::new(Arc::new(program), ()).await.unwrap();
+
+        // Inputs must be within `0..=max_input`, which is `0..=209` for A and `0..=23` for B
+        for (rejected, accepted) in [([210, -1], 209), ([24, -1], 0)] {
+            interpreter.step_until_halt().await.unwrap();
+
+            // Rejected inputs leave the interpreter waiting for another one
+            for value in rejected {
+                assert!(
+                    interpreter
+                        .give_input(Int::from(value))
+                        .await
+                        .unwrap()
+                        .is_err()
+                );
+                assert!(matches!(
+                    interpreter.state().execution_state(),
+                    ExecutionState::Paused(PausedState::Input { .. })
+                ));
+            }
+
+            assert!(
+                interpreter
+                    .give_input(Int::from(accepted))
+                    .await
+                    .unwrap()
+                    .is_ok()
+            );
+        }
+
+        interpreter.step_until_halt().await.unwrap();
+
+        assert!(interpreter.state().messages.iter().any(|message| message == "A is 209"));
+        assert_eq!(interpreter.state().messages.back().unwrap(), "B is 0");
+    }
+
+    #[tokio::test]
+    async fn queued_inputs() {
+        let code = r#"
+            .registers {
+                A, B ← 3x3 builtin (210, 24)
+            }
+
+                input "A:" A
+                input "B:" B
+                print "A:" A
+                print "B:" B
+                input "A:" A
+                halt "Done"
+        "#;
+
+        let reporter = Reporter::default();
+        let (program, _) = match compile(&file(code), |_| unreachable!(), Arc::clone(&reporter)) {
+            Some(v) => v,
+            None => panic!("{:?}", reporter.iter().collect::<Vec<_>>()),
+        };
+
+        let mut interpreter: Interpreter<SimulatedPuzzle> =
+            Interpreter::new(Arc::new(program), ()).await.unwrap();
+
+        interpreter.queue_input(Int::from(77_u64));
+        interpreter.queue_input(Int::from(13_u64));
+
+        // The queue runs out at the third input
+        assert!(matches!(
+            interpreter.step_until_halt().await.unwrap(),
+            PausedState::Input { .. }
+        ));
+
+        assert!(
+            interpreter
+                .give_input(Int::from(1_u64))
+                .await
+                .unwrap()
+                .is_ok()
+        );
+
+        assert!(matches!(
+            interpreter.step_until_halt().await.unwrap(),
+            PausedState::Halt {
+                maybe_puzzle_idx_and_register: None,
+            }
+        ));
+
+        assert_eq!(
+            interpreter.state().messages.iter().collect_vec(),
+            [
+                "A: (max input 209)",
+                "B: (max input 23)",
+                "A: 77",
+                "B: 13",
+                "A: (max input 209)",
+                "Done"
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn json_round_trip() {
+        let code = r#"
+            .registers {
+                A, B ← 3x3 builtin (210, 24)
+            }
+
+                input "A:" A
+                add B 5
+            loop:
+                solved-goto B done
+                add A 1
+                add B 23
+                goto loop
+            done:
+                halt "A is" A
+        "#;
+
+        let reporter = Reporter::default();
+        let (program, _) = match compile(&file(code), |_| unreachable!(), Arc::clone(&reporter)) {
+            Some(v) => v,
+            None => panic!("{:?}", reporter.iter().collect::<Vec<_>>()),
+        };
+
+        let json = program.to_json();
+        let loaded = qter_core::Program::from_json(&json).unwrap();
+        assert_eq!(loaded.to_json(), json);
+        assert_eq!(loaded.registers(), program.registers());
+        let program = loaded;
+
+        let mut interpreter: Interpreter<SimulatedPuzzle> =
+            Interpreter::new(Arc::new(program), ()).await.unwrap();
+
+        interpreter.step().await.unwrap();
+
+        assert!(
+            interpreter
+                .give_input(Int::from(100_u64))
+                .await
+                .unwrap()
+                .is_ok()
+        );
+
+        assert!(matches!(
+            interpreter.step_until_halt().await.unwrap(),
+            PausedState::Halt {
+                maybe_puzzle_idx_and_register: Some(_),
+            }
+        ));
+
+        assert_eq!(
+            interpreter.state().messages.iter().collect_vec(),
+            ["A: (max input 209)", "A is 105"]
+        );
+    }
+
+    #[tokio::test]
+    async fn q_round_trip() {
+        let code = r#"
+            .registers {
+                A, B ← 3x3 builtin (210, 24)
+            }
+
+                input "A:" A
+                add B 5
+            loop:
+                solved-goto B done
+                add A 1
+                add B 23
+                goto loop
+            done:
+                print "B is" B
+                halt "A is" A
+        "#;
+
+        let reporter = Reporter::default();
+        let (program, _) = match compile(&file(code), |_| unreachable!(), Arc::clone(&reporter)) {
+            Some(v) => v,
+            None => panic!("{:?}", reporter.iter().collect::<Vec<_>>()),
+        };
+
+        let q = emit_q(&program, "code.q".into(), &reporter).unwrap().0;
+
+        let program = match parse_q(&q, &reporter) {
+            Some(v) => v,
+            None => panic!("{:?}", reporter.iter().collect::<Vec<_>>()),
+        };
+
+        assert_str_eq!(
+            emit_q(&program, "code.q".into(), &reporter)
+                .unwrap()
+                .0
+                .inner(),
+            q.inner()
+        );
+
+        let mut interpreter: Interpreter<SimulatedPuzzle> =
+            Interpreter::new(Arc::new(program), ()).await.unwrap();
+
+        interpreter.step().await.unwrap();
+
+        assert!(
+            interpreter
+                .give_input(Int::from(100_u64))
+                .await
+                .unwrap()
+                .is_ok()
+        );
+
+        assert!(matches!(
+            interpreter.step_until_halt().await.unwrap(),
+            PausedState::Halt {
+                maybe_puzzle_idx_and_register: Some(_),
+            }
+        ));
+
+        assert_eq!(
+            interpreter.state().messages.iter().collect_vec(),
+            ["A: (max input 209)", "B is 0", "A is 105"]
+        );
+    }
+
     async fn test_all_inputs<const N: usize>(
         code: &'static str,
         q_code: &'static str,
@@ -816,6 +1949,181 @@ mod tests {
         .await;
     }
 
+    #[tokio::test]
+    async fn theoretical_modulus() {
+        let code = r#"
+            .registers {
+                A <- theoretical 90
+            }
+
+                input "Number:" A
+                solved-goto A%6 divisible
+                halt "Not divisible"
+            divisible:
+                halt "Divisible"
+        "#;
+
+        let reporter = Reporter::default();
+        let (program, _) = match compile(&file(code), |_| unreachable!(), Arc::clone(&reporter)) {
+            Some(v) => v,
+            None => panic!("{:?}", reporter.iter().collect::<Vec<_>>()),
+        };
+
+        let program = Arc::new(program);
+
+        for input in 0..90_u64 {
+            let mut interpreter: Interpreter<SimulatedPuzzle> =
+                Interpreter::new(Arc::clone(&program), ()).await.unwrap();
+
+            assert!(matches!(
+                interpreter.step_until_halt().await.unwrap(),
+                PausedState::Input {
+                    data: ByPuzzleType::Theoretical(_),
+                    ..
+                }
+            ));
+
+            assert!(
+                interpreter
+                    .give_input(Int::from(input))
+                    .await
+                    .unwrap()
+                    .is_ok()
+            );
+
+            interpreter.step_until_halt().await.unwrap();
+
+            let expected = if input % 6 == 0 {
+                "Divisible"
+            } else {
+                "Not divisible"
+            };
+
+            assert_eq!(
+                interpreter.state().messages.back().map(String::as_str),
+                Some(expected)
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn message_placeholder() {
+        let code = r#"
+            .registers {
+                A, B <- 3x3 builtin (90, 90)
+            }
+
+                add A 5
+                print "A is {} right now" A
+                print "A is" A
+                print "Braces {} are only special with a register"
+                halt "A ended at {}!" A
+        "#;
+
+        let reporter = Reporter::default();
+        let (program, _) = match compile(&file(code), |_| unreachable!(), Arc::clone(&reporter)) {
+            Some(v) => v,
+            None => panic!("{:?}", reporter.iter().collect::<Vec<_>>()),
+        };
+
+        let mut interpreter: Interpreter<SimulatedPuzzle> =
+            Interpreter::new(Arc::new(program), ()).await.unwrap();
+
+        interpreter.step_until_halt().await.unwrap();
+
+        assert_eq!(
+            interpreter.state().messages,
+            [
+                "A is 5 right now",
+                "A is 5",
+                "Braces {} are only special with a register",
+                "A ended at 5!"
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn architecture_switching() {
+        let code = r#"
+            .registers {
+                (
+                    A, B <- 3x3 builtin (90, 90)
+                    C <- 3x3 builtin (1260)
+                )
+            }
+
+                add A 5
+                print "A is" A
+                switch C
+                add C 1000
+                print "C is" C
+                switch A
+                add B 3
+                print "A is" A
+                halt "B is" B
+        "#;
+
+        let reporter = Reporter::default();
+        let (program, _) = match compile(&file(code), |_| unreachable!(), &reporter) {
+            Some(v) => v,
+            None => panic!("{:?}", reporter.iter().collect::<Vec<_>>()),
+        };
+
+        let mut interpreter: Interpreter<SimulatedPuzzle> =
+            Interpreter::new(Arc::new(program), ()).await.unwrap();
+
+        interpreter.step_until_halt().await.unwrap();
+
+        // Switching solves the puzzle, so `A` starts from zero again after switching back
+        assert_eq!(
+            interpreter.state().messages,
+            ["A is 5", "C is 1000", "A is 0", "B is 3"]
+        );
+    }
+
+    #[tokio::test]
+    async fn escaped_messages() {
+        let code = r#"
+            .registers {
+                A, B <- 3x3 builtin (90, 90)
+            }
+
+                input "\"A\":" A
+                print "She said \"hi\"\nand left \\o/"
+                halt "Done"
+        "#;
+
+        let reporter = Reporter::default();
+        let (program, _) = match compile(&file(code), |_| unreachable!(), Arc::clone(&reporter)) {
+            Some(v) => v,
+            None => panic!("{:?}", reporter.iter().collect::<Vec<_>>()),
+        };
+
+        let mut interpreter: Interpreter<SimulatedPuzzle> =
+            Interpreter::new(Arc::new(program), ()).await.unwrap();
+
+        interpreter.step_until_halt().await.unwrap();
+
+        assert!(
+            interpreter
+                .give_input(Int::from(0_u64))
+                .await
+                .unwrap()
+                .is_ok()
+        );
+
+        interpreter.step_until_halt().await.unwrap();
+
+        assert_eq!(
+            interpreter.state().messages,
+            [
+                "\"A\": (max input 89)",
+                "She said \"hi\"\nand left \\o/",
+                "Done"
+            ]
+        );
+    }
+
     #[tokio::test]
     async fn add_coalesce() {
         let code = "
@@ -1261,4 +2569,230 @@ A: 3x3
             assert_eq!(message, expected);
         }
     }
+
+    #[tokio::test]
+    async fn step_back() {
+        let code = "
+            .registers {
+                A, B, C <- 3x3 builtin (30, 30, 30)
+            }
+
+                input \"A:\" A
+                add B 10
+            spot:
+                solved-goto B done
+                add B 1
+                goto spot
+            done:
+                halt \"A=\" A
+        ";
+
+        let reporter = Reporter::default();
+        let (program, _) = match compile(&file(code), |_| unreachable!(), Arc::clone(&reporter)) {
+            Some(v) => v,
+            None => panic!("{:?}", reporter.iter().collect::<Vec<_>>()),
+        };
+
+        let mut interpreter: Interpreter<SimulatedPuzzle> =
+            Interpreter::new(Arc::new(program), ()).await.unwrap();
+
+        interpreter.step_until_halt().await.unwrap();
+        assert_eq!(
+            interpreter.step_back().await.unwrap(),
+            Err(StepBackError::NoHistory)
+        );
+        interpreter.reset().await.unwrap();
+
+        interpreter.state_mut().enable_history(usize::MAX);
+        assert_eq!(
+            interpreter.step_back().await.unwrap(),
+            Err(StepBackError::NoHistory)
+        );
+
+        interpreter.step_until_halt().await.unwrap();
+        assert!(
+            interpreter
+                .give_input(Int::from(7_u64))
+                .await
+                .unwrap()
+                .is_ok()
+        );
+        interpreter.step_until_halt().await.unwrap();
+        let halt_idx = interpreter.state().program_counter();
+
+        assert_eq!(interpreter.step_back().await.unwrap(), Ok(()));
+        assert_eq!(interpreter.state().program_counter(), halt_idx);
+        assert!(matches!(
+            interpreter.state().execution_state(),
+            ExecutionState::Running
+        ));
+
+        interpreter.step_until_halt().await.unwrap();
+        assert_eq!(interpreter.state_mut().messages().back().unwrap(), "A= 7");
+
+        while interpreter.state().can_step_back() {
+            assert_eq!(interpreter.step_back().await.unwrap(), Ok(()));
+        }
+
+        assert_eq!(interpreter.state().program_counter(), 0);
+        assert_eq!(
+            interpreter
+                .state_mut()
+                .puzzle_states
+                .puzzle_state_mut(PuzzleIdx(0))
+                .puzzle_state(),
+            &Permutation::identity()
+        );
+    }
+
+    #[tokio::test]
+    async fn history_limit() {
+        let code = "
+            .registers {
+                A, B ← 3x3 builtin (90, 90)
+            }
+
+                add A 1
+                print \"A is\" A
+                add A 1
+                print \"A is\" A
+                halt \"A is\" A
+        ";
+
+        let reporter = Reporter::default();
+        let (program, _) = match compile(&file(code), |_| unreachable!(), Arc::clone(&reporter)) {
+            Some(v) => v,
+            None => panic!("{:?}", reporter.iter().collect::<Vec<_>>()),
+        };
+
+        let mut interpreter: Interpreter<SimulatedPuzzle> =
+            Interpreter::new(Arc::new(program), ()).await.unwrap();
+        interpreter.state_mut().enable_history(2);
+
+        interpreter.step_until_halt().await.unwrap();
+
+        let mut undone = 0;
+        while interpreter.state().can_step_back() {
+            assert_eq!(interpreter.step_back().await.unwrap(), Ok(()));
+            undone += 1;
+        }
+
+        assert_eq!(undone, 2);
+        assert_eq!(
+            interpreter.step_back().await.unwrap(),
+            Err(StepBackError::NoHistory)
+        );
+        assert_eq!(interpreter.state_mut().messages().back().unwrap(), "A is 2");
+    }
+
+    #[tokio::test]
+    async fn breakpoints() {
+        let code = "
+            .registers {
+                A, B ← 3x3 builtin (90, 90)
+            }
+
+                add A 3
+            loop:
+                print \"A is\" A
+                solved-goto A done
+                add A 89
+                add B 1
+                goto loop
+            done:
+                halt \"B is\" B
+        ";
+
+        let reporter = Reporter::default();
+        let (program, _) = match compile(&file(code), |_| unreachable!(), Arc::clone(&reporter)) {
+            Some(v) => v,
+            None => panic!("{:?}", reporter.iter().collect::<Vec<_>>()),
+        };
+
+        let loop_idx = program
+            .instructions
+            .iter()
+            .position(|instruction| matches!(**instruction, Instruction::Print(_)))
+            .unwrap();
+
+        let mut interpreter: Interpreter<SimulatedPuzzle> =
+            Interpreter::new(Arc::new(program), ()).await.unwrap();
+
+        assert!(interpreter.set_breakpoint(loop_idx));
+        assert!(!interpreter.set_breakpoint(loop_idx));
+
+        for _ in 0..4 {
+            assert!(matches!(
+                interpreter.run_until_breakpoint().await.unwrap(),
+                ExecutionState::Running
+            ));
+            assert_eq!(interpreter.state().program_counter(), loop_idx);
+        }
+
+        assert!(interpreter.clear_breakpoint(loop_idx));
+        assert!(interpreter.breakpoints().is_empty());
+
+        assert!(matches!(
+            interpreter.run_until_breakpoint().await.unwrap(),
+            ExecutionState::Paused(PausedState::Halt { .. })
+        ));
+        assert_eq!(interpreter.state_mut().messages().back().unwrap(), "B is 3");
+    }
+
+    #[tokio::test]
+    async fn reset() {
+        let code = "
+            .registers {
+                A, B ← 3x3 builtin (90, 90)
+                C ← theoretical 10
+            }
+
+                add A 3
+                add C 4
+                halt \"A is\" A
+        ";
+
+        let reporter = Reporter::default();
+        let (program, _) = match compile(&file(code), |_| unreachable!(), Arc::clone(&reporter)) {
+            Some(v) => v,
+            None => panic!("{:?}", reporter.iter().collect::<Vec<_>>()),
+        };
+
+        let mut interpreter: Interpreter<SimulatedPuzzle> =
+            Interpreter::new(Arc::new(program), ()).await.unwrap();
+
+        for _ in 0..2 {
+            assert!(matches!(
+                interpreter.step_until_halt().await.unwrap(),
+                PausedState::Halt { .. }
+            ));
+            assert_eq!(interpreter.state_mut().messages().len(), 1);
+            assert_eq!(interpreter.state_mut().messages()[0], "A is 3");
+
+            interpreter.reset().await.unwrap();
+
+            assert_eq!(interpreter.state().program_counter(), 0);
+            assert!(interpreter.state_mut().messages().is_empty());
+            assert!(!interpreter.state().can_step_back());
+            assert!(matches!(
+                interpreter.state().execution_state(),
+                ExecutionState::Running
+            ));
+            assert!(
+                interpreter
+                    .state()
+                    .puzzle_states
+                    .theoretical_state(TheoreticalIdx(0))
+                    .value()
+                    .is_zero()
+            );
+            assert_eq!(
+                interpreter
+                    .state_mut()
+                    .puzzle_state_mut(PuzzleIdx(0))
+                    .puzzle_state(),
+                &Permutation::identity()
+            );
+        }
+    }
 }
@@ -7,17 +7,24 @@ mod remote_robot;
 
 #[cfg(feature = "remote_robot")]
 pub use remote_robot::*;
+
+#[cfg(any(test, feature = "test-util"))]
+mod recording_robot;
+
+#[cfg(any(test, feature = "test-util"))]
+pub use recording_robot::*;
 use serde::{Deserialize, Serialize};
 
-use std::{convert::Infallible, error::Error, fmt::Display, sync::Arc};
+use std::{collections::HashMap, convert::Infallible, error::Error, fmt::Display, sync::Arc};
 
+use log::info;
 use puzzle_theory::{
     numbers::{I, Int, U, lcm_iter},
     permutations::{Algorithm, Permutation, PermutationGroup},
 };
 use qter_core::{
     Program, PuzzleIdx, TheoreticalIdx,
-    architectures::{chromatic_orders_by_facelets, decode},
+    architectures::{IncrementalDecoder, chromatic_orders_by_facelets},
 };
 use tokio_stream::StreamExt;
 
@@ -73,6 +80,20 @@ pub trait PuzzleState {
     /// Check whether the given facelets are solved
     async fn facelets_solved(&mut self, facelets: &[usize]) -> Result<bool, Self::Error>;
 
+    /// Check whether each of the given groups of facelets is solved, returning one result per group. Puzzle states that need to observe a physical puzzle should override this to only observe it once.
+    async fn facelets_solved_many(
+        &mut self,
+        groups: &[&[usize]],
+    ) -> Result<Vec<bool>, Self::Error> {
+        let mut solved = Vec::with_capacity(groups.len());
+
+        for facelets in groups {
+            solved.push(self.facelets_solved(facelets).await?);
+        }
+
+        Ok(solved)
+    }
+
     /// Decode the permutation using the register generator and the given facelets.
     ///
     /// In general, an arbitrary scramble cannot be decoded. If this is the case, the function will return `None`.
@@ -104,6 +125,38 @@ pub trait PuzzleState {
 
     /// Bring the puzzle to the solved state
     async fn solve(&mut self) -> Result<(), Self::Error>;
+
+    /// Bring the puzzle to the given state. This can be used to load values into registers.
+    async fn solve_to(&mut self, target: &Permutation) -> Result<(), Self::Error>;
+
+    /// Get the exact state of the puzzle if it is known without observing a physical puzzle. This allows the interpreter to step backwards over instructions that cannot be inverted algebraically.
+    fn exact_state_mut(&mut self) -> Option<&mut Permutation> {
+        None
+    }
+}
+
+/// Find the facelets whose mappings disagree between the two permutations, in increasing order. `facelet_count` is the number of facelets in the puzzle that the permutations act on.
+#[must_use]
+pub fn facelet_diff(
+    expected: &Permutation,
+    observed: &Permutation,
+    facelet_count: usize,
+) -> Vec<usize> {
+    (0..facelet_count)
+        .filter(|&facelet| expected.mapping().get(facelet) != observed.mapping().get(facelet))
+        .collect()
+}
+
+/// Whether every one of the facelets has the color it has in the solved state
+fn facelets_solved_in(
+    perm_group: &PermutationGroup,
+    state: &Permutation,
+    facelets: &[usize],
+) -> bool {
+    facelets.iter().all(|&facelet| {
+        let maps_to = state.mapping().get(facelet);
+        perm_group.facelet_colors()[maps_to] == perm_group.facelet_colors()[facelet]
+    })
 }
 
 pub trait RobotLike {
@@ -138,21 +191,78 @@ pub trait RobotLike {
         self.compose_perm(&state).await
     }
 
+    /// Bring the puzzle to the given state. Same guarantees as `compose_into`.
+    async fn solve_to(&mut self, target: &Permutation) -> Result<(), Self::Error> {
+        // Let the current state be C and the target be T. We need some X such that C X = T, so X = C⁻¹ T
+        let mut state = self.take_picture().await?.clone();
+        state.invert();
+        state.compose_into(target);
+        self.compose_perm(&state).await
+    }
+
     /// Compose a permutation to the robot; used for solving an unknown permutation. Same guarantees as `compose_into`.
     async fn compose_perm(&mut self, perm: &Permutation) -> Result<(), Self::Error>;
+
+    /// Tell the robot that the puzzle is currently in the `reference` state so that vision-based robots can calibrate against it. Robots that don't need calibration ignore this.
+    async fn calibrate(&mut self, reference: Permutation) -> Result<(), Self::Error> {
+        let _ = reference;
+        Ok(())
+    }
 }
 
 pub struct RobotState<R: RobotLike> {
     robot: R,
     perm_group: Arc<PermutationGroup>,
+    max_decode_iterations: Option<Int<U>>,
+}
+
+#[derive(Debug)]
+pub enum RobotStateError<E> {
+    Robot(E),
+    /// Decoding a register took more iterations than the limit given when initializing the robot state
+    DecodeIterationLimitExceeded {
+        limit: Int<U>,
+    },
 }
 
+impl<E> From<E> for RobotStateError<E> {
+    fn from(value: E) -> Self {
+        Self::Robot(value)
+    }
+}
+
+impl<E: Display> Display for RobotStateError<E> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            RobotStateError::Robot(r) => r.fmt(f),
+            RobotStateError::DecodeIterationLimitExceeded { limit } => {
+                write!(
+                    f,
+                    "Decoding the register took more than the maximum of {limit} iterations"
+                )
+            }
+        }
+    }
+}
+
+impl<E: Error> Error for RobotStateError<E> {}
+
 impl<R: RobotLike> RobotState<R> {
+    /// Get the robot underlying the puzzle state
+    pub fn robot(&self) -> &R {
+        &self.robot
+    }
+
+    /// Get the robot underlying the puzzle state mutably
+    pub fn robot_mut(&mut self) -> &mut R {
+        &mut self.robot
+    }
+
     async fn repeat_until(
         &mut self,
         facelets: &[usize],
         generator: &Algorithm,
-    ) -> Result<Option<Int<U>>, R::Error> {
+    ) -> Result<Option<Int<U>>, RobotStateError<R::Error>> {
         let mut sum = Int::<U>::zero();
 
         let chromatic_orders = chromatic_orders_by_facelets(generator);
@@ -168,6 +278,12 @@ impl<R: RobotLike> RobotState<R> {
                 return Ok(None);
             }
 
+            if let Some(limit) = self.max_decode_iterations
+                && sum > limit
+            {
+                return Err(RobotStateError::DecodeIterationLimitExceeded { limit });
+            }
+
             self.compose_into(generator).await?;
         }
 
@@ -176,36 +292,41 @@ impl<R: RobotLike> RobotState<R> {
 }
 
 impl<R: RobotLike> PuzzleState for RobotState<R> {
-    type InitializationArg = R::InitializationArg;
-    type Error = R::Error;
+    /// The maximum number of times to repeat the generator when decoding a register, along with the arguments to initialize the robot. If the limit is exceeded, decoding fails with `RobotStateError::DecodeIterationLimitExceeded` rather than continuing up to the order of the register.
+    type InitializationArg = (Option<Int<U>>, R::InitializationArg);
+    type Error = RobotStateError<R::Error>;
 
     async fn compose_into(&mut self, alg: &Algorithm) -> Result<(), Self::Error> {
-        self.robot.compose_into(alg).await
+        Ok(self.robot.compose_into(alg).await?)
     }
 
     async fn initialize(
         perm_group: Arc<PermutationGroup>,
-        args: Self::InitializationArg,
+        (max_decode_iterations, args): Self::InitializationArg,
     ) -> Result<Self, Self::Error> {
         Ok(RobotState {
             perm_group: Arc::clone(&perm_group),
             robot: R::initialize(perm_group, args).await?,
+            max_decode_iterations,
         })
     }
 
     async fn facelets_solved(&mut self, facelets: &[usize]) -> Result<bool, Self::Error> {
         let state = self.robot.take_picture().await?;
 
-        for &facelet in facelets {
-            let maps_to = state.mapping().get(facelet);
-            if self.perm_group.facelet_colors()[maps_to]
-                != self.perm_group.facelet_colors()[facelet]
-            {
-                return Ok(false);
-            }
-        }
+        Ok(facelets_solved_in(&self.perm_group, state, facelets))
+    }
+
+    async fn facelets_solved_many(
+        &mut self,
+        groups: &[&[usize]],
+    ) -> Result<Vec<bool>, Self::Error> {
+        let state = self.robot.take_picture().await?;
 
-        Ok(true)
+        Ok(groups
+            .iter()
+            .map(|facelets| facelets_solved_in(&self.perm_group, state, facelets))
+            .collect())
     }
 
     async fn print(
@@ -224,8 +345,12 @@ impl<R: RobotLike> PuzzleState for RobotState<R> {
 
         self.compose_into(&exponentiated).await?;
 
-        if &before != self.robot.take_picture().await? {
-            eprintln!("Printing did not return the cube to the original state!");
+        let after = self.robot.take_picture().await?;
+        if &before != after {
+            eprintln!(
+                "Printing did not return the cube to the original state! Facelets {:?} differ.",
+                facelet_diff(&before, after, self.perm_group.facelet_count())
+            );
             return Ok(None);
         }
         Ok(Some(c))
@@ -252,14 +377,37 @@ impl<R: RobotLike> PuzzleState for RobotState<R> {
     }
 
     async fn solve(&mut self) -> Result<(), Self::Error> {
-        self.robot.solve().await
+        Ok(self.robot.solve().await?)
+    }
+
+    async fn solve_to(&mut self, target: &Permutation) -> Result<(), Self::Error> {
+        Ok(self.robot.solve_to(target).await?)
     }
 }
 
+/// Number the colors of the puzzle, returning the number of the color of each facelet in the solved state
+fn solved_color_ids(perm_group: &PermutationGroup) -> Arc<[usize]> {
+    let mut ids = HashMap::new();
+
+    perm_group
+        .facelet_colors()
+        .iter()
+        .map(|color| {
+            let next_id = ids.len();
+            *ids.entry(color).or_insert(next_id)
+        })
+        .collect()
+}
+
 #[derive(Clone, Debug)]
 pub struct SimulatedPuzzle {
-    perm_group: Arc<PermutationGroup>,
+    /// The color of each facelet in the solved state, numbered so that colors can be compared without going through the permutation group
+    solved_colors: Arc<[usize]>,
+    distinct_colors: bool,
+    /// Changing the state other than through the methods of `SimulatedPuzzle` leaves `decoders` out of date
     pub(crate) state: Permutation,
+    /// A decoder for every register decoded so far, which keep track of register values through the algorithms applied so that counting loops don't decode the whole state every iteration
+    decoders: Vec<IncrementalDecoder>,
 }
 
 impl SimulatedPuzzle {
@@ -267,6 +415,30 @@ impl SimulatedPuzzle {
     pub fn puzzle_state(&self) -> &Permutation {
         &self.state
     }
+
+    fn decode(&mut self, facelets: &[usize], generator: &Algorithm) -> Option<Int<U>> {
+        let idx = match self.decoders.iter().position(|decoder| {
+            decoder.facelets() == facelets && decoder.generator() == generator
+        }) {
+            Some(idx) => idx,
+            None => {
+                self.decoders
+                    .push(IncrementalDecoder::new(facelets.to_vec(), generator.to_owned()));
+                self.decoders.len() - 1
+            }
+        };
+
+        self.decoders[idx].decode(&self.state)
+    }
+
+    fn invalidate_decoders(&mut self) {
+        self.decoders.iter_mut().for_each(IncrementalDecoder::invalidate);
+    }
+
+    /// Check whether facelets are solved by whether they moved instead of by their colors. This skips the color comparisons, which is useful for benchmarking permutation throughput, but is only correct if every facelet of the puzzle has a distinct color.
+    pub fn assume_distinct_colors(&mut self, distinct_colors: bool) {
+        self.distinct_colors = distinct_colors;
+    }
 }
 
 impl PuzzleState for SimulatedPuzzle {
@@ -276,26 +448,30 @@ impl PuzzleState for SimulatedPuzzle {
     async fn initialize(perm_group: Arc<PermutationGroup>, (): ()) -> Result<Self, Infallible> {
         Ok(SimulatedPuzzle {
             state: Permutation::identity(),
-            perm_group,
+            solved_colors: solved_color_ids(&perm_group),
+            distinct_colors: false,
+            decoders: Vec::new(),
         })
     }
 
     async fn compose_into(&mut self, alg: &Algorithm) -> Result<(), Infallible> {
         self.state.compose_into(alg.permutation());
+        for decoder in &mut self.decoders {
+            decoder.apply(alg);
+        }
         Ok(())
     }
 
     async fn facelets_solved(&mut self, facelets: &[usize]) -> Result<bool, Infallible> {
-        for &facelet in facelets {
-            let maps_to = self.state.mapping().get(facelet);
-            if self.perm_group.facelet_colors()[maps_to]
-                != self.perm_group.facelet_colors()[facelet]
-            {
-                return Ok(false);
-            }
+        if self.distinct_colors {
+            return Ok(facelets
+                .iter()
+                .all(|&facelet| self.state.mapping().get(facelet) == facelet));
         }
 
-        Ok(true)
+        Ok(facelets.iter().all(|&facelet| {
+            self.solved_colors[self.state.mapping().get(facelet)] == self.solved_colors[facelet]
+        }))
     }
 
     async fn print(
@@ -303,14 +479,27 @@ impl PuzzleState for SimulatedPuzzle {
         facelets: &[usize],
         generator: &Algorithm,
     ) -> Result<Option<Int<U>>, Infallible> {
-        Ok(decode(&self.state, facelets, generator))
+        Ok(self.decode(facelets, generator))
     }
 
     async fn solve(&mut self) -> Result<(), Infallible> {
         self.state = Permutation::identity();
+        self.invalidate_decoders();
+        Ok(())
+    }
+
+    async fn solve_to(&mut self, target: &Permutation) -> Result<(), Infallible> {
+        self.state = target.clone();
+        self.invalidate_decoders();
         Ok(())
     }
 
+    fn exact_state_mut(&mut self) -> Option<&mut Permutation> {
+        // The caller may change the state arbitrarily
+        self.invalidate_decoders();
+        Some(&mut self.state)
+    }
+
     async fn repeat_until(
         &mut self,
         facelets: &[usize],
@@ -318,7 +507,7 @@ impl PuzzleState for SimulatedPuzzle {
     ) -> Result<Option<()>, Infallible> {
         let mut generator = generator.to_owned();
         generator.exponentiate(-Int::<U>::one());
-        let Some(v) = decode(&self.state, facelets, &generator) else {
+        let Some(v) = self.decode(facelets, &generator) else {
             return Ok(None);
         };
         generator.exponentiate(-v);
@@ -344,14 +533,65 @@ impl RobotLike for SimulatedPuzzle {
     }
 
     async fn solve(&mut self) -> Result<(), Self::Error> {
+        <Self as PuzzleState>::solve(self).await
+    }
+
+    async fn compose_perm(&mut self, perm: &Permutation) -> Result<(), Infallible> {
+        self.state.compose_into(perm);
+        self.invalidate_decoders();
+        Ok(())
+    }
+}
+
+/// A robot that logs every command instead of moving a puzzle, so that the robot server protocol can be exercised end-to-end while bringing up new hardware. Pictures return the state that the commands so far would have produced.
+pub struct DryRunRobot {
+    state: Permutation,
+}
+
+impl RobotLike for DryRunRobot {
+    type InitializationArg = ();
+    type Error = Infallible;
+
+    async fn initialize(_perm_group: Arc<PermutationGroup>, (): ()) -> Result<Self, Infallible> {
+        info!("Dry run: initialized");
+        Ok(DryRunRobot {
+            state: Permutation::identity(),
+        })
+    }
+
+    async fn compose_into(&mut self, alg: &Algorithm) -> Result<(), Infallible> {
+        info!(
+            "Dry run: perform {}",
+            alg.move_seq_iter()
+                .map(|v| &**v)
+                .collect::<Vec<_>>()
+                .join(" ")
+        );
+        self.state.compose_into(alg.permutation());
+        Ok(())
+    }
+
+    async fn take_picture(&mut self) -> Result<&Permutation, Infallible> {
+        info!("Dry run: take a picture, reporting {}", self.state);
+        Ok(&self.state)
+    }
+
+    async fn solve(&mut self) -> Result<(), Infallible> {
+        info!("Dry run: solve");
         self.state = Permutation::identity();
         Ok(())
     }
 
     async fn compose_perm(&mut self, perm: &Permutation) -> Result<(), Infallible> {
+        info!("Dry run: compose {perm}");
         self.state.compose_into(perm);
         Ok(())
     }
+
+    async fn calibrate(&mut self, reference: Permutation) -> Result<(), Infallible> {
+        info!("Dry run: calibrate against {reference}");
+        Ok(())
+    }
 }
 
 /// Simulates the current puzzle state and takes actions when there is a mismatch between the simulated puzzle state and the result of `R::take_picture`.
@@ -495,6 +735,10 @@ impl<R: RobotLike> RobotLike for WrapSimulatedPuzzle<R> {
         self.puzzle.compose_into(perm);
         Ok(self.robot.compose_perm(perm).await?)
     }
+
+    async fn calibrate(&mut self, reference: Permutation) -> Result<(), Self::Error> {
+        Ok(self.robot.calibrate(reference).await?)
+    }
 }
 
 /// A collection of the states of every puzzle and theoretical register
@@ -569,7 +813,25 @@ impl<P: PuzzleState> PuzzleStates<P> {
         &mut self.theoretical_states[idx.0]
     }
 
+    #[must_use]
+    pub fn puzzle_state(&self, idx: PuzzleIdx) -> &P {
+        &self.puzzle_states[idx.0]
+    }
+
     pub fn puzzle_state_mut(&mut self, idx: PuzzleIdx) -> &mut P {
         &mut self.puzzle_states[idx.0]
     }
+
+    /// Zero out every theoretical register and solve every puzzle
+    pub(crate) async fn reset(&mut self) -> Result<(), P::Error> {
+        for theoretical_state in &mut self.theoretical_states {
+            theoretical_state.zero_out();
+        }
+
+        for puzzle_state in &mut self.puzzle_states {
+            puzzle_state.solve().await?;
+        }
+
+        Ok(())
+    }
 }
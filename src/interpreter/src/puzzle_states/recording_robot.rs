@@ -0,0 +1,87 @@
+use std::{convert::Infallible, mem, sync::Arc};
+
+use puzzle_theory::permutations::{Algorithm, Permutation, PermutationGroup};
+
+use super::RobotLike;
+
+/// A command that a `RecordingRobot` received
+#[derive(Clone, Debug, PartialEq)]
+pub enum RecordedCommand {
+    ComposeInto(Algorithm),
+    TakePicture,
+    Solve,
+    ComposePerm(Permutation),
+    Calibrate(Permutation),
+}
+
+/// A robot that simulates the puzzle and records every command it's given so that tests can make assertions about them. This allows QAT programs to be tested end-to-end against the `RobotLike` interface without a physical robot.
+pub struct RecordingRobot {
+    perm_group: Arc<PermutationGroup>,
+    state: Permutation,
+    commands: Vec<RecordedCommand>,
+}
+
+impl RecordingRobot {
+    /// Every command received so far, in the order that they were received
+    #[must_use]
+    pub fn commands(&self) -> &[RecordedCommand] {
+        &self.commands
+    }
+
+    /// Remove and return every command received so far
+    pub fn take_commands(&mut self) -> Vec<RecordedCommand> {
+        mem::take(&mut self.commands)
+    }
+
+    /// The state that the commands so far have put the puzzle in
+    #[must_use]
+    pub fn state(&self) -> &Permutation {
+        &self.state
+    }
+
+    #[must_use]
+    pub fn perm_group(&self) -> &Arc<PermutationGroup> {
+        &self.perm_group
+    }
+}
+
+impl RobotLike for RecordingRobot {
+    type InitializationArg = ();
+    type Error = Infallible;
+
+    async fn initialize(perm_group: Arc<PermutationGroup>, (): ()) -> Result<Self, Infallible> {
+        Ok(RecordingRobot {
+            perm_group,
+            state: Permutation::identity(),
+            commands: Vec::new(),
+        })
+    }
+
+    async fn compose_into(&mut self, alg: &Algorithm) -> Result<(), Infallible> {
+        self.commands.push(RecordedCommand::ComposeInto(alg.clone()));
+        self.state.compose_into(alg.permutation());
+        Ok(())
+    }
+
+    async fn take_picture(&mut self) -> Result<&Permutation, Infallible> {
+        self.commands.push(RecordedCommand::TakePicture);
+        Ok(&self.state)
+    }
+
+    async fn solve(&mut self) -> Result<(), Infallible> {
+        self.commands.push(RecordedCommand::Solve);
+        self.state = Permutation::identity();
+        Ok(())
+    }
+
+    async fn compose_perm(&mut self, perm: &Permutation) -> Result<(), Infallible> {
+        self.commands.push(RecordedCommand::ComposePerm(perm.clone()));
+        self.state.compose_into(perm);
+        Ok(())
+    }
+
+    async fn calibrate(&mut self, reference: Permutation) -> Result<(), Infallible> {
+        self.commands.push(RecordedCommand::Calibrate(reference));
+        Ok(())
+    }
+}
@@ -1,7 +1,8 @@
-use std::{fmt::Display, io::Error, sync::Arc};
+use std::{fmt::Display, io::Error, pin::Pin, sync::Arc};
 
-use log::trace;
+use log::{trace, warn};
 use puzzle_theory::permutations::{Algorithm, Permutation, PermutationGroup};
+use qter_core::stabilizer_chain::StabilizerChain;
 use tokio::io::{
     AsyncBufRead, AsyncBufReadExt, AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt, BufReader,
 };
@@ -42,92 +43,429 @@ impl<T: AsyncRead + AsyncWrite + Unpin> Connection for BufReader<T> {
     }
 }
 
+type ReconnectFn<C> = Box<dyn FnMut() -> Pin<Box<dyn Future<Output = Result<C, Error>>>>>;
+
+/// What `RemoteRobot` does when its connection to the robot server fails
+pub struct ReconnectPolicy<C> {
+    reconnect: Option<ReconnectFn<C>>,
+    max_retries: usize,
+}
+
+impl<C> ReconnectPolicy<C> {
+    /// Never reconnect, returning IO errors immediately
+    #[must_use]
+    pub fn none() -> Self {
+        ReconnectPolicy {
+            reconnect: None,
+            max_retries: 0,
+        }
+    }
+
+    /// When a command fails because of an IO error, open a new connection using `reconnect` and redo the handshake, giving up after `max_retries` failed attempts.
+    ///
+    /// Commands that can safely be performed twice, such as taking a picture or solving the puzzle, are then resent. Moves are not resent because the server may have performed them before the connection failed, in which case resending them would silently put the puzzle in the wrong state. Instead, the error is returned after reconnecting and the caller must check the state of the puzzle before continuing.
+    #[must_use]
+    pub fn new<F: Future<Output = Result<C, Error>> + 'static>(
+        max_retries: usize,
+        mut reconnect: impl FnMut() -> F + 'static,
+    ) -> Self {
+        ReconnectPolicy {
+            reconnect: Some(Box::new(move || Box::pin(reconnect()))),
+            max_retries,
+        }
+    }
+}
+
+impl<C> Default for ReconnectPolicy<C> {
+    fn default() -> Self {
+        Self::none()
+    }
+}
+
+/// How the server encodes the puzzle state in its response to `!PICTURE`. The client asks for the newest format with `!PICTURE-VERSION <version>` after sending the group, and servers that don't know the version reject it, leaving the format unchanged.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Default)]
+pub enum PictureFormat {
+    /// Version 1: the permutation in cycle notation, for example `(0, 1)`. This is what servers send unless asked otherwise.
+    #[default]
+    Cycles,
+    /// Version 2: `2`, the number of facelets, and then the facelet that each facelet is moved to, all separated by spaces. Every facelet is listed explicitly, so orientation and piece positions don't depend on any cycle notation conventions.
+    Mapping,
+}
+
+impl PictureFormat {
+    /// The format that clients ask for when connecting
+    pub const NEWEST: PictureFormat = PictureFormat::Mapping;
+
+    fn version(self) -> u8 {
+        match self {
+            PictureFormat::Cycles => 1,
+            PictureFormat::Mapping => 2,
+        }
+    }
+
+    fn from_version(version: &str) -> Option<PictureFormat> {
+        match version {
+            "1" => Some(PictureFormat::Cycles),
+            "2" => Some(PictureFormat::Mapping),
+            _ => None,
+        }
+    }
+
+    fn encode(self, state: &Permutation, facelet_count: usize) -> String {
+        match self {
+            PictureFormat::Cycles => state.to_string(),
+            PictureFormat::Mapping => {
+                let mut encoded = format!("2 {facelet_count}");
+
+                for facelet in 0..facelet_count {
+                    encoded.push(' ');
+                    encoded.push_str(&state.mapping().get(facelet).to_string());
+                }
+
+                encoded
+            }
+        }
+    }
+
+    fn decode(self, picture: &str, facelet_count: usize) -> Result<Permutation, Error> {
+        match self {
+            PictureFormat::Cycles => picture.parse::<Permutation>().map_err(Error::other),
+            PictureFormat::Mapping => {
+                let mut numbers = picture
+                    .split_whitespace()
+                    .map(str::parse::<usize>)
+                    .collect::<Result<Vec<_>, _>>()
+                    .map_err(|e| Error::other(format!("Malformed picture `{picture}`: {e}")))?
+                    .into_iter();
+
+                if numbers.next() != Some(2) {
+                    return Err(Error::other(format!(
+                        "Expected a version 2 picture, found `{picture}`"
+                    )));
+                }
+
+                if numbers.next() != Some(facelet_count) || numbers.len() != facelet_count {
+                    return Err(Error::other(format!(
+                        "Expected a picture of {facelet_count} facelets, found `{picture}`"
+                    )));
+                }
+
+                let mapping = numbers.collect::<Vec<_>>();
+
+                let mut seen = vec![false; facelet_count];
+                for &maps_to in &mapping {
+                    if maps_to >= facelet_count || seen[maps_to] {
+                        return Err(Error::other(format!(
+                            "The picture `{picture}` is not a permutation"
+                        )));
+                    }
+                    seen[maps_to] = true;
+                }
+
+                let mut visited = vec![false; facelet_count];
+                let mut cycles = Vec::new();
+
+                for start in 0..facelet_count {
+                    let mut cycle = Vec::new();
+                    let mut facelet = start;
+
+                    while !visited[facelet] {
+                        visited[facelet] = true;
+                        cycle.push(facelet);
+                        facelet = mapping[facelet];
+                    }
+
+                    if cycle.len() > 1 {
+                        cycles.push(cycle);
+                    }
+                }
+
+                Ok(Permutation::from_cycles(cycles))
+            }
+        }
+    }
+}
+
 pub struct RemoteRobot<C: Connection> {
     conn: C,
+    group: Arc<PermutationGroup>,
+    /// Used to reject pictures of states that the puzzle can't be in
+    chain: StabilizerChain,
     current_state: Option<Permutation>,
+    reconnect_policy: ReconnectPolicy<C>,
+    picture_format: PictureFormat,
+}
+
+/// Read the message that follows an `!ERR`
+async fn read_err_message<R: AsyncBufRead + Unpin + ?Sized>(
+    reader: &mut R,
+) -> Result<Error, Error> {
+    let mut len_be = [0; 2];
+    reader.read_exact(&mut len_be).await?;
+    let len = u16::from_be_bytes(len_be) as usize;
+
+    let mut message = Box::from(vec![0; len]);
+    reader.read_exact(&mut message).await?;
+
+    // Refactor once https://github.com/rust-lang/rust/issues/129436 is stable
+    Ok(Error::other(String::from_utf8_lossy(&message)))
 }
 
-async fn ack_or_err<C: Connection>(conn: &mut C) -> Result<(), Error> {
+/// Read the server's acknowledgement of a command. The outer result is an error if the connection failed while the inner result is an error if the server rejected the command.
+async fn ack_or_err<C: Connection>(conn: &mut C) -> Result<Result<(), Error>, Error> {
     let reader = conn.reader();
 
     let mut which = [0; 5];
     reader.read_exact(&mut which).await?;
 
     if which == *b"!ACK\n" {
-        Ok(())
+        Ok(Ok(()))
     } else if which == *b"!ERR\n" {
-        let mut len_be = [0; 2];
-        reader.read_exact(&mut len_be).await?;
-        let len = u16::from_be_bytes(len_be) as usize;
+        Ok(Err(read_err_message(reader).await?))
+    } else {
+        Ok(Err(Error::other("Server did not correctly acknowledge command")))
+    }
+}
 
-        let mut message = Box::from(vec![0; len]);
-        reader.read_exact(&mut message).await?;
+/// What the server replies to a command with
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Reply {
+    /// An acknowledgement
+    Ack,
+    /// An acknowledgement followed by a line of response
+    AckThenLine,
+    /// A `!PONG`. Servers that don't support pings reject them with an error, which keeps the connection alive just as well, so that isn't treated as a failure.
+    Pong,
+}
 
-        // Refactor once https://github.com/rust-lang/rust/issues/129436 is stable
-        Err(Error::other(String::from_utf8_lossy(&message)))
-    } else {
-        Err(Error::other("Server did not correctly acknowledge command"))
+/// Send a command and wait for the server's reply, returning the line that follows the acknowledgement for `Reply::AckThenLine`. The results are nested the same way as for `ack_or_err`.
+async fn send_command<C: Connection>(
+    conn: &mut C,
+    command: &[u8],
+    reply: Reply,
+) -> Result<Result<Option<String>, Error>, Error> {
+    let writer = conn.writer();
+    writer.write_all(command).await?;
+    writer.write_all(b"\n").await?;
+    writer.flush().await?;
+
+    if reply == Reply::Pong {
+        let reader = conn.reader();
+
+        let mut which = [0; 5];
+        reader.read_exact(&mut which).await?;
+
+        if which == *b"!PONG" {
+            reader.read_exact(&mut [0]).await?;
+        } else if which == *b"!ERR\n" {
+            read_err_message(reader).await?;
+        } else {
+            return Ok(Err(Error::other("Server did not correctly respond to a ping")));
+        }
+
+        return Ok(Ok(None));
+    }
+
+    if let Err(e) = ack_or_err(conn).await? {
+        return Ok(Err(e));
+    }
+
+    if reply == Reply::Ack {
+        return Ok(Ok(None));
     }
+
+    let mut response = String::new();
+    conn.reader().read_line(&mut response).await?;
+    Ok(Ok(Some(response)))
 }
 
-impl<C: Connection> RobotLike for RemoteRobot<C> {
-    type InitializationArg = C;
-    type Error = Error;
+/// Send the puzzle's group to the server, which it expects before any commands, then ask for the given picture format. Returns the picture format that the server agreed to.
+async fn handshake<C: Connection>(
+    group: &PermutationGroup,
+    mut conn: C,
+    picture_format: PictureFormat,
+) -> Result<(C, PictureFormat), Error> {
+    let mut encoded = serde_json::to_vec(group).map_err(|e| Error::other(e.to_string()))?;
+    encoded.push(b'\n');
+
+    let Ok(len) = u16::try_from(encoded.len()) else {
+        return Err(Error::other(format!(
+            "Cannot send a group with such a large encoding to the server ({} > 65535)",
+            encoded.len()
+        )));
+    };
+
+    let len = len.to_be_bytes();
+
+    conn.writer().write_all(&len).await?;
+    conn.writer().write_all(&encoded).await?;
+    conn.writer().flush().await?;
+
+    ack_or_err(&mut conn).await??;
+
+    // Servers start out with the legacy format, so there's only something to negotiate otherwise
+    if picture_format == PictureFormat::Cycles {
+        return Ok((conn, picture_format));
+    }
+
+    let command = format!("!PICTURE-VERSION {}", picture_format.version());
+    match send_command(&mut conn, command.as_bytes(), Reply::Ack).await? {
+        Ok(_) => Ok((conn, picture_format)),
+        Err(e) => {
+            warn!("The robot server doesn't support {picture_format:?} pictures: {e}");
+            Ok((conn, PictureFormat::Cycles))
+        }
+    }
+}
 
-    async fn initialize(group: Arc<PermutationGroup>, mut conn: C) -> Result<Self, Self::Error> {
-        let mut encoded = serde_json::to_vec(&*group).map_err(|e| Error::other(e.to_string()))?;
-        encoded.push(b'\n');
+impl<C: Connection> RemoteRobot<C> {
+    /// Exchange a `!PING` for a `!PONG` with the server, reconnecting according to the reconnect policy if the connection fails. This keeps transports that close idle connections from doing so while the client isn't sending commands, for example while the interpreter is paused waiting for input.
+    ///
+    /// Decoding a register in `halt` or `repeat-until` sends commands every iteration, so it doesn't leave the connection idle and doesn't need pings. On the other hand, the server handles one command at a time, so pings can't keep the connection alive while the server is executing a long command.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the connection failed
+    pub async fn ping(&mut self) -> Result<(), Error> {
+        self.command(b"!PING", Reply::Pong, true).await?;
+        Ok(())
+    }
 
-        let Ok(len) = u16::try_from(encoded.len()) else {
-            return Err(Error::other(format!(
-                "Cannot send a group with such a large encoding to the server ({} > 65535)",
-                encoded.len()
-            )));
+    /// Ask the server to send pictures in the given format, falling back to `PictureFormat::Cycles` if it doesn't support it. The format is negotiated again whenever the connection is reestablished. Returns the format that will be used.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the connection failed
+    pub async fn negotiate_picture_format(
+        &mut self,
+        picture_format: PictureFormat,
+    ) -> Result<PictureFormat, Error> {
+        let command = format!("!PICTURE-VERSION {}", picture_format.version());
+
+        let response = send_command(&mut self.conn, command.as_bytes(), Reply::Ack).await?;
+
+        self.picture_format = match response {
+            Ok(_) => picture_format,
+            Err(e) => {
+                warn!("The robot server doesn't support {picture_format:?} pictures: {e}");
+                self.picture_format
+            }
         };
 
-        let len = len.to_be_bytes();
+        Ok(self.picture_format)
+    }
 
-        conn.writer().write_all(&len).await?;
-        conn.writer().write_all(&encoded).await?;
-        conn.writer().flush().await?;
+    /// The format that the server sends pictures in
+    #[must_use]
+    pub fn picture_format(&self) -> PictureFormat {
+        self.picture_format
+    }
+
+    /// Send a command to the server, reconnecting according to the reconnect policy if the connection fails. Commands are only resent after reconnecting if `idempotent` is set, since otherwise the server may have performed the command before the connection failed.
+    async fn command(
+        &mut self,
+        command: &[u8],
+        reply: Reply,
+        idempotent: bool,
+    ) -> Result<Option<String>, Error> {
+        let mut retries_left = self.reconnect_policy.max_retries;
+
+        loop {
+            let io_error = match send_command(&mut self.conn, command, reply).await {
+                Ok(response) => return response,
+                Err(e) => e,
+            };
+
+            if !self.reconnect(&mut retries_left, &io_error).await {
+                return Err(io_error);
+            }
+
+            if !idempotent {
+                self.current_state = None;
+                return Err(Error::new(
+                    io_error.kind(),
+                    format!(
+                        "Lost the connection to the robot server while sending `{}`, so it may or may not have been performed: {io_error}",
+                        String::from_utf8_lossy(command)
+                    ),
+                ));
+            }
+        }
+    }
 
-        ack_or_err(&mut conn).await?;
+    /// Open a new connection and redo the handshake, using up a retry for every attempt. Returns whether a connection was established.
+    async fn reconnect(&mut self, retries_left: &mut usize, io_error: &Error) -> bool {
+        let Some(reconnect) = &mut self.reconnect_policy.reconnect else {
+            return false;
+        };
+
+        while *retries_left > 0 {
+            *retries_left -= 1;
+
+            warn!("Lost the connection to the robot server, reconnecting: {io_error}");
+
+            match reconnect().await {
+                Ok(conn) => match handshake(&self.group, conn, self.picture_format).await {
+                    Ok((conn, picture_format)) => {
+                        self.conn = conn;
+                        self.picture_format = picture_format;
+                        return true;
+                    }
+                    Err(e) => warn!("Failed to redo the handshake with the robot server: {e}"),
+                },
+                Err(e) => warn!("Failed to reconnect to the robot server: {e}"),
+            }
+        }
+
+        false
+    }
+}
+
+impl<C: Connection> RobotLike for RemoteRobot<C> {
+    /// The connection to the server along with what to do if it fails
+    type InitializationArg = (C, ReconnectPolicy<C>);
+    type Error = Error;
+
+    async fn initialize(
+        group: Arc<PermutationGroup>,
+        (conn, reconnect_policy): (C, ReconnectPolicy<C>),
+    ) -> Result<Self, Self::Error> {
+        let (conn, picture_format) = handshake(&group, conn, PictureFormat::NEWEST).await?;
 
         Ok(RemoteRobot {
             conn,
+            chain: StabilizerChain::new(&group),
+            group,
             current_state: None,
+            reconnect_policy,
+            picture_format,
         })
     }
 
     async fn compose_into(&mut self, alg: &Algorithm) -> Result<(), Self::Error> {
         self.current_state = None;
-        let writer = self.conn.writer();
-        writer
-            .write_all(
-                alg.move_seq_iter()
-                    .map(|v| &**v)
-                    .collect::<Vec<_>>()
-                    .join(" ")
-                    .as_bytes(),
-            )
-            .await?;
-        writer.write_all("\n".as_bytes()).await?;
-        writer.flush().await?;
-        ack_or_err(&mut self.conn).await
+        let command = alg
+            .move_seq_iter()
+            .map(|v| &**v)
+            .collect::<Vec<_>>()
+            .join(" ");
+        self.command(command.as_bytes(), Reply::Ack, false).await?;
+        Ok(())
     }
 
     async fn take_picture(&mut self) -> Result<&Permutation, Self::Error> {
         // Note that I can't check for `Some` and return early because the borrow checker isn't smart enough to recognize that that is okay
         if self.current_state.is_none() {
-            let writer = self.conn.writer();
-            writer.write_all(b"!PICTURE\n").await?;
-            writer.flush().await?;
+            let picture = self.command(b"!PICTURE", Reply::AckThenLine, true).await?.unwrap();
+            let state = self.picture_format.decode(&picture, self.group.facelet_count())?;
 
-            ack_or_err(&mut self.conn).await?;
+            if !self.chain.contains(&state) {
+                return Err(Error::other(format!(
+                    "The picture `{}` is not a state that the puzzle can be in",
+                    picture.trim()
+                )));
+            }
 
-            let mut perm_str = String::new();
-            self.conn.reader().read_line(&mut perm_str).await?;
-            let state = perm_str.parse::<Permutation>().map_err(Error::other)?;
             let _ = self.current_state.insert(state);
         }
 
@@ -137,18 +475,19 @@ impl<C: Connection> RobotLike for RemoteRobot<C> {
     async fn solve(&mut self) -> Result<(), Self::Error> {
         self.current_state = Some(Permutation::identity());
 
-        let writer = self.conn.writer();
-        writer.write_all(b"!SOLVE\n").await?;
-        writer.flush().await?;
-        ack_or_err(&mut self.conn).await
+        self.command(b"!SOLVE", Reply::Ack, true).await?;
+        Ok(())
     }
 
     async fn compose_perm(&mut self, perm: &Permutation) -> Result<(), Self::Error> {
-        let writer = self.conn.writer();
-        writer.write_all(perm.to_string().as_bytes()).await?;
-        writer.write_all(b"\n").await?;
-        writer.flush().await?;
-        ack_or_err(&mut self.conn).await
+        self.command(perm.to_string().as_bytes(), Reply::Ack, false).await?;
+        Ok(())
+    }
+
+    async fn calibrate(&mut self, reference: Permutation) -> Result<(), Self::Error> {
+        self.command(format!("!CALIBRATE {reference}").as_bytes(), Reply::Ack, true)
+            .await?;
+        Ok(())
     }
 }
 
@@ -176,8 +515,17 @@ async fn send_ack<V, C: Connection>(
     }
 }
 
+async fn send_pong<C: Connection>(conn: &mut C) -> Result<(), Error> {
+    let writer = conn.writer();
+    writer.write_all(b"!PONG\n").await?;
+    writer.flush().await?;
+    Ok(())
+}
+
 /// Enable remote control of a robot through the given connection.
 ///
+/// A `!PING` command is answered with `!PONG` rather than being acknowledged like the other commands. It doesn't reach the robot and exists only to keep idle connections alive. `!CALIBRATE` followed by a permutation is forwarded to `RobotLike::calibrate`. `!PICTURE-VERSION` followed by a version number changes how later pictures are encoded; see `PictureFormat`.
+///
 /// # Errors
 ///
 /// Returns an error in case of an IO failure of the channel. All errors given by the robot are forwarded through the connection by stringifying them.
@@ -215,6 +563,8 @@ where
         return Ok(());
     };
 
+    let mut picture_format = PictureFormat::Cycles;
+
     loop {
         let mut command = String::new();
         conn.reader()
@@ -230,7 +580,30 @@ where
 
         let command = command.trim();
 
-        if command == "!SOLVE" {
+        if command == "!PING" {
+            send_pong(&mut conn).await?;
+        } else if let Some(version) = command.strip_prefix("!PICTURE-VERSION ") {
+            if let Some(new_format) = send_ack(
+                &mut conn,
+                PictureFormat::from_version(version)
+                    .ok_or_else(|| format!("Unsupported picture version {version}")),
+            )
+            .await?
+            {
+                picture_format = new_format;
+            }
+        } else if let Some(reference) = command.strip_prefix("!CALIBRATE ") {
+            send_ack(
+                &mut conn,
+                async {
+                    let reference = reference.parse::<Permutation>()?;
+
+                    robot.calibrate(reference).await.map_err(|e| e.to_string())
+                }
+                .await,
+            )
+            .await?;
+        } else if command == "!SOLVE" {
             send_ack(&mut conn, robot.solve().await.map_err(|v| v.to_string())).await?;
         } else if command == "!PICTURE" {
             let Some(state) = send_ack(
@@ -242,8 +615,10 @@ where
                 continue;
             };
 
+            let picture = picture_format.encode(state, group.facelet_count());
+
             let writer = conn.writer();
-            writer.write_all(state.to_string().as_bytes()).await?;
+            writer.write_all(picture.as_bytes()).await?;
             writer.write_all("\n".as_bytes()).await?;
             writer.flush().await?;
         } else if command.starts_with('(') {
@@ -274,20 +649,23 @@ where
 
 #[cfg(test)]
 mod tests {
-    use std::{collections::VecDeque, sync::Arc};
+    use std::{collections::VecDeque, io::Error, sync::Arc};
 
     use puzzle_theory::{
         permutations::{Algorithm, Permutation, PermutationGroup},
         puzzle_geometry::parsing::puzzle,
     };
-    use tokio::io::{AsyncReadExt, AsyncWriteExt, BufReader};
+    use tokio::io::{AsyncReadExt, AsyncWriteExt, BufReader, BufWriter};
     use tokio_util::io::simplex;
 
-    use super::{RemoteRobot, RobotLike, run_robot_server};
+    use super::{PictureFormat, ReconnectPolicy, RemoteRobot, RobotLike, run_robot_server};
+    use crate::puzzle_states::DryRunRobot;
 
     #[tokio::test]
     async fn remote_robot() {
         let cube3 = puzzle("3x3").permutation_group();
+        let u = Algorithm::parse_from_string(Arc::clone(&cube3), "U").unwrap();
+        let u_picture = PictureFormat::Mapping.encode(u.permutation(), cube3.facelet_count());
 
         // Yes, we do in fact have to use the `tokio-util` simplex instead of the `tokio` simplex
         // https://github.com/tokio-rs/tokio/issues/6914
@@ -296,7 +674,8 @@ mod tests {
 
         let task = tokio::spawn(async move {
             tx.write_all(
-                b"!ACK\n!ACK\n!ACK\n(0, 1)\n!ACK\n!ACK\n!ERR\n\0\x03ABC!ERR\n\0\x04ABCD!ERR\n\0\x04XYZW!ERR\n\0\x05ABCDE",
+                format!("!ACK\n!ACK\n!ACK\n!ACK\n{u_picture}\n!ACK\n!ACK\n!ERR\n\0\x03ABC!ERR\n\0\x04ABCD!ERR\n\0\x04XYZW!ERR\n\0\x05ABCDE")
+                    .as_bytes(),
             )
             .await
             .unwrap();
@@ -307,15 +686,18 @@ mod tests {
             rx.read_to_string(&mut data).await.unwrap();
             assert_eq!(
                 data,
-                "\0\x06\"3x3\"\nU D U2 D2 U' D'\n!PICTURE\n(1, 2, 3)\n!SOLVE\nU\n!PICTURE\n()\n!SOLVE\n"
+                "\0\x06\"3x3\"\n!PICTURE-VERSION 2\nU D U2 D2 U' D'\n!PICTURE\n(1, 2, 3)\n!SOLVE\nU\n!PICTURE\n()\n!SOLVE\n"
             );
         });
 
         let robot_rx = BufReader::new(robot_rx);
 
-        let mut remote_robot = RemoteRobot::initialize(Arc::clone(&cube3), (robot_rx, robot_tx))
-            .await
-            .unwrap();
+        let mut remote_robot = RemoteRobot::initialize(
+            Arc::clone(&cube3),
+            ((robot_rx, robot_tx), ReconnectPolicy::none()),
+        )
+        .await
+        .unwrap();
 
         remote_robot
             .compose_into(
@@ -323,14 +705,8 @@ mod tests {
             )
             .await
             .unwrap();
-        assert_eq!(
-            remote_robot.take_picture().await.unwrap(),
-            &Permutation::from_cycles(vec![vec![0, 1]])
-        );
-        assert_eq!(
-            remote_robot.take_picture().await.unwrap(),
-            &Permutation::from_cycles(vec![vec![0, 1]])
-        );
+        assert_eq!(remote_robot.take_picture().await.unwrap(), u.permutation());
+        assert_eq!(remote_robot.take_picture().await.unwrap(), u.permutation());
         remote_robot
             .compose_perm(&Permutation::from_cycles(vec![vec![1, 2, 3]]))
             .await
@@ -360,6 +736,231 @@ mod tests {
         task.await.unwrap();
     }
 
+    #[tokio::test]
+    async fn remote_robot_reconnects() {
+        let cube3 = puzzle("3x3").permutation_group();
+        let u = Algorithm::parse_from_string(Arc::clone(&cube3), "U").unwrap();
+        let last_response = format!(
+            "!ACK\n!ACK\n!ACK\n{}\n",
+            PictureFormat::Mapping.encode(u.permutation(), cube3.facelet_count())
+        );
+
+        // Every connection drops right after the handshake, except for the last which is only used to take a picture
+        let mut txs = Vec::new();
+        let mut rxs = Vec::new();
+        let mut conns = VecDeque::new();
+
+        for response in [&b"!ACK\n!ACK\n"[..], b"!ACK\n!ACK\n", last_response.as_bytes()] {
+            let (mut tx, robot_rx) = simplex::new(1000);
+            let (robot_tx, rx) = simplex::new(1000);
+            tx.write_all(response).await.unwrap();
+            txs.push(tx);
+            rxs.push(rx);
+            conns.push_back((BufReader::new(robot_rx), robot_tx));
+        }
+        drop(txs);
+
+        let first_conn = conns.pop_front().unwrap();
+        let reconnect_policy = ReconnectPolicy::new(1, move || {
+            let conn = conns.pop_front();
+            async move { conn.ok_or_else(|| Error::other("No more connections")) }
+        });
+
+        let mut remote_robot =
+            RemoteRobot::initialize(Arc::clone(&cube3), (first_conn, reconnect_policy))
+                .await
+                .unwrap();
+
+        // The move may have been performed before the connection dropped, so it isn't resent
+        assert!(remote_robot.compose_into(&u).await.is_err());
+
+        // Taking a picture is safe to repeat
+        assert_eq!(remote_robot.take_picture().await.unwrap(), u.permutation());
+
+        drop(remote_robot);
+
+        let mut sent = Vec::new();
+        for mut rx in rxs {
+            let mut data = String::new();
+            rx.read_to_string(&mut data).await.unwrap();
+            sent.push(data);
+        }
+
+        assert_eq!(
+            sent,
+            [
+                "\0\x06\"3x3\"\n!PICTURE-VERSION 2\nU\n",
+                "\0\x06\"3x3\"\n!PICTURE-VERSION 2\n!PICTURE\n",
+                "\0\x06\"3x3\"\n!PICTURE-VERSION 2\n!PICTURE\n"
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn invalid_picture() {
+        let cube3 = puzzle("3x3").permutation_group();
+
+        // Swapping two stickers isn't something that the puzzle can do
+        let swap = Permutation::from_cycles(vec![vec![0, 1]]);
+
+        let responses = [
+            // A server that only knows the cycles format rejects the request for a newer one
+            format!("!ACK\n!ERR\n\0\x03ABC!ACK\n{swap}\n"),
+            format!(
+                "!ACK\n!ACK\n!ACK\n{}\n",
+                PictureFormat::Mapping.encode(&swap, cube3.facelet_count())
+            ),
+        ];
+
+        for response in responses {
+            let (mut tx, robot_rx) = simplex::new(1000);
+            let (robot_tx, _rx) = simplex::new(1000);
+            tx.write_all(response.as_bytes()).await.unwrap();
+
+            let mut remote_robot = RemoteRobot::initialize(
+                Arc::clone(&cube3),
+                ((BufReader::new(robot_rx), robot_tx), ReconnectPolicy::none()),
+            )
+            .await
+            .unwrap();
+
+            assert!(remote_robot.take_picture().await.is_err());
+        }
+    }
+
+    #[test]
+    fn picture_formats() {
+        let cube3 = puzzle("3x3").permutation_group();
+        let u = Algorithm::parse_from_string(Arc::clone(&cube3), "U").unwrap();
+
+        for format in [PictureFormat::Cycles, PictureFormat::Mapping] {
+            let encoded = format.encode(u.permutation(), cube3.facelet_count());
+            assert_eq!(
+                &format.decode(&encoded, cube3.facelet_count()).unwrap(),
+                u.permutation()
+            );
+        }
+
+        assert_eq!(
+            PictureFormat::Mapping.decode("2 3 1 0 2\n", 3).unwrap(),
+            Permutation::from_cycles(vec![vec![0, 1]])
+        );
+        assert!(PictureFormat::Mapping.decode("2 3 0 0 1", 3).is_err());
+        assert!(PictureFormat::Mapping.decode("2 3 1 0", 3).is_err());
+        assert!(PictureFormat::Mapping.decode("2 4 1 0 2 3", 3).is_err());
+        assert!(PictureFormat::Mapping.decode("(0, 1)", 3).is_err());
+    }
+
+    #[tokio::test]
+    async fn negotiate_picture_format() {
+        let cube3 = puzzle("3x3").permutation_group();
+        let u = Algorithm::parse_from_string(Arc::clone(&cube3), "U").unwrap();
+        let u2 = Algorithm::parse_from_string(Arc::clone(&cube3), "U2").unwrap();
+
+        let (client_tx, server_rx) = simplex::new(1000);
+        let (server_tx, client_rx) = simplex::new(1000);
+
+        let server = run_robot_server::<_, DryRunRobot>((BufReader::new(server_rx), server_tx), ());
+
+        let client = async {
+            let mut remote_robot = RemoteRobot::initialize(
+                Arc::clone(&cube3),
+                ((BufReader::new(client_rx), client_tx), ReconnectPolicy::none()),
+            )
+            .await
+            .unwrap();
+
+            // The newest format is negotiated while connecting
+            assert_eq!(remote_robot.picture_format(), PictureFormat::Mapping);
+
+            remote_robot.compose_into(&u).await.unwrap();
+            assert_eq!(remote_robot.take_picture().await.unwrap(), u.permutation());
+
+            assert_eq!(
+                remote_robot
+                    .negotiate_picture_format(PictureFormat::Cycles)
+                    .await
+                    .unwrap(),
+                PictureFormat::Cycles
+            );
+
+            remote_robot.compose_into(&u).await.unwrap();
+            assert_eq!(remote_robot.take_picture().await.unwrap(), u2.permutation());
+        };
+
+        let (server, ()) = tokio::join!(server, client);
+        server.unwrap();
+
+        // Servers that don't know the command reject it and keep sending cycles
+        let (mut tx, robot_rx) = simplex::new(1000);
+        let (robot_tx, _rx) = simplex::new(1000);
+        tx.write_all(b"!ACK\n!ERR\n\0\x03ABC!ERR\n\0\x03ABC")
+            .await
+            .unwrap();
+
+        let mut remote_robot = RemoteRobot::initialize(
+            Arc::clone(&cube3),
+            ((BufReader::new(robot_rx), robot_tx), ReconnectPolicy::none()),
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(remote_robot.picture_format(), PictureFormat::Cycles);
+        assert_eq!(
+            remote_robot
+                .negotiate_picture_format(PictureFormat::Mapping)
+                .await
+                .unwrap(),
+            PictureFormat::Cycles
+        );
+    }
+
+    #[tokio::test]
+    async fn ping() {
+        let cube3 = puzzle("3x3").permutation_group();
+
+        let (client_tx, server_rx) = simplex::new(1000);
+        let (server_tx, client_rx) = simplex::new(1000);
+
+        // Buffer the server's writes so that a reply that isn't flushed never arrives
+        let server = run_robot_server::<_, DryRunRobot>(
+            (BufReader::new(server_rx), BufWriter::new(server_tx)),
+            (),
+        );
+
+        let client = async {
+            let mut remote_robot = RemoteRobot::initialize(
+                Arc::clone(&cube3),
+                ((BufReader::new(client_rx), client_tx), ReconnectPolicy::none()),
+            )
+            .await
+            .unwrap();
+
+            remote_robot.ping().await.unwrap();
+            remote_robot.solve().await.unwrap();
+            remote_robot.ping().await.unwrap();
+        };
+
+        let (server, ()) = tokio::join!(server, client);
+        server.unwrap();
+
+        // Servers without ping support reject it like an invalid algorithm, as well as the request for a newer picture format
+        let (mut tx, robot_rx) = simplex::new(1000);
+        let (robot_tx, _rx) = simplex::new(1000);
+        tx.write_all(b"!ACK\n!ERR\n\0\x03ABC!ERR\n\0\x03ABC")
+            .await
+            .unwrap();
+
+        let mut remote_robot = RemoteRobot::initialize(
+            cube3,
+            ((BufReader::new(robot_rx), robot_tx), ReconnectPolicy::none()),
+        )
+        .await
+        .unwrap();
+
+        remote_robot.ping().await.unwrap();
+    }
+
     #[derive(PartialEq, Eq, Debug)]
     enum Command {
         ComposeInto {
@@ -497,4 +1098,31 @@ mod tests {
 
         assert_eq!(commands, VecDeque::new());
     }
+
+    #[tokio::test]
+    async fn dry_run_robot_server() {
+        let (mut tx, robot_rx) = simplex::new(1000);
+        let (robot_tx, mut rx) = simplex::new(1000);
+
+        let u = Algorithm::parse_from_string(puzzle("3x3").permutation_group(), "U").unwrap();
+        let expected = format!("!ACK\n!ACK\n!ACK\n{}\n!ACK\n!ACK\n", u.permutation());
+
+        let task = tokio::spawn(async move {
+            tx.write_all(b"\0\x06\"3x3\"\nU\n!PICTURE\n!SOLVE\n!CALIBRATE (0, 1)\n")
+                .await
+                .unwrap();
+            drop(tx);
+
+            let mut out = String::new();
+            rx.read_to_string(&mut out).await.unwrap();
+
+            assert_eq!(out, expected);
+        });
+
+        run_robot_server::<_, DryRunRobot>((BufReader::new(robot_rx), robot_tx), ())
+            .await
+            .unwrap();
+
+        task.await.unwrap();
+    }
 }
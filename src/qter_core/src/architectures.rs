@@ -1,7 +1,8 @@
 use std::{
     borrow::Cow,
-    collections::BTreeMap,
-    fmt::Debug,
+    collections::{BTreeMap, BTreeSet, HashMap},
+    error::Error,
+    fmt::{Debug, Display, Write},
     sync::{Arc, LazyLock, OnceLock},
 };
 
@@ -15,6 +16,8 @@ use puzzle_theory::{
 
 use crate::{Facelets, shared_facelet_detection::algorithms_to_cycle_generators, table_encoding};
 
+pub use crate::shared_facelet_detection::{SharedFaceletReport, shared_facelet_report};
+
 pub(crate) const OPTIMIZED_TABLES: [&[u8]; 5] = [
     include_bytes!("../puzzles/210-24.bin"),
     include_bytes!("../puzzles/30-30-30.bin"),
@@ -101,21 +104,82 @@ impl CycleGeneratorSubcycle {
 
 /// Create an `Algorithm` from what values it should add to which registers.
 ///
-/// `effect` is a list of tuples of register indices and how much to add to add to them.
+/// `effect` is a list of tuples of register indices and how much to add to add to them. If the effect doesn't change any register, the identity is returned.
 #[allow(clippy::missing_panics_doc)]
 pub fn new_from_effect(arch: &Architecture, effect: Vec<(usize, Int<U>)>) -> Algorithm {
-    let mut move_seq = Vec::new();
+    let move_seq = effect_move_seq(arch, effect);
+
+    if move_seq.is_empty() {
+        return Algorithm::identity(arch.group_arc());
+    }
+
+    Algorithm::new_from_move_seq(arch.group_arc(), move_seq).unwrap()
+}
 
+/// Estimate how expensive it is to add the given values to the given registers, as the number of moves in the algorithm that `new_from_effect` would create.
+pub fn effect_cost(arch: &Architecture, effect: &[(usize, Int<U>)]) -> usize {
+    effect_move_seq(arch, effect.iter().copied()).len()
+}
+
+fn effect_move_seq(
+    arch: &Architecture,
+    effect: impl IntoIterator<Item = (usize, Int<U>)>,
+) -> Vec<ArcIntern<str>> {
     let mut expanded_effect = vec![Int::<U>::zero(); arch.registers().len()];
 
     for (register, amt) in effect {
         expanded_effect[register] = amt % arch.registers()[register].order();
     }
 
-    let table = arch.decoding_table();
+    table_move_seq(arch.decoding_table(), expanded_effect.clone())
+        .unwrap_or_else(|| generator_move_seq(arch, &expanded_effect))
+}
+
+/// Repeat each register's generator or its inverse to create the effect
+fn generator_move_seq(arch: &Architecture, expanded_effect: &[Int<U>]) -> Vec<ArcIntern<str>> {
+    let mut move_seq = Vec::new();
+
+    for (register, &amt) in arch.registers().iter().zip(expanded_effect) {
+        let order = register.order();
+
+        let (alg, mut times) = if amt > order / Int::<U>::from(2_u32) {
+            let mut inverse = register.algorithm().clone();
+            inverse.exponentiate(-Int::<I>::one());
+            (inverse, order - amt)
+        } else {
+            (register.algorithm().clone(), amt)
+        };
+
+        while !times.is_zero() {
+            move_seq.extend(alg.move_seq_iter().cloned());
+            times = times - Int::<U>::one();
+        }
+    }
+
+    move_seq
+}
+
+/// Greedily build the effect out of the algorithms in the decoding table. Returns `None` if the table can't create the effect, which can only happen when the table isn't seeded with the register generators: it may be empty, or the greedy choices may end up going around in circles.
+fn table_move_seq(
+    table: &DecodingTable,
+    mut expanded_effect: Vec<Int<U>>,
+) -> Option<Vec<ArcIntern<str>>> {
+    let mut move_seq = Vec::new();
+
+    if table.iter().next().is_none() {
+        return expanded_effect.iter().all(|v| v.is_zero()).then_some(move_seq);
+    }
+
     let orders = table.orders();
 
+    // The choice of algorithm only depends on what is left to do, so seeing the same remainder twice means that the loop would never end
+    let mut seen = BTreeSet::new();
+
     while expanded_effect.iter().any(|v| !v.is_zero()) {
+        if !seen.insert(expanded_effect.clone()) {
+            return None;
+        }
+
         let (true_effect, alg) = table.closest_alg(&expanded_effect);
 
         expanded_effect
@@ -133,7 +197,7 @@ pub fn new_from_effect(arch: &Architecture, effect: Vec<(usize, Int<U>)>) -> Alg
         move_seq.extend_from_slice(alg);
     }
 
-    Algorithm::new_from_move_seq(arch.group_arc(), move_seq).unwrap()
+    Some(move_seq)
 }
 
 /// Calculate the order of every cycle of facelets created by seeing this `Algorithm` instance as a register generator.
@@ -141,16 +205,33 @@ pub fn new_from_effect(arch: &Architecture, effect: Vec<(usize, Int<U>)>) -> Alg
 /// Returns a list of chromatic orders where the index is the facelet.
 pub fn chromatic_orders_by_facelets(alg: &Algorithm) -> Vec<Int<U>> {
     let mut out = vec![Int::one(); alg.group().facelet_count()];
-
-    alg.permutation().cycles().iter().for_each(|cycle| {
-        let chromatic_order = length_of_substring_that_this_string_is_n_repeated_copies_of(
-            cycle.iter().map(|&idx| &*alg.group().facelet_colors()[idx]),
-        );
+    let colors = alg.group().facelet_colors();
+
+    // Symmetric puzzles have many cycles with the same sequence of colors, so each sequence is only measured once. The colors are interned, so the sequences are hashed and compared by pointer, while measuring compares the text of each color and allocates its own buffer.
+    let mut by_colors = HashMap::<Vec<&ArcIntern<str>>, Int<U>>::new();
+    // Reused between cycles so that only sequences that haven't been seen before are allocated
+    let mut cycle_colors = Vec::new();
+
+    for cycle in alg.permutation().cycles() {
+        cycle_colors.clear();
+        cycle_colors.extend(cycle.iter().map(|&idx| &colors[idx]));
+
+        let chromatic_order = match by_colors.get(&cycle_colors) {
+            Some(&chromatic_order) => chromatic_order,
+            None => {
+                let chromatic_order =
+                    Int::from(length_of_substring_that_this_string_is_n_repeated_copies_of(
+                        cycle_colors.iter().map(|color| &***color),
+                    ));
+                by_colors.insert(cycle_colors.clone(), chromatic_order);
+                chromatic_order
+            }
+        };
 
         for &facelet in cycle {
-            out[facelet] = Int::from(chromatic_order);
+            out[facelet] = chromatic_order;
         }
-    });
+    }
 
     out
 }
@@ -192,9 +273,12 @@ impl CycleGenerator {
     }
 
     /// Find a collection of facelets that allow decoding the register and that allow determining whether the register is solved
-    #[allow(clippy::missing_panics_doc)]
+    ///
+    /// # Panics
+    ///
+    /// Panics if every cycle of the register is shared with another register, since there would be no facelets to decode it from. `Architecture::validate` reports this as `ArchValidationError::TrivialRegister`.
     pub fn signature_facelets(&self) -> Facelets {
-        // This will never fail when `remainder_mod` is the order.
+        // This will never fail when `remainder_mod` is the order, unless all of the cycles are shared
         self.signature_facelets_mod(self.order()).unwrap()
     }
 
@@ -203,6 +287,11 @@ impl CycleGenerator {
     /// With some registers, you can decode cycles individually and pick out information about the register modulo some number. This will attempt to do so for a given remainder to target. It will return `None` if it's impossible to decode the given modulus from the register.
     #[allow(clippy::missing_panics_doc)]
     pub fn signature_facelets_mod(&self, remainder_mod: Int<U>) -> Option<Facelets> {
+        if self.unshared_cycles().is_empty() {
+            // Every facelet is also moved by another register so none of them can tell us anything
+            return None;
+        }
+
         let mut cycles_with_extras = vec![];
 
         // Create a list of all cycles
@@ -382,8 +471,73 @@ impl DecodingTable {
     pub(crate) fn orders(&self) -> &[Int<U>] {
         &self.orders
     }
+
+    /// Iterate over every entry of the table as the cycle combination achieved paired with the algorithm that achieves it, ordered lexicographically by the cycle combination.
+    pub fn iter(&self) -> impl Iterator<Item = (&[Int<U>], &[ArcIntern<str>])> {
+        self.table
+            .iter()
+            .map(|(achieves, alg)| (achieves.as_slice(), alg.as_slice()))
+    }
+}
+
+/// A reason that the registers of an architecture cannot be decoded reliably
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ArchValidationError {
+    /// The register has no facelets that are unshared with other registers, so it can never hold a value other than zero
+    TrivialRegister { register: usize },
+    /// A facelet used to decode `register` is moved by `other_register` without being recorded as shared
+    SignatureFaceletInterference {
+        register: usize,
+        other_register: usize,
+        facelet: usize,
+    },
+    /// Decoding the register's own generator didn't give one
+    DecodeMismatch {
+        register: usize,
+        expected: Int<U>,
+        found: Option<Int<U>>,
+    },
+}
+
+impl Display for ArchValidationError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ArchValidationError::TrivialRegister { register } => {
+                write!(
+                    f,
+                    "Register {register} shares all of its cycles with other registers, so it has no facelets to be decoded from"
+                )
+            }
+            ArchValidationError::SignatureFaceletInterference {
+                register,
+                other_register,
+                facelet,
+            } => write!(
+                f,
+                "Facelet {facelet} is used to decode register {register} but is moved by register {other_register}"
+            ),
+            ArchValidationError::DecodeMismatch {
+                register,
+                expected,
+                found: Some(found),
+            } => write!(
+                f,
+                "Register {register} decodes its own generator as {found} instead of {expected}"
+            ),
+            ArchValidationError::DecodeMismatch {
+                register,
+                expected,
+                found: None,
+            } => write!(
+                f,
+                "Register {register} fails to decode its own generator, which should be {expected}"
+            ),
+        }
+    }
 }
 
+impl Error for ArchValidationError {}
+
 /// An architecture of a `PermutationGroup`
 #[derive(Debug, Clone)]
 pub struct Architecture {
@@ -391,6 +545,7 @@ pub struct Architecture {
     registers: Vec<CycleGenerator>,
     shared_facelets: Vec<usize>,
     optimized_table: Option<Cow<'static, [u8]>>,
+    generator_seeds: bool,
     decoded_table: OnceLock<DecodingTable>,
 }
 
@@ -408,6 +563,7 @@ impl Architecture {
             registers,
             shared_facelets,
             optimized_table: None,
+            generator_seeds: true,
             decoded_table: OnceLock::new(),
         }
     }
@@ -419,6 +575,17 @@ impl Architecture {
         self.optimized_table = Some(optimized_table);
     }
 
+    /// Set whether the decoding table is seeded with each register's generator and its inverse before the optimized entries are added. This is on by default; turning it off makes the table contain only the entries of the optimized table, which is useful for measuring how much the optimized table covers on its own.
+    ///
+    /// Without the seeds, the decoding table may be empty or unable to create some effects, in which case `new_from_effect` repeats the generators instead of looking algorithms up.
+    pub fn set_generator_seeds(&mut self, generator_seeds: bool) {
+        if self.generator_seeds != generator_seeds {
+            self.generator_seeds = generator_seeds;
+            // The table has to be rebuilt if it was already created with the old setting
+            self.decoded_table = OnceLock::new();
+        }
+    }
+
     /// Retrieve a table of optimized algorithms by how they affect each cycle type.
     pub fn decoding_table(&self) -> &DecodingTable {
         self.decoded_table.get_or_init(|| {
@@ -453,7 +620,13 @@ impl Architecture {
                 }
             };
 
-            for item in self.registers().iter().flat_map(|register| {
+            let seeds = if self.generator_seeds {
+                self.registers()
+            } else {
+                &[]
+            };
+
+            for item in seeds.iter().flat_map(|register| {
                 let mut inverse = register.algorithm.clone();
                 inverse.exponentiate(-Int::<I>::one());
                 [
@@ -502,6 +675,146 @@ impl Architecture {
     pub fn shared_facelets(&self) -> &[usize] {
         &self.shared_facelets
     }
+
+    /// Get the name of the piece that the facelet is a part of
+    ///
+    /// # Panics
+    ///
+    /// Panics if the facelet doesn't exist in the permutation group
+    #[must_use]
+    pub fn piece_of_facelet(&self, facelet: usize) -> ArcIntern<str> {
+        ArcIntern::clone(&self.perm_group.piece_assignments()[facelet])
+    }
+
+    /// Get every facelet that is a part of the piece, in increasing order. This is empty if there is no piece with that name.
+    #[must_use]
+    pub fn facelets_of_piece(&self, piece: &str) -> Vec<usize> {
+        self.perm_group
+            .piece_assignments()
+            .iter()
+            .enumerate()
+            .filter(|(_, v)| &***v == piece)
+            .map(|(facelet, _)| facelet)
+            .collect()
+    }
+
+    /// Iterate over every combination of register values that the decoding table reaches with a single entry, paired with the algorithm that the table uses to reach it. The combinations are ordered lexicographically.
+    pub fn reachable_states(&self) -> impl Iterator<Item = (Vec<Int<U>>, Algorithm)> + '_ {
+        self.decoding_table().iter().map(|(achieves, alg)| {
+            (
+                achieves.to_vec(),
+                Algorithm::new_from_move_seq(self.group_arc(), alg.to_vec()).unwrap(),
+            )
+        })
+    }
+
+    /// Check that the registers of the architecture are independent enough to be decoded reliably. This gives fast feedback for hand-written architectures rather than having `decode` return `None` at runtime.
+    ///
+    /// # Errors
+    ///
+    /// Returns the first problem found with the registers
+    pub fn validate(&self) -> Result<(), ArchValidationError> {
+        for (register, generator) in self.registers.iter().enumerate() {
+            if generator.order() == Int::<U>::one() {
+                return Err(ArchValidationError::TrivialRegister { register });
+            }
+
+            let signature_facelets = generator.signature_facelets();
+
+            for (other_register, other_generator) in self.registers.iter().enumerate() {
+                if other_register == register {
+                    continue;
+                }
+
+                let mapping = other_generator.algorithm.permutation().mapping();
+
+                if let Some(&facelet) = signature_facelets.facelets().iter().find(|&&facelet| {
+                    mapping.get(facelet) != facelet && !self.shared_facelets.contains(&facelet)
+                }) {
+                    return Err(ArchValidationError::SignatureFaceletInterference {
+                        register,
+                        other_register,
+                        facelet,
+                    });
+                }
+            }
+
+            let expected = Int::<U>::one() % generator.order();
+            let found = decode(
+                generator.algorithm.permutation(),
+                signature_facelets.facelets(),
+                &generator.algorithm,
+            );
+
+            if found != Some(expected) {
+                return Err(ArchValidationError::DecodeMismatch {
+                    register,
+                    expected,
+                    found,
+                });
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Describe the architecture in a human readable way. This lists every register's generator and order along with the facelets of each of its unshared cycles, followed by the shared facelets and whether an optimized table is present.
+    #[must_use]
+    pub fn describe(&self) -> String {
+        let mut out = String::new();
+
+        for (i, register) in self.registers.iter().enumerate() {
+            let generator = register.algorithm.move_seq_iter().map(|v| &**v).join(" ");
+
+            writeln!(
+                out,
+                "Register {i} has order {} and is generated by {generator}",
+                register.order()
+            )
+            .unwrap();
+
+            for cycle in register.unshared_cycles() {
+                writeln!(
+                    out,
+                    "    Cycle with chromatic order {}: facelets {}",
+                    cycle.chromatic_order(),
+                    cycle.facelet_cycle().iter().join(", ")
+                )
+                .unwrap();
+            }
+        }
+
+        if self.shared_facelets.is_empty() {
+            writeln!(out, "No facelets are shared between registers").unwrap();
+        } else {
+            writeln!(
+                out,
+                "Shared facelets: {}",
+                self.shared_facelets.iter().join(", ")
+            )
+            .unwrap();
+        }
+
+        if self.optimized_table.is_some() {
+            writeln!(out, "Has an optimized table").unwrap();
+        } else {
+            writeln!(out, "Does not have an optimized table").unwrap();
+        }
+
+        out
+    }
+
+    /// Get a report of which registers share each of the shared facelets
+    pub fn shared_facelet_report(&self) -> SharedFaceletReport {
+        shared_facelet_report(
+            &self.perm_group,
+            &self
+                .registers
+                .iter()
+                .map(|register| register.algorithm.clone())
+                .collect_vec(),
+        )
+    }
 }
 
 /// Get any presets associated with the given `PuzzleGeometry`
@@ -607,11 +920,23 @@ pub fn decode(
     permutation: &Permutation,
     facelets: &[usize],
     generator: &Algorithm,
+) -> Option<Int<U>> {
+    // Only depends on the generator, so it's calculated once rather than for every facelet
+    let chromatic_orders = chromatic_orders_by_facelets(generator);
+
+    decode_with_chromatic_orders(permutation, facelets, generator, &chromatic_orders)
+}
+
+fn decode_with_chromatic_orders(
+    permutation: &Permutation,
+    facelets: &[usize],
+    generator: &Algorithm,
+    chromatic_orders: &[Int<U>],
 ) -> Option<Int<U>> {
     chinese_remainder_theorem(facelets.iter().map(|&facelet| {
         let maps_to = permutation.mapping().get(facelet);
 
-        let chromatic_order = chromatic_orders_by_facelets(generator)[facelet];
+        let chromatic_order = chromatic_orders[facelet];
 
         if maps_to == facelet {
             return Some((Int::zero(), chromatic_order));
@@ -635,25 +960,184 @@ pub fn decode(
     }))
 }
 
+/// Decodes a register over and over as algorithms are applied to the puzzle. Rather than decoding the whole state each time, the value decoded previously is updated with the effect of each algorithm applied since, which avoids repeating the chinese remainder theorem in loops that add to a register.
+///
+/// A value is only remembered while every cycle of the generator that passes through a signature facelet is rotated like a power of the generator, both in the decoded state and in every algorithm applied since. Otherwise, pieces outside of the signature facelets could have been moved in a way that the value doesn't account for.
+#[derive(Debug, Clone)]
+pub struct IncrementalDecoder {
+    facelets: Vec<usize>,
+    generator: Algorithm,
+    chromatic_orders: Vec<Int<U>>,
+    /// The cycle of the generator through each signature facelet, starting at that facelet
+    cycles: Vec<Vec<usize>>,
+    order: Int<U>,
+    value: Option<Int<U>>,
+    // Loops usually apply the same algorithm each iteration so its effect is remembered
+    last_effect: Option<(Algorithm, Option<Int<U>>)>,
+}
+
+impl IncrementalDecoder {
+    /// Create a decoder for the register with the given generator that decodes from the given facelets. Nothing is decoded until `decode` is called.
+    #[must_use]
+    pub fn new(facelets: Vec<usize>, generator: Algorithm) -> IncrementalDecoder {
+        let chromatic_orders = chromatic_orders_by_facelets(&generator);
+        let order = lcm_iter(facelets.iter().map(|&facelet| chromatic_orders[facelet]));
+
+        let mapping = generator.permutation().mapping();
+        let cycles = facelets
+            .iter()
+            .map(|&facelet| {
+                let mut cycle = vec![facelet];
+                let mut facelet_at = mapping.get(facelet);
+
+                while facelet_at != facelet {
+                    cycle.push(facelet_at);
+                    facelet_at = mapping.get(facelet_at);
+                }
+
+                cycle
+            })
+            .collect();
+
+        IncrementalDecoder {
+            facelets,
+            generator,
+            chromatic_orders,
+            cycles,
+            order,
+            value: None,
+            last_effect: None,
+        }
+    }
+
+    /// The signature facelets that the register is decoded from
+    #[must_use]
+    pub fn facelets(&self) -> &[usize] {
+        &self.facelets
+    }
+
+    /// The generator of the register
+    #[must_use]
+    pub fn generator(&self) -> &Algorithm {
+        &self.generator
+    }
+
+    /// The value of the register as of the last algorithm applied, if it is known
+    #[must_use]
+    pub fn value(&self) -> Option<Int<U>> {
+        self.value
+    }
+
+    /// Get the value of the register, only decoding the permutation if the value isn't already known. The permutation must be the state that every algorithm applied so far has resulted in. This always agrees with `decode`.
+    pub fn decode(&mut self, permutation: &Permutation) -> Option<Int<U>> {
+        if self.value.is_some() {
+            return self.value;
+        }
+
+        // If the state is rotated like a power of the generator, the value can be remembered
+        self.value = self.rotation(permutation);
+        if self.value.is_some() {
+            return self.value;
+        }
+
+        decode_with_chromatic_orders(
+            permutation,
+            &self.facelets,
+            &self.generator,
+            &self.chromatic_orders,
+        )
+    }
+
+    /// Update the value of the register to account for the algorithm being applied to the puzzle. If the algorithm doesn't rotate the cycles of the register like a power of the generator, the value is forgotten and the next call to `decode` decodes the permutation in full.
+    pub fn apply(&mut self, alg: &Algorithm) {
+        let Some(value) = self.value else {
+            return;
+        };
+
+        let effect = match &self.last_effect {
+            Some((last, effect)) if last == alg => *effect,
+            _ => {
+                let effect = self.rotation(alg.permutation());
+                self.last_effect = Some((alg.clone(), effect));
+                effect
+            }
+        };
+
+        self.value = effect.map(|effect| (value + effect) % self.order);
+    }
+
+    /// Forget the value of the register. This must be called if the puzzle is changed other than through `apply`, for example by solving it.
+    pub fn invalidate(&mut self) {
+        self.value = None;
+    }
+
+    /// Find the power of the generator that the permutation acts like on the cycles of the register, if it does act like one
+    fn rotation(&self, permutation: &Permutation) -> Option<Int<U>> {
+        let mapping = permutation.mapping();
+
+        chinese_remainder_theorem(self.cycles.iter().map(|cycle| {
+            let by = cycle.iter().position(|&facelet| facelet == mapping.get(cycle[0]))?;
+
+            let rotates_whole_cycle = cycle
+                .iter()
+                .enumerate()
+                .all(|(i, &facelet)| mapping.get(facelet) == cycle[(i + by) % cycle.len()]);
+
+            if !rotates_whole_cycle {
+                return None;
+            }
+
+            let chromatic_order = self.chromatic_orders[cycle[0]];
+            Some((Int::<U>::from(by) % chromatic_order, chromatic_order))
+        }))
+    }
+}
+
 #[cfg(test)]
 mod tests {
 
-    use std::sync::Arc;
+    use std::{borrow::Cow, collections::HashSet, sync::Arc};
 
     use internment::ArcIntern;
 
     use puzzle_theory::{
         numbers::{Int, U},
-        permutations::{Algorithm, Permutation},
+        permutations::{Algorithm, Permutation, PermutationGroup},
         puzzle_geometry::parsing::puzzle,
     };
 
-    use crate::architectures::{
-        decode, length_of_substring_that_this_string_is_n_repeated_copies_of, with_presets,
+    use crate::{
+        Facelets, FaceletsError,
+        architectures::{
+            ArchValidationError, IncrementalDecoder, chromatic_orders_by_facelets, decode,
+            effect_cost, length_of_substring_that_this_string_is_n_repeated_copies_of,
+            new_from_effect, with_presets,
+        },
+        table_encoding,
     };
 
     use super::Architecture;
 
+    /// An optimized table containing only `U2`
+    fn u2_table() -> Cow<'static, [u8]> {
+        Cow::Owned(table_encoding::encode_table(&[vec![ArcIntern::from("U2")]]).unwrap().0)
+    }
+
+    /// An architecture on the 3x3 whose registers are generated by `U` and `D`
+    fn u_d_architecture() -> (Arc<PermutationGroup>, Architecture) {
+        let perm_group = puzzle("3x3").permutation_group();
+
+        let arch = Architecture::new(
+            Arc::clone(&perm_group),
+            ["U", "D"]
+                .iter()
+                .map(|alg| Algorithm::parse_from_string(Arc::clone(&perm_group), alg).unwrap())
+                .collect(),
+        );
+
+        (perm_group, arch)
+    }
+
     #[test]
     fn three_by_three() {
         let cube_def = with_presets(puzzle("3x3").permutation_group());
@@ -689,9 +1173,308 @@ mod tests {
             for (register, expected) in arch.registers.iter().zip(expected.iter()) {
                 assert_eq!(register.order(), Int::<U>::from(*expected));
             }
+
+            arch.validate().unwrap();
+        }
+    }
+
+    #[test]
+    fn validate_trivial_register() {
+        let perm_group = puzzle("3x3").permutation_group();
+
+        let arch = Architecture::new(
+            Arc::clone(&perm_group),
+            ["U", "U2"]
+                .iter()
+                .map(|alg| Algorithm::parse_from_string(Arc::clone(&perm_group), alg).unwrap())
+                .collect(),
+        );
+
+        assert_eq!(
+            arch.validate(),
+            Err(ArchValidationError::TrivialRegister { register: 0 })
+        );
+
+        assert!(
+            arch.registers()[0]
+                .signature_facelets_mod(Int::<U>::one())
+                .is_none()
+        );
+    }
+
+    #[test]
+    fn decoding_table_iter() {
+        let (_, arch) = u_d_architecture();
+
+        let table = arch.decoding_table();
+
+        assert_ne!(table.iter().count(), 0);
+
+        for (achieves, alg) in table.iter() {
+            assert_eq!(table.closest_alg(achieves), (achieves, alg));
+        }
+    }
+
+    #[test]
+    fn signature_facelets_compare() {
+        let (_, arch) = u_d_architecture();
+
+        let u = arch.registers()[0].signature_facelets();
+        let d = arch.registers()[1].signature_facelets();
+
+        assert_eq!(u, arch.registers()[0].signature_facelets());
+        assert_ne!(u, d);
+
+        let deduplicated = [u.clone(), d.clone(), u.clone()]
+            .into_iter()
+            .collect::<HashSet<_>>();
+        assert_eq!(deduplicated.len(), 2);
+
+        for &facelet in u.facelets() {
+            assert!(u.contains_facelet(facelet));
+            assert!(!d.contains_facelet(facelet));
+        }
+    }
+
+    #[test]
+    fn facelets_new_checked() {
+        let perm_group = puzzle("3x3").permutation_group();
+        let count = perm_group.facelet_count();
+
+        let four = Int::<U>::from(4_u32);
+
+        let facelets = Facelets::new_checked(vec![0, count - 1], vec![], four, &perm_group);
+        assert_eq!(facelets.unwrap().facelets(), &[0, count - 1]);
+
+        assert_eq!(
+            Facelets::new_checked(vec![0, count], vec![], four, &perm_group),
+            Err(FaceletsError::FaceletOutOfRange {
+                facelet: count,
+                facelet_count: count,
+            })
+        );
+
+        assert_eq!(
+            Facelets::new_checked(vec![0], vec![], Int::zero(), &perm_group),
+            Err(FaceletsError::ZeroOrder)
+        );
+    }
+
+    #[test]
+    fn describe() {
+        let (_, arch) = u_d_architecture();
+
+        let description = arch.describe();
+
+        assert!(description.contains("Register 0 has order 4 and is generated by U\n"));
+        assert!(description.contains("Register 1 has order 4 and is generated by D\n"));
+        assert!(description.contains("No facelets are shared between registers"));
+        assert!(description.ends_with("Does not have an optimized table\n"));
+        assert_eq!(
+            description.matches("Cycle with chromatic order").count(),
+            arch.registers()
+                .iter()
+                .map(|register| register.unshared_cycles().len())
+                .sum::<usize>()
+        );
+    }
+
+    #[test]
+    fn without_generator_seeds() {
+        let (_, mut arch) = u_d_architecture();
+
+        // U, U', D, and D'
+        assert_eq!(arch.decoding_table().iter().count(), 4);
+
+        arch.set_generator_seeds(false);
+        assert_eq!(arch.decoding_table().iter().count(), 0);
+
+        // U2 is its own inverse
+        arch.set_optimized_table(u2_table());
+        assert_eq!(arch.decoding_table().iter().count(), 1);
+
+        arch.set_generator_seeds(true);
+        assert_eq!(arch.decoding_table().iter().count(), 5);
+    }
+
+    #[test]
+    fn reachable_states_decode() {
+        let (_, arch) = u_d_architecture();
+
+        assert_ne!(arch.reachable_states().count(), 0);
+
+        for (values, alg) in arch.reachable_states() {
+            for (register, value) in arch.registers().iter().zip(values) {
+                assert_eq!(
+                    decode(
+                        alg.permutation(),
+                        register.signature_facelets().facelets(),
+                        &register.algorithm
+                    ),
+                    Some(value)
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn incremental_decoding() {
+        let (perm_group, arch) = u_d_architecture();
+
+        let register = &arch.registers()[0];
+        let facelets = register.signature_facelets().facelets().to_vec();
+
+        let mut decoder = IncrementalDecoder::new(facelets.clone(), register.algorithm.clone());
+        let mut state = Permutation::identity();
+
+        assert_eq!(decoder.value(), None);
+        assert_eq!(decoder.decode(&state), Some(Int::zero()));
+
+        for alg in ["U", "U2", "D", "U'", "U"] {
+            let alg = Algorithm::parse_from_string(Arc::clone(&perm_group), alg).unwrap();
+
+            state.compose_into(alg.permutation());
+            decoder.apply(&alg);
+
+            assert_eq!(
+                decoder.value(),
+                decode(&state, &facelets, &register.algorithm)
+            );
+        }
+
+        // R doesn't act like a power of U so the value has to be forgotten
+        let r = Algorithm::parse_from_string(Arc::clone(&perm_group), "R").unwrap();
+        state.compose_into(r.permutation());
+        decoder.apply(&r);
+
+        assert_eq!(decoder.value(), None);
+        assert_eq!(
+            decoder.decode(&state),
+            decode(&state, &facelets, &register.algorithm)
+        );
+        // The U layer isn't a power of U anymore, so the value can't be carried forward
+        assert_eq!(decoder.value(), None);
+
+        decoder.invalidate();
+        assert_eq!(decoder.value(), None);
+    }
+
+    #[test]
+    fn pieces_and_facelets() {
+        let (perm_group, arch) = u_d_architecture();
+
+        for facelet in 0..perm_group.facelet_count() {
+            let piece = arch.piece_of_facelet(facelet);
+            let facelets = arch.facelets_of_piece(&piece);
+
+            assert!(facelets.contains(&facelet));
+            assert!(facelets.iter().all(|&v| arch.piece_of_facelet(v) == piece));
+        }
+
+        assert!(arch.facelets_of_piece("not a piece").is_empty());
+    }
+
+    #[test]
+    fn chromatic_orders() {
+        let perm_group = puzzle("3x3").permutation_group();
+
+        let u = Algorithm::parse_from_string(Arc::clone(&perm_group), "U").unwrap();
+        let orders = chromatic_orders_by_facelets(&u);
+
+        // The stickers on the U face cycle through the same color, while the stickers around it cycle through four
+        assert_eq!(orders.iter().filter(|&&v| v == Int::<U>::from(4_u32)).count(), 12);
+        assert!(orders.iter().all(|&v| v == Int::<U>::one() || v == Int::<U>::from(4_u32)));
+
+        // Many of the cycles of `R U` share their colors with other cycles, and reusing their orders must give the same result as measuring every cycle
+        let r_u = Algorithm::parse_from_string(Arc::clone(&perm_group), "R U").unwrap();
+        let orders = chromatic_orders_by_facelets(&r_u);
+        let colors = perm_group.facelet_colors();
+
+        for cycle in r_u.permutation().cycles() {
+            let measured = Int::<U>::from(
+                length_of_substring_that_this_string_is_n_repeated_copies_of(
+                    cycle.iter().map(|&idx| &*colors[idx]),
+                ),
+            );
+
+            assert!(cycle.iter().all(|&facelet| orders[facelet] == measured));
+        }
+    }
+
+    #[test]
+    fn effect_cost_matches_algorithm() {
+        let (_, arch) = u_d_architecture();
+
+        assert_eq!(effect_cost(&arch, &[]), 0);
+        assert_eq!(effect_cost(&arch, &[(0, Int::<U>::from(4_u64))]), 0);
+
+        for effect in [
+            vec![(0, Int::<U>::one())],
+            vec![(1, Int::<U>::from(3_u64))],
+            vec![(0, Int::<U>::from(2_u64)), (1, Int::<U>::one())],
+        ] {
+            assert_eq!(
+                effect_cost(&arch, &effect),
+                new_from_effect(&arch, effect).move_seq_iter().count()
+            );
         }
     }
 
+    #[test]
+    fn new_from_effect_without_table() {
+        let (perm_group, mut arch) = u_d_architecture();
+        arch.set_generator_seeds(false);
+
+        assert_eq!(new_from_effect(&arch, vec![]).permutation(), &Permutation::identity());
+
+        let u_prime = Algorithm::parse_from_string(Arc::clone(&perm_group), "U'").unwrap();
+        assert_eq!(
+            new_from_effect(&arch, vec![(0, Int::<U>::from(3_u64))]).permutation(),
+            u_prime.permutation()
+        );
+
+        let d2 = Algorithm::parse_from_string(Arc::clone(&perm_group), "D2").unwrap();
+        assert_eq!(
+            new_from_effect(&arch, vec![(1, Int::<U>::from(2_u64))]).permutation(),
+            d2.permutation()
+        );
+    }
+
+    #[test]
+    fn new_from_effect_with_partial_table() {
+        let (perm_group, mut arch) = u_d_architecture();
+        arch.set_generator_seeds(false);
+        arch.set_optimized_table(u2_table());
+
+        // U2 can't create a single U, so the generators have to be used
+        let u = Algorithm::parse_from_string(Arc::clone(&perm_group), "U").unwrap();
+        assert_eq!(
+            new_from_effect(&arch, vec![(0, Int::<U>::one())]).permutation(),
+            u.permutation()
+        );
+
+        let u2_d = Algorithm::parse_from_string(Arc::clone(&perm_group), "U2 D").unwrap();
+        assert_eq!(
+            new_from_effect(&arch, vec![(0, Int::<U>::from(2_u64)), (1, Int::<U>::one())])
+                .permutation(),
+            u2_d.permutation()
+        );
+
+        let u2 = Algorithm::parse_from_string(Arc::clone(&perm_group), "U2").unwrap();
+        assert_eq!(
+            new_from_effect(&arch, vec![(0, Int::<U>::from(2_u64))]).permutation(),
+            u2.permutation()
+        );
+    }
+
+    #[test]
+    fn decrement_uses_inverse_generator() {
+        let (_, arch) = u_d_architecture();
+
+        // Adding `order - 1` should be a single `U'` rather than three `U`s
+        assert_eq!(effect_cost(&arch, &[(0, Int::<U>::from(3_u64))]), 1);
+    }
+
     #[test]
     fn length_of_substring_whatever() {
         assert_eq!(
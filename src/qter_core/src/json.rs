@@ -0,0 +1,611 @@
+use std::{fmt::Display, str::FromStr, sync::Arc};
+
+use internment::ArcIntern;
+use itertools::Itertools;
+use puzzle_theory::{
+    numbers::{Int, U},
+    permutations::{Algorithm, PermutationGroup},
+    puzzle_geometry::PuzzleGeometry,
+    span::{File, Span},
+};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    ByPuzzleType, Facelets, FaceletsError, Halt, Input, Instruction, Print, Program, PuzzleIdx,
+    RegisterMetadata, RepeatUntil, SolvedGoto, TheoreticalIdx,
+};
+
+/// An error encountered while loading a `Program` from JSON
+#[derive(Debug)]
+pub enum ProgramJsonError {
+    Json(serde_json::Error),
+    /// A puzzle definition that couldn't be parsed
+    UnknownPuzzle(String),
+    /// An algorithm containing a move that its puzzle doesn't have
+    InvalidAlgorithm(String),
+    /// A register order, amount, or modulus that isn't a non-negative integer
+    InvalidNumber(String),
+    /// An instruction referring to a puzzle or theoretical register that doesn't exist
+    InvalidIndex(usize),
+    /// A jump to an instruction past the end of the program
+    InvalidInstructionIdx(usize),
+    /// Facelets that don't exist on their puzzle, or that decode modulo zero
+    InvalidFacelets(FaceletsError),
+}
+
+impl Display for ProgramJsonError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ProgramJsonError::Json(e) => e.fmt(f),
+            ProgramJsonError::UnknownPuzzle(name) => write!(f, "Unknown puzzle `{name}`"),
+            ProgramJsonError::InvalidAlgorithm(alg) => write!(f, "Invalid algorithm `{alg}`"),
+            ProgramJsonError::InvalidNumber(num) => write!(f, "Invalid number `{num}`"),
+            ProgramJsonError::InvalidIndex(idx) => {
+                write!(f, "Reference to a nonexistent puzzle or register {idx}")
+            }
+            ProgramJsonError::InvalidInstructionIdx(idx) => {
+                write!(f, "Jump to a nonexistent instruction {idx}")
+            }
+            ProgramJsonError::InvalidFacelets(e) => e.fmt(f),
+        }
+    }
+}
+
+impl std::error::Error for ProgramJsonError {}
+
+impl From<serde_json::Error> for ProgramJsonError {
+    fn from(value: serde_json::Error) -> Self {
+        ProgramJsonError::Json(value)
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+struct ProgramRepr {
+    /// The orders of the theoretical registers as decimal strings
+    theoretical: Vec<String>,
+    /// The definitions of the puzzles, for example `3x3`
+    puzzles: Vec<String>,
+    /// Missing from programs serialized before register metadata was recorded
+    #[serde(default)]
+    registers: Vec<RegisterMetadataRepr>,
+    instructions: Vec<InstructionRepr>,
+}
+
+/// A declared register, which refers to either a theoretical register or a puzzle by index
+#[derive(Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "kebab-case")]
+enum RegisterMetadataRepr {
+    Theoretical {
+        name: String,
+        order: String,
+        theoretical: usize,
+    },
+    Puzzle {
+        name: String,
+        order: String,
+        puzzle: usize,
+    },
+}
+
+#[derive(Serialize, Deserialize)]
+struct FaceletsRepr {
+    facelets: Vec<usize>,
+    pieces: Vec<String>,
+    order: String,
+}
+
+/// A register on a puzzle, given by its generator and the facelets needed to decode it
+#[derive(Serialize, Deserialize)]
+struct PuzzleRegisterRepr {
+    puzzle: usize,
+    algorithm: String,
+    facelets: FaceletsRepr,
+}
+
+/// Instructions are split into theoretical and puzzle variants the same way that `ByPuzzleType` splits them. Algorithms are written as space separated move sequences.
+#[derive(Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "kebab-case")]
+enum InstructionRepr {
+    Goto {
+        instruction_idx: usize,
+    },
+    SolvedGotoTheoretical {
+        instruction_idx: usize,
+        theoretical: usize,
+        modulus: Option<String>,
+    },
+    SolvedGoto {
+        instruction_idx: usize,
+        puzzle: usize,
+        facelets: FaceletsRepr,
+    },
+    InputTheoretical {
+        message: String,
+        theoretical: usize,
+    },
+    Input {
+        message: String,
+        register: PuzzleRegisterRepr,
+    },
+    HaltTheoretical {
+        message: String,
+        theoretical: Option<usize>,
+    },
+    Halt {
+        message: String,
+        register: Option<PuzzleRegisterRepr>,
+    },
+    PrintTheoretical {
+        message: String,
+        theoretical: Option<usize>,
+    },
+    Print {
+        message: String,
+        register: Option<PuzzleRegisterRepr>,
+    },
+    AddTheoretical {
+        theoretical: usize,
+        amount: String,
+    },
+    PerformAlgorithm {
+        puzzle: usize,
+        algorithm: String,
+    },
+    SolveTheoretical {
+        theoretical: usize,
+    },
+    Solve {
+        puzzle: usize,
+    },
+    RepeatUntil {
+        puzzle: usize,
+        facelets: FaceletsRepr,
+        algorithm: String,
+    },
+}
+
+fn alg_to_repr(alg: &Algorithm) -> String {
+    alg.move_seq_iter().map(|v| &**v).join(" ")
+}
+
+fn facelets_to_repr(facelets: &Facelets) -> FaceletsRepr {
+    FaceletsRepr {
+        facelets: facelets.facelets().to_vec(),
+        pieces: facelets.pieces().iter().map(|v| (**v).to_owned()).collect(),
+        order: facelets.order().to_string(),
+    }
+}
+
+fn register_to_repr(
+    puzzle_idx: PuzzleIdx,
+    alg: &Algorithm,
+    facelets: &Facelets,
+) -> PuzzleRegisterRepr {
+    PuzzleRegisterRepr {
+        puzzle: puzzle_idx.0,
+        algorithm: alg_to_repr(alg),
+        facelets: facelets_to_repr(facelets),
+    }
+}
+
+fn instruction_to_repr(instruction: &Instruction) -> InstructionRepr {
+    match instruction {
+        Instruction::Goto { instruction_idx } => InstructionRepr::Goto {
+            instruction_idx: *instruction_idx,
+        },
+        Instruction::SolvedGoto(ByPuzzleType::Theoretical((solved_goto, idx, modulus))) => {
+            InstructionRepr::SolvedGotoTheoretical {
+                instruction_idx: solved_goto.instruction_idx,
+                theoretical: idx.0,
+                modulus: modulus.map(|v| v.to_string()),
+            }
+        }
+        Instruction::SolvedGoto(ByPuzzleType::Puzzle((solved_goto, idx, facelets))) => {
+            InstructionRepr::SolvedGoto {
+                instruction_idx: solved_goto.instruction_idx,
+                puzzle: idx.0,
+                facelets: facelets_to_repr(facelets),
+            }
+        }
+        Instruction::Input(ByPuzzleType::Theoretical((input, idx))) => {
+            InstructionRepr::InputTheoretical {
+                message: input.message.clone(),
+                theoretical: idx.0,
+            }
+        }
+        Instruction::Input(ByPuzzleType::Puzzle((input, idx, alg, facelets))) => {
+            InstructionRepr::Input {
+                message: input.message.clone(),
+                register: register_to_repr(*idx, alg, facelets),
+            }
+        }
+        Instruction::Halt(ByPuzzleType::Theoretical((halt, idx))) => {
+            InstructionRepr::HaltTheoretical {
+                message: halt.message.clone(),
+                theoretical: idx.map(|v| v.0),
+            }
+        }
+        Instruction::Halt(ByPuzzleType::Puzzle((halt, register))) => InstructionRepr::Halt {
+            message: halt.message.clone(),
+            register: register
+                .as_ref()
+                .map(|(idx, alg, facelets)| register_to_repr(*idx, alg, facelets)),
+        },
+        Instruction::Print(ByPuzzleType::Theoretical((print, idx))) => {
+            InstructionRepr::PrintTheoretical {
+                message: print.message.clone(),
+                theoretical: idx.map(|v| v.0),
+            }
+        }
+        Instruction::Print(ByPuzzleType::Puzzle((print, register))) => InstructionRepr::Print {
+            message: print.message.clone(),
+            register: register
+                .as_ref()
+                .map(|(idx, alg, facelets)| register_to_repr(*idx, alg, facelets)),
+        },
+        Instruction::PerformAlgorithm(ByPuzzleType::Theoretical((idx, amount))) => {
+            InstructionRepr::AddTheoretical {
+                theoretical: idx.0,
+                amount: amount.to_string(),
+            }
+        }
+        Instruction::PerformAlgorithm(ByPuzzleType::Puzzle((idx, alg))) => {
+            InstructionRepr::PerformAlgorithm {
+                puzzle: idx.0,
+                algorithm: alg_to_repr(alg),
+            }
+        }
+        Instruction::Solve(ByPuzzleType::Theoretical(idx)) => {
+            InstructionRepr::SolveTheoretical { theoretical: idx.0 }
+        }
+        Instruction::Solve(ByPuzzleType::Puzzle(idx)) => InstructionRepr::Solve { puzzle: idx.0 },
+        Instruction::RepeatUntil(ByPuzzleType::Theoretical(v)) => match *v {},
+        Instruction::RepeatUntil(ByPuzzleType::Puzzle(repeat_until)) => {
+            InstructionRepr::RepeatUntil {
+                puzzle: repeat_until.puzzle_idx.0,
+                facelets: facelets_to_repr(&repeat_until.facelets),
+                algorithm: alg_to_repr(&repeat_until.alg),
+            }
+        }
+    }
+}
+
+/// Converts the references in an `InstructionRepr` back into the puzzle groups and theoretical registers that they index
+struct Resolver<'a> {
+    theoretical_count: usize,
+    instruction_count: usize,
+    puzzles: &'a [Arc<PermutationGroup>],
+}
+
+impl Resolver<'_> {
+    fn theoretical(&self, idx: usize) -> Result<TheoreticalIdx, ProgramJsonError> {
+        if idx < self.theoretical_count {
+            Ok(TheoreticalIdx(idx))
+        } else {
+            Err(ProgramJsonError::InvalidIndex(idx))
+        }
+    }
+
+    fn puzzle(&self, idx: usize) -> Result<(PuzzleIdx, &Arc<PermutationGroup>), ProgramJsonError> {
+        match self.puzzles.get(idx) {
+            Some(group) => Ok((PuzzleIdx(idx), group)),
+            None => Err(ProgramJsonError::InvalidIndex(idx)),
+        }
+    }
+
+    fn instruction_idx(&self, idx: usize) -> Result<usize, ProgramJsonError> {
+        if idx < self.instruction_count {
+            Ok(idx)
+        } else {
+            Err(ProgramJsonError::InvalidInstructionIdx(idx))
+        }
+    }
+
+    fn facelets(
+        &self,
+        puzzle: usize,
+        facelets: FaceletsRepr,
+    ) -> Result<Facelets, ProgramJsonError> {
+        let (_, group) = self.puzzle(puzzle)?;
+
+        Facelets::new_checked(
+            facelets.facelets,
+            facelets.pieces.iter().map(|v| ArcIntern::from(&**v)).collect(),
+            number_from_repr(&facelets.order)?,
+            group,
+        )
+        .map_err(ProgramJsonError::InvalidFacelets)
+    }
+
+    fn alg(&self, puzzle: usize, alg: &str) -> Result<Algorithm, ProgramJsonError> {
+        let (_, group) = self.puzzle(puzzle)?;
+
+        Algorithm::new_from_move_seq(
+            Arc::clone(group),
+            alg.split_whitespace().map(ArcIntern::from).collect(),
+        )
+        .map_err(|_| ProgramJsonError::InvalidAlgorithm(alg.to_owned()))
+    }
+
+    fn register(
+        &self,
+        register: PuzzleRegisterRepr,
+    ) -> Result<(PuzzleIdx, Algorithm, Facelets), ProgramJsonError> {
+        let (idx, _) = self.puzzle(register.puzzle)?;
+
+        Ok((
+            idx,
+            self.alg(register.puzzle, &register.algorithm)?,
+            self.facelets(register.puzzle, register.facelets)?,
+        ))
+    }
+}
+
+fn number_from_repr(number: &str) -> Result<Int<U>, ProgramJsonError> {
+    Int::<U>::from_str(number).map_err(|_| ProgramJsonError::InvalidNumber(number.to_owned()))
+}
+
+fn instruction_from_repr(
+    instruction: InstructionRepr,
+    r: &Resolver<'_>,
+) -> Result<Instruction, ProgramJsonError> {
+    Ok(match instruction {
+        InstructionRepr::Goto { instruction_idx } => Instruction::Goto {
+            instruction_idx: r.instruction_idx(instruction_idx)?,
+        },
+        InstructionRepr::SolvedGotoTheoretical {
+            instruction_idx,
+            theoretical,
+            modulus,
+        } => Instruction::SolvedGoto(ByPuzzleType::Theoretical((
+            SolvedGoto {
+                instruction_idx: r.instruction_idx(instruction_idx)?,
+            },
+            r.theoretical(theoretical)?,
+            modulus.as_deref().map(number_from_repr).transpose()?,
+        ))),
+        InstructionRepr::SolvedGoto {
+            instruction_idx,
+            puzzle,
+            facelets,
+        } => Instruction::SolvedGoto(ByPuzzleType::Puzzle((
+            SolvedGoto {
+                instruction_idx: r.instruction_idx(instruction_idx)?,
+            },
+            r.puzzle(puzzle)?.0,
+            r.facelets(puzzle, facelets)?,
+        ))),
+        InstructionRepr::InputTheoretical {
+            message,
+            theoretical,
+        } => Instruction::Input(ByPuzzleType::Theoretical((
+            Input { message },
+            r.theoretical(theoretical)?,
+        ))),
+        InstructionRepr::Input { message, register } => {
+            let (idx, alg, facelets) = r.register(register)?;
+            Instruction::Input(ByPuzzleType::Puzzle((Input { message }, idx, alg, facelets)))
+        }
+        InstructionRepr::HaltTheoretical {
+            message,
+            theoretical,
+        } => Instruction::Halt(ByPuzzleType::Theoretical((
+            Halt { message },
+            theoretical.map(|v| r.theoretical(v)).transpose()?,
+        ))),
+        InstructionRepr::Halt { message, register } => Instruction::Halt(ByPuzzleType::Puzzle((
+            Halt { message },
+            register.map(|v| r.register(v)).transpose()?,
+        ))),
+        InstructionRepr::PrintTheoretical {
+            message,
+            theoretical,
+        } => Instruction::Print(ByPuzzleType::Theoretical((
+            Print { message },
+            theoretical.map(|v| r.theoretical(v)).transpose()?,
+        ))),
+        InstructionRepr::Print { message, register } => {
+            Instruction::Print(ByPuzzleType::Puzzle((
+                Print { message },
+                register.map(|v| r.register(v)).transpose()?,
+            )))
+        }
+        InstructionRepr::AddTheoretical {
+            theoretical,
+            amount,
+        } => Instruction::PerformAlgorithm(ByPuzzleType::Theoretical((
+            r.theoretical(theoretical)?,
+            number_from_repr(&amount)?,
+        ))),
+        InstructionRepr::PerformAlgorithm { puzzle, algorithm } => Instruction::PerformAlgorithm(
+            ByPuzzleType::Puzzle((r.puzzle(puzzle)?.0, r.alg(puzzle, &algorithm)?)),
+        ),
+        InstructionRepr::SolveTheoretical { theoretical } => {
+            Instruction::Solve(ByPuzzleType::Theoretical(r.theoretical(theoretical)?))
+        }
+        InstructionRepr::Solve { puzzle } => {
+            Instruction::Solve(ByPuzzleType::Puzzle(r.puzzle(puzzle)?.0))
+        }
+        InstructionRepr::RepeatUntil {
+            puzzle,
+            facelets,
+            algorithm,
+        } => Instruction::RepeatUntil(ByPuzzleType::Puzzle(RepeatUntil {
+            puzzle_idx: r.puzzle(puzzle)?.0,
+            facelets: r.facelets(puzzle, facelets)?,
+            alg: r.alg(puzzle, &algorithm)?,
+        })),
+    })
+}
+
+/// A span covering an entire string, for values that don't come from QAT source code
+fn whole_span(name: &str, contents: &str) -> Span {
+    let file = File::new(ArcIntern::from(name), ArcIntern::from(contents));
+    Span::new(file, 0, contents.len())
+}
+
+impl Program {
+    /// Serialize the program to JSON so that it can be loaded by tools that can't parse QAT or Q. Puzzles are written as their definitions, numbers as decimal strings, and algorithms as space separated moves.
+    #[must_use]
+    pub fn to_json(&self) -> String {
+        let repr = ProgramRepr {
+            theoretical: self.theoretical.iter().map(|v| (**v).to_string()).collect(),
+            puzzles: self
+                .puzzles
+                .iter()
+                .map(|v| v.span().slice().to_string())
+                .collect(),
+            registers: self
+                .register_metadata
+                .iter()
+                .map(|reg| match reg.storage {
+                    ByPuzzleType::Theoretical(idx) => RegisterMetadataRepr::Theoretical {
+                        name: (*reg.name).to_owned(),
+                        order: reg.order.to_string(),
+                        theoretical: idx.0,
+                    },
+                    ByPuzzleType::Puzzle(idx) => RegisterMetadataRepr::Puzzle {
+                        name: (*reg.name).to_owned(),
+                        order: reg.order.to_string(),
+                        puzzle: idx.0,
+                    },
+                })
+                .collect(),
+            instructions: self
+                .instructions
+                .iter()
+                .map(|v| instruction_to_repr(v))
+                .collect(),
+        };
+
+        serde_json::to_string(&repr).unwrap()
+    }
+
+    /// Load a program serialized by `Program::to_json`. Since the program doesn't come from source code, the instructions' spans cover the whole JSON string, and each puzzle's span covers its definition.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the JSON is malformed, or if it refers to puzzles, moves, registers, facelets, or instructions that don't exist.
+    pub fn from_json(json: &str) -> Result<Program, ProgramJsonError> {
+        let repr = serde_json::from_str::<ProgramRepr>(json)?;
+
+        let source = whole_span("<json>", json);
+
+        let theoretical = repr
+            .theoretical
+            .iter()
+            .map(|v| Ok(source.clone().with(number_from_repr(v)?)))
+            .collect::<Result<Vec<_>, ProgramJsonError>>()?;
+
+        let puzzles = repr
+            .puzzles
+            .iter()
+            .map(|name| {
+                let group = name
+                    .parse::<PuzzleGeometry>()
+                    .map_err(|_| ProgramJsonError::UnknownPuzzle(name.clone()))?
+                    .permutation_group();
+
+                Ok(whole_span("<json>", name).with(group))
+            })
+            .collect::<Result<Vec<_>, ProgramJsonError>>()?;
+
+        let groups = puzzles.iter().map(|v| Arc::clone(v)).collect::<Vec<_>>();
+        let resolver = Resolver {
+            theoretical_count: theoretical.len(),
+            instruction_count: repr.instructions.len(),
+            puzzles: &groups,
+        };
+
+        let register_metadata = repr
+            .registers
+            .iter()
+            .map(|reg| {
+                Ok(match reg {
+                    RegisterMetadataRepr::Theoretical {
+                        name,
+                        order,
+                        theoretical,
+                    } => RegisterMetadata {
+                        name: ArcIntern::from(&**name),
+                        order: number_from_repr(order)?,
+                        storage: ByPuzzleType::Theoretical(resolver.theoretical(*theoretical)?),
+                    },
+                    RegisterMetadataRepr::Puzzle {
+                        name,
+                        order,
+                        puzzle,
+                    } => RegisterMetadata {
+                        name: ArcIntern::from(&**name),
+                        order: number_from_repr(order)?,
+                        storage: ByPuzzleType::Puzzle(resolver.puzzle(*puzzle)?.0),
+                    },
+                })
+            })
+            .collect::<Result<Vec<_>, ProgramJsonError>>()?;
+
+        let instructions = repr
+            .instructions
+            .into_iter()
+            .map(|v| Ok(source.clone().with(instruction_from_repr(v, &resolver)?)))
+            .collect::<Result<Vec<_>, ProgramJsonError>>()?;
+
+        Ok(Program {
+            theoretical,
+            puzzles,
+            register_metadata,
+            instructions,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{FaceletsError, Program, ProgramJsonError};
+
+    fn program_json(instruction: &str) -> String {
+        format!(r#"{{"theoretical":["10"],"puzzles":["3x3"],"instructions":[{instruction}]}}"#)
+    }
+
+    #[test]
+    fn jump_out_of_range() {
+        let goto = program_json(r#"{"kind":"goto","instruction_idx":0}"#);
+        assert!(Program::from_json(&goto).is_ok());
+
+        assert!(matches!(
+            Program::from_json(&program_json(r#"{"kind":"goto","instruction_idx":1}"#)),
+            Err(ProgramJsonError::InvalidInstructionIdx(1))
+        ));
+
+        assert!(matches!(
+            Program::from_json(&program_json(
+                r#"{"kind":"solved-goto-theoretical","instruction_idx":5,"theoretical":0,"modulus":null}"#
+            )),
+            Err(ProgramJsonError::InvalidInstructionIdx(5))
+        ));
+    }
+
+    #[test]
+    fn facelets_out_of_range() {
+        let solved_goto = |facelet: usize, order: &str| {
+            Program::from_json(&program_json(&format!(
+                r#"{{"kind":"solved-goto","instruction_idx":0,"puzzle":0,"facelets":{{"facelets":[{facelet}],"pieces":[],"order":"{order}"}}}}"#
+            )))
+        };
+
+        assert!(solved_goto(0, "4").is_ok());
+
+        assert!(matches!(
+            solved_goto(1000, "4"),
+            Err(ProgramJsonError::InvalidFacelets(FaceletsError::FaceletOutOfRange {
+                facelet: 1000,
+                ..
+            }))
+        ));
+
+        assert!(matches!(
+            solved_goto(0, "0"),
+            Err(ProgramJsonError::InvalidFacelets(FaceletsError::ZeroOrder))
+        ));
+    }
+}
@@ -6,8 +6,14 @@
 )]
 
 pub mod architectures;
+#[cfg(feature = "json")]
+mod json;
 mod shared_facelet_detection;
+pub mod stabilizer_chain;
 pub mod table_encoding;
 
 mod runtime;
 pub use runtime::*;
+
+#[cfg(feature = "json")]
+pub use json::ProgramJsonError;
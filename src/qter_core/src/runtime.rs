@@ -1,5 +1,7 @@
 use std::convert::Infallible;
-use std::fmt::Debug;
+use std::error::Error;
+use std::fmt::{Debug, Display};
+use std::hash::{Hash, Hasher};
 use std::sync::Arc;
 
 use internment::ArcIntern;
@@ -8,7 +10,7 @@ use puzzle_theory::permutations::{Algorithm, PermutationGroup};
 use puzzle_theory::span::WithSpan;
 
 /// The facelets needed for `solved-goto`
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub struct Facelets {
     facelets: Vec<usize>,
     pieces: Vec<ArcIntern<str>>,
@@ -24,6 +26,33 @@ impl Facelets {
         }
     }
 
+    /// Create a `Facelets`, checking that every facelet exists in the permutation group and that the order is nonzero so that decoding with it can't index out of bounds
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if a facelet is out of range for the group or if the order is zero
+    pub fn new_checked(
+        facelets: Vec<usize>,
+        pieces: Vec<ArcIntern<str>>,
+        order: Int<U>,
+        perm_group: &PermutationGroup,
+    ) -> Result<Self, FaceletsError> {
+        if order.is_zero() {
+            return Err(FaceletsError::ZeroOrder);
+        }
+
+        let facelet_count = perm_group.facelet_count();
+
+        if let Some(&facelet) = facelets.iter().find(|&&facelet| facelet >= facelet_count) {
+            return Err(FaceletsError::FaceletOutOfRange {
+                facelet,
+                facelet_count,
+            });
+        }
+
+        Ok(Self::new(facelets, pieces, order))
+    }
+
     pub fn facelets(&self) -> &[usize] {
         &self.facelets
     }
@@ -35,8 +64,47 @@ impl Facelets {
     pub fn order(&self) -> Int<U> {
         self.order
     }
+
+    /// Whether the facelet is one of the facelets that need to be checked
+    pub fn contains_facelet(&self, facelet: usize) -> bool {
+        self.facelets.contains(&facelet)
+    }
+}
+
+impl Hash for Facelets {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        // Equal values have equal facelets and pieces, so leaving out the order is consistent with `Eq`
+        self.facelets.hash(state);
+        self.pieces.hash(state);
+    }
+}
+
+/// A reason that a `Facelets` could not be constructed
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum FaceletsError {
+    /// The facelet doesn't exist in a permutation group with `facelet_count` facelets
+    FaceletOutOfRange { facelet: usize, facelet_count: usize },
+    /// The order to decode modulo is zero
+    ZeroOrder,
+}
+
+impl Display for FaceletsError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            FaceletsError::FaceletOutOfRange {
+                facelet,
+                facelet_count,
+            } => write!(
+                f,
+                "Facelet {facelet} is out of range for a puzzle with {facelet_count} facelets"
+            ),
+            FaceletsError::ZeroOrder => write!(f, "The order to decode modulo must not be zero"),
+        }
+    }
 }
 
+impl Error for FaceletsError {}
+
 /// The generator of a register along with the facelets needed to decode it
 pub struct RegisterGenerator;
 
@@ -150,13 +218,14 @@ pub enum Instruction {
     RepeatUntil(ByPuzzleType<'static, RepeatUntil>),
 }
 
+/// A theoretical `solved-goto` may carry a modulus, in which case it jumps when the register's value is divisible by the modulus rather than when it is zero
 #[derive(Clone, Debug)]
 pub struct SolvedGoto {
     pub instruction_idx: usize,
 }
 
 impl SeparatesByPuzzleType for SolvedGoto {
-    type Theoretical<'s> = (Self, TheoreticalIdx);
+    type Theoretical<'s> = (Self, TheoreticalIdx, Option<Int<U>>);
 
     type Puzzle<'s> = (Self, PuzzleIdx, Facelets);
 }
@@ -230,6 +299,38 @@ pub struct Program {
     pub theoretical: Vec<WithSpan<Int<U>>>,
     /// A list of puzzles to be used for registers
     pub puzzles: Vec<WithSpan<Arc<PermutationGroup>>>,
+    /// The registers that the program declared; empty if the program wasn't compiled from QAT
+    pub register_metadata: Vec<RegisterMetadata>,
     /// The program itself
     pub instructions: Vec<WithSpan<Instruction>>,
 }
+
+impl Program {
+    /// Every register that the program declared along with its name, its order, and whether it is theoretical or stored on a puzzle, in the order that they were declared
+    #[must_use]
+    pub fn registers(&self) -> &[RegisterMetadata] {
+        &self.register_metadata
+    }
+
+    /// Find a declared register by name
+    #[must_use]
+    pub fn register(&self, name: &str) -> Option<&RegisterMetadata> {
+        self.register_metadata.iter().find(|reg| &*reg.name == name)
+    }
+}
+
+/// A register declared by the QAT program that a `Program` was compiled from
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RegisterMetadata {
+    pub name: ArcIntern<str>,
+    pub order: Int<U>,
+    /// The theoretical register that this is, or the puzzle that the register is stored on
+    pub storage: ByPuzzleType<'static, StateIdx>,
+}
+
+impl RegisterMetadata {
+    #[must_use]
+    pub fn is_theoretical(&self) -> bool {
+        matches!(self.storage, ByPuzzleType::Theoretical(_))
+    }
+}
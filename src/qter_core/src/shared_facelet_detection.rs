@@ -1,4 +1,7 @@
-use std::{collections::HashSet, sync::Arc};
+use std::{
+    collections::{BTreeMap, BTreeSet, HashSet},
+    sync::Arc,
+};
 
 use itertools::Itertools;
 use puzzle_theory::{
@@ -54,6 +57,68 @@ fn find_orbits<'a>(
     union_find
 }
 
+/// A report of which facelets are shared between the registers of an architecture, and which registers share them
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SharedFaceletReport {
+    by_facelet: BTreeMap<usize, BTreeSet<usize>>,
+}
+
+impl SharedFaceletReport {
+    /// Get the shared facelets, each with the indices of the registers whose algorithms move facelets in its orbit
+    #[must_use]
+    pub fn by_facelet(&self) -> &BTreeMap<usize, BTreeSet<usize>> {
+        &self.by_facelet
+    }
+
+    /// Get the facelets that each pair of registers shares, keyed by the register indices in increasing order
+    #[must_use]
+    pub fn by_register_pair(&self) -> BTreeMap<(usize, usize), Vec<usize>> {
+        let mut by_register_pair = BTreeMap::<_, Vec<_>>::new();
+
+        for (&facelet, registers) in &self.by_facelet {
+            for (&a, &b) in registers.iter().tuple_combinations() {
+                by_register_pair.entry((a, b)).or_default().push(facelet);
+            }
+        }
+
+        by_register_pair
+    }
+
+    /// Whether none of the registers share any facelets
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.by_facelet.is_empty()
+    }
+}
+
+/// Find which facelets are shared between the registers generated by `algorithms`, and which registers share each of them. Register indices correspond to indices into `algorithms`.
+///
+/// A facelet is shared when algorithms of more than one register move facelets in its orbit, which is the same criterion `Architecture::new` uses to compute `Architecture::shared_facelets`.
+#[must_use]
+pub fn shared_facelet_report(
+    group: &PermutationGroup,
+    algorithms: &[Algorithm],
+) -> SharedFaceletReport {
+    let orbits = find_orbits(
+        group.facelet_count(),
+        algorithms.iter().map(Algorithm::permutation),
+    );
+
+    let by_facelet = (0..group.facelet_count())
+        .filter_map(|facelet| {
+            let registers = &orbits.find(facelet).set_meta().0;
+
+            if registers.len() > 1 {
+                Some((facelet, registers.iter().copied().collect()))
+            } else {
+                None
+            }
+        })
+        .collect();
+
+    SharedFaceletReport { by_facelet }
+}
+
 /// Convert the algorithms into a list of cycle generators and a list of shared facelets
 ///
 /// # Errors
@@ -118,8 +183,9 @@ mod tests {
 
     use crate::architectures::{Architecture, CycleGeneratorSubcycle};
 
-    #[test]
-    fn simple() {
+    use super::shared_facelet_report;
+
+    fn simple_group() -> Arc<PermutationGroup> {
         let mut generators = HashMap::new();
 
         generators.insert(
@@ -159,7 +225,7 @@ mod tests {
             Permutation::from_cycles(vec![vec![13, 12, 11, 10]]),
         );
 
-        let perm_group = Arc::new(PermutationGroup::new(
+        Arc::new(PermutationGroup::new(
             vec![
                 ArcIntern::from("A"),
                 ArcIntern::from("B"),
@@ -178,7 +244,12 @@ mod tests {
             ],
             vec![ArcIntern::from("A"); 14],
             generators,
-        ));
+        ))
+    }
+
+    #[test]
+    fn simple() {
+        let perm_group = simple_group();
 
         let preset = Architecture::new(
             Arc::clone(&perm_group),
@@ -215,4 +286,38 @@ mod tests {
             ]
         );
     }
+
+    #[test]
+    fn report() {
+        let perm_group = simple_group();
+
+        let report = shared_facelet_report(
+            &perm_group,
+            &[
+                Algorithm::parse_from_string(Arc::clone(&perm_group), "A B").unwrap(),
+                Algorithm::parse_from_string(Arc::clone(&perm_group), "C D E").unwrap(),
+            ],
+        );
+
+        assert_eq!(
+            report.by_facelet().keys().copied().collect::<Vec<_>>(),
+            vec![3, 4, 5, 6, 7]
+        );
+        assert!(
+            report
+                .by_facelet()
+                .values()
+                .all(|registers| registers.iter().copied().eq([0, 1]))
+        );
+        assert_eq!(
+            report.by_register_pair().into_iter().collect::<Vec<_>>(),
+            vec![((0, 1), vec![3, 4, 5, 6, 7])]
+        );
+
+        let report = shared_facelet_report(
+            &perm_group,
+            &[Algorithm::parse_from_string(Arc::clone(&perm_group), "A D").unwrap()],
+        );
+        assert!(report.is_empty());
+    }
 }
@@ -0,0 +1,184 @@
+use puzzle_theory::permutations::{Permutation, PermutationGroup};
+
+/// A permutation stored as the facelet that each facelet is moved to
+type Mapping = Box<[usize]>;
+
+/// Apply `a` and then `b`
+fn compose(a: &[usize], b: &[usize]) -> Mapping {
+    a.iter().map(|&facelet| b[facelet]).collect()
+}
+
+fn invert(a: &[usize]) -> Mapping {
+    let mut inverse = vec![0; a.len()];
+
+    for (facelet, &maps_to) in a.iter().enumerate() {
+        inverse[maps_to] = facelet;
+    }
+
+    inverse.into_boxed_slice()
+}
+
+fn is_identity(a: &[usize]) -> bool {
+    a.iter().enumerate().all(|(facelet, &maps_to)| facelet == maps_to)
+}
+
+/// The subgroup that fixes every facelet before this level's facelet
+struct Level {
+    /// Generators of the subgroup that aren't in the subgroup of the next level
+    generators: Vec<Mapping>,
+    /// For each facelet that the subgroup can move this level's facelet to, an element that does so along with its inverse
+    transversal: Vec<Option<(Mapping, Mapping)>>,
+}
+
+/// A stabilizer chain of a permutation group, which allows testing whether a permutation is a member of the group. It is built using Knuth's variant of the Schreier-Sims algorithm from "Efficient representation of perm groups", with the facelets in order as the base.
+pub struct StabilizerChain {
+    levels: Vec<Level>,
+}
+
+impl StabilizerChain {
+    #[must_use]
+    pub fn new(group: &PermutationGroup) -> StabilizerChain {
+        let facelet_count = group.facelet_count();
+
+        let identity = (0..facelet_count).collect::<Mapping>();
+
+        let mut chain = StabilizerChain {
+            levels: (0..facelet_count)
+                .map(|facelet| {
+                    let mut transversal = vec![None; facelet_count];
+                    transversal[facelet] = Some((identity.clone(), identity.clone()));
+
+                    Level {
+                        generators: Vec::new(),
+                        transversal,
+                    }
+                })
+                .collect(),
+        };
+
+        for (_, generator) in group.generators() {
+            let generator = (0..facelet_count)
+                .map(|facelet| generator.mapping().get(facelet))
+                .collect::<Mapping>();
+
+            chain.add_generator(0, generator);
+        }
+
+        chain
+    }
+
+    /// Whether the permutation is an element of the group
+    #[must_use]
+    pub fn contains(&self, permutation: &Permutation) -> bool {
+        let facelet_count = self.levels.len();
+
+        if permutation
+            .mapping()
+            .all_changes()
+            .any(|(from, to)| from >= facelet_count || to >= facelet_count)
+        {
+            return false;
+        }
+
+        let mapping = (0..facelet_count)
+            .map(|facelet| permutation.mapping().get(facelet))
+            .collect::<Mapping>();
+
+        self.sift(0, mapping)
+    }
+
+    /// Whether the permutation, which must fix the facelets of every level before `level`, is in the subgroup at `level`
+    fn sift(&self, level: usize, mut mapping: Mapping) -> bool {
+        for (facelet, level) in self.levels.iter().enumerate().skip(level) {
+            let Some((_, inverse)) = &level.transversal[mapping[facelet]] else {
+                return false;
+            };
+
+            mapping = compose(&mapping, inverse);
+        }
+
+        true
+    }
+
+    /// Knuth's procedure A: extend the subgroup at `level` to include `mapping`, which must fix the facelets of every level before `level`
+    fn add_generator(&mut self, level: usize, mapping: Mapping) {
+        if self.sift(level, mapping.clone()) {
+            return;
+        }
+
+        self.levels[level].generators.push(mapping.clone());
+
+        let representatives = self.levels[level]
+            .transversal
+            .iter()
+            .flatten()
+            .map(|(representative, _)| representative.clone())
+            .collect::<Vec<_>>();
+
+        for representative in representatives {
+            self.extend_orbit(level, compose(&representative, &mapping));
+        }
+    }
+
+    /// Knuth's procedure B: `mapping` is an element of the subgroup at `level`. Add it to the transversal if it moves the level's facelet somewhere new, otherwise make sure that the next level's subgroup contains what is left of it after undoing the existing representative.
+    fn extend_orbit(&mut self, level: usize, mapping: Mapping) {
+        let maps_to = mapping[level];
+
+        if let Some((_, inverse)) = &self.levels[level].transversal[maps_to] {
+            let residue = compose(&mapping, inverse);
+
+            if !is_identity(&residue) {
+                self.add_generator(level + 1, residue);
+            }
+
+            return;
+        }
+
+        let inverse = invert(&mapping);
+        self.levels[level].transversal[maps_to] = Some((mapping.clone(), inverse));
+
+        let generators = self.levels[level].generators.clone();
+
+        for generator in generators {
+            self.extend_orbit(level, compose(&mapping, &generator));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use puzzle_theory::{
+        permutations::{Algorithm, Permutation},
+        puzzle_geometry::parsing::puzzle,
+    };
+
+    use super::StabilizerChain;
+
+    #[test]
+    fn cube_membership() {
+        let cube3 = puzzle("3x3").permutation_group();
+        let chain = StabilizerChain::new(&cube3);
+
+        assert!(chain.contains(&Permutation::identity()));
+
+        for alg in ["U", "R U R' U'", "F2 B' L D2 R' U F'", "R U2 D' B D'"] {
+            let alg = Algorithm::parse_from_string(Arc::clone(&cube3), alg).unwrap();
+            assert!(chain.contains(alg.permutation()), "{alg:?}");
+        }
+
+        // Swapping two stickers doesn't keep the pieces intact
+        assert!(!chain.contains(&Permutation::from_cycles(vec![vec![0, 1]])));
+
+        // Only one of the four-cycles that a U move is made of leaves the pieces broken apart
+        let u = Algorithm::parse_from_string(Arc::clone(&cube3), "U").unwrap();
+        let four_cycle = u.permutation().cycles().iter().find(|cycle| cycle.len() == 4).unwrap();
+        assert!(!chain.contains(&Permutation::from_cycles(vec![four_cycle.to_vec()])));
+
+        assert!(!chain.contains(&Permutation::from_cycles(vec![vec![
+            0,
+            cube3.facelet_count()
+        ]])));
+    }
+}
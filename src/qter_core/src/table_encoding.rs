@@ -1,13 +1,54 @@
 use core::hash::Hash;
 use std::{
     collections::{HashMap, HashSet},
+    error::Error,
+    fmt::Display,
+    io::{self, BufReader, Read},
+    num::NonZeroUsize,
     rc::Rc,
+    thread,
 };
 
 use internment::ArcIntern;
 use itertools::Itertools;
 use pog_ans::{Cache, CodingFSM, TakeFrom, ans_decode, ans_encode};
 
+/// Written at the start of every versioned table. Tables from before versioning start directly with a little-endian symbol count, which is never large enough to look like this.
+const MAGIC: [u8; 4] = *b"QTBL";
+
+/// The newest format version that can be decoded. `encode_table` writes version one tables, which contain a single encoded list of algorithms, and `encode_table_parallel` writes version two tables, which contain several lists encoded independently. Headerless legacy tables are treated as version zero, whose contents are laid out the same way as version one.
+pub const FORMAT_VERSION: u8 = 2;
+
+const SINGLE_TABLE_VERSION: u8 = 1;
+
+const CHUNKED_TABLE_VERSION: u8 = 2;
+
+/// A reason that a table couldn't be decoded
+#[derive(Debug)]
+pub enum TableDecodeError {
+    /// The table was written with a format version that this version of qter doesn't understand
+    UnsupportedVersion(u8),
+    /// The table ended early or contained invalid data
+    Malformed,
+    /// Reading the table failed
+    Io(io::Error),
+}
+
+impl Display for TableDecodeError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            TableDecodeError::UnsupportedVersion(version) => write!(
+                f,
+                "The table has format version {version} but only versions up to {FORMAT_VERSION} are supported"
+            ),
+            TableDecodeError::Malformed => write!(f, "The table is malformed"),
+            TableDecodeError::Io(e) => write!(f, "Failed to read the table: {e}"),
+        }
+    }
+}
+
+impl Error for TableDecodeError {}
+
 #[derive(Debug)]
 struct TableStats {
     frequencies: Vec<u32>,
@@ -20,6 +61,58 @@ struct TableStats {
 /// Also returns the compressed size of the data with the header size subtracted out.
 #[must_use]
 pub fn encode_table(algs: &[Vec<ArcIntern<str>>]) -> Option<(Vec<u8>, usize)> {
+    let mut stream = Vec::new();
+
+    stream.extend_from_slice(&MAGIC);
+    stream.push(SINGLE_TABLE_VERSION);
+
+    let compressed_size = encode_table_contents(&mut stream, algs)?;
+
+    Some((stream, compressed_size))
+}
+
+/// Like `encode_table`, but splits the algorithms into chunks that are encoded concurrently, one for each available thread. Each chunk is modelled separately so the table may be slightly larger than one created by `encode_table`.
+///
+/// Returns None if any chunk has too many unique generators to be able to encode them.
+#[must_use]
+pub fn encode_table_parallel(algs: &[Vec<ArcIntern<str>>]) -> Option<(Vec<u8>, usize)> {
+    let threads = thread::available_parallelism().map_or(1, NonZeroUsize::get);
+    let chunk_size = algs.len().div_ceil(threads).max(1);
+
+    let chunks = thread::scope(|scope| {
+        algs.chunks(chunk_size)
+            .map(|chunk| {
+                scope.spawn(move || {
+                    let mut stream = Vec::new();
+                    let compressed_size = encode_table_contents(&mut stream, chunk)?;
+                    Some((stream, compressed_size))
+                })
+            })
+            .collect_vec()
+            .into_iter()
+            .map(|handle| handle.join().unwrap())
+            .collect::<Option<Vec<_>>>()
+    })?;
+
+    let mut stream = Vec::new();
+
+    stream.extend_from_slice(&MAGIC);
+    stream.push(CHUNKED_TABLE_VERSION);
+    stream.extend_from_slice(&(chunks.len() as u32).to_le_bytes());
+
+    let mut compressed_size = 0;
+
+    for (chunk, chunk_compressed_size) in chunks {
+        stream.extend_from_slice(&(chunk.len() as u32).to_le_bytes());
+        stream.extend_from_slice(&chunk);
+        compressed_size += chunk_compressed_size;
+    }
+
+    Some((stream, compressed_size))
+}
+
+/// Encodes the table without a header, returning the compressed size of the algorithms
+fn encode_table_contents(stream: &mut Vec<u8>, algs: &[Vec<ArcIntern<str>>]) -> Option<usize> {
     // Statistical modelling of twisty puzzle algs:
     //
     // First, we're going to keep track of frequencies of different generators. I technically don't know but I highly doubt that generators for optimal solutions will be completely uniform. Also, if Arhan decides to pick algs with better finger tricks, this will take advantage of the distribution.
@@ -96,8 +189,6 @@ pub fn encode_table(algs: &[Vec<ArcIntern<str>>]) -> Option<(Vec<u8>, usize)> {
 
     // Now `disallowed_pairs` means the correct thing
 
-    let mut stream = Vec::new();
-
     stream.extend_from_slice(&(stats.frequencies.len() as u32).to_le_bytes());
 
     for (symbol, &idx) in symbol_indices.iter().sorted_unstable_by_key(|(_, i)| **i) {
@@ -145,7 +236,7 @@ pub fn encode_table(algs: &[Vec<ArcIntern<str>>]) -> Option<(Vec<u8>, usize)> {
     stream.extend_from_slice(&(disallowed_pair_symbols.len() as u32).to_le_bytes());
 
     ans_encode(
-        &mut stream,
+        stream,
         &disallowed_pair_symbols,
         mk_disallowed_pair_symbols_fsm(stats.frequencies.len() + 1),
     );
@@ -164,11 +255,11 @@ pub fn encode_table(algs: &[Vec<ArcIntern<str>>]) -> Option<(Vec<u8>, usize)> {
 
     let before = stream.len();
 
-    ans_encode(&mut stream, &symbols, mk_distribution_fsm(stats));
+    ans_encode(stream, &symbols, mk_distribution_fsm(stats));
 
     let after = stream.len();
 
-    Some((stream, after - before))
+    Some(after - before)
 }
 
 fn rest_unweighted(ranges: &mut [u16], mut range_left: usize) {
@@ -225,8 +316,58 @@ fn rest_weighted(ranges: &mut [u16], mut range_left: usize, distribution: &[u32]
     }
 }
 
-/// Decodes a table and returns None if it can't be decoded
-pub fn decode_table(data: &mut impl Iterator<Item = u8>) -> Option<Vec<Vec<ArcIntern<str>>>> {
+/// Decodes a table, accepting both versioned tables and headerless legacy tables
+///
+/// # Errors
+///
+/// Returns an error if the table has an unknown format version or can't be decoded
+pub fn decode_table(
+    data: &mut impl Iterator<Item = u8>,
+) -> Result<Vec<Vec<ArcIntern<str>>>, TableDecodeError> {
+    let header = data.by_ref().take(MAGIC.len()).collect_vec();
+
+    if header != MAGIC {
+        // A legacy table, so the bytes we peeked at are part of the contents
+        return decode_table_contents(&mut header.into_iter().chain(data))
+            .ok_or(TableDecodeError::Malformed);
+    }
+
+    match data.next() {
+        Some(SINGLE_TABLE_VERSION) => {
+            decode_table_contents(data).ok_or(TableDecodeError::Malformed)
+        }
+        Some(CHUNKED_TABLE_VERSION) => {
+            decode_chunked_table(data).ok_or(TableDecodeError::Malformed)
+        }
+        Some(version) => Err(TableDecodeError::UnsupportedVersion(version)),
+        None => Err(TableDecodeError::Malformed),
+    }
+}
+
+fn decode_chunked_table(
+    data: &mut impl Iterator<Item = u8>,
+) -> Option<Vec<Vec<ArcIntern<str>>>> {
+    let chunk_count = u32::take_from(data)?;
+
+    let mut algs = Vec::new();
+
+    for _ in 0..chunk_count {
+        let chunk_len = u32::take_from(data)? as usize;
+        let chunk = data.take(chunk_len).collect_vec();
+
+        if chunk.len() != chunk_len {
+            return None;
+        }
+
+        algs.extend(decode_table_contents(&mut chunk.into_iter())?);
+    }
+
+    Some(algs)
+}
+
+fn decode_table_contents(
+    data: &mut impl Iterator<Item = u8>,
+) -> Option<Vec<Vec<ArcIntern<str>>>> {
     let symbol_count = u32::take_from(data)?;
 
     let mut symbols = Vec::new();
@@ -300,6 +441,27 @@ pub fn decode_table(data: &mut impl Iterator<Item = u8>) -> Option<Vec<Vec<ArcIn
     Some(algs)
 }
 
+/// Decodes a table incrementally from a reader so that the encoded table never has to be held in memory all at once
+///
+/// # Errors
+///
+/// Returns an error if reading fails or if `decode_table` would fail
+pub fn decode_table_from_reader(
+    reader: impl Read,
+) -> Result<Vec<Vec<ArcIntern<str>>>, TableDecodeError> {
+    let mut read_error = None;
+
+    let algs = decode_table(&mut BufReader::new(reader).bytes().map_while(|byte| {
+        // A truncated stream could otherwise be misparsed as a shorter table
+        byte.map_err(|e| read_error = Some(e)).ok()
+    }));
+
+    match read_error {
+        Some(e) => Err(TableDecodeError::Io(e)),
+        None => algs,
+    }
+}
+
 fn mk_disallowed_pair_symbols_fsm(symbol_count: usize) -> impl CodingFSM<u16> + Clone {
     Cache::new(DisallowedPairSymbolsFSM {
         symbol_count,
@@ -506,9 +668,11 @@ mod tests {
     use internment::ArcIntern;
     use itertools::Itertools;
 
-    use crate::table_encoding::decode_table;
+    use crate::table_encoding::{
+        FORMAT_VERSION, MAGIC, TableDecodeError, decode_table, decode_table_from_reader,
+    };
 
-    use super::encode_table;
+    use super::{encode_table, encode_table_parallel};
 
     fn mk_algs_datastructure(spec: &str) -> Vec<Vec<ArcIntern<str>>> {
         spec.split('\n')
@@ -540,6 +704,72 @@ mod tests {
         // panic!()
     }
 
+    #[test]
+    fn test_table_decoding_from_reader() {
+        let algs = mk_algs_datastructure(
+            "
+                A B C
+                C B A
+                B
+            ",
+        );
+
+        let encoded = encode_table(&algs).unwrap().0;
+        let decoded = decode_table_from_reader(encoded.as_slice()).unwrap();
+        assert_eq!(algs, decoded);
+    }
+
+    #[test]
+    fn legacy_table_decoding() {
+        let algs = mk_algs_datastructure(
+            "
+                A B C
+                C B A
+            ",
+        );
+
+        let encoded = encode_table(&algs).unwrap().0;
+        let legacy = &encoded[MAGIC.len() + 1..];
+
+        assert_eq!(decode_table(&mut legacy.iter().copied()).unwrap(), algs);
+    }
+
+    #[test]
+    fn unsupported_table_version() {
+        let mut encoded = encode_table(&mk_algs_datastructure("A B")).unwrap().0;
+        encoded[MAGIC.len()] = FORMAT_VERSION + 1;
+
+        assert!(matches!(
+            decode_table(&mut encoded.iter().copied()),
+            Err(TableDecodeError::UnsupportedVersion(version)) if version == FORMAT_VERSION + 1
+        ));
+    }
+
+    #[test]
+    fn parallel_table_encoding() {
+        let generators = ["U", "U'", "U2", "R", "R'", "R2", "F", "F'", "F2"].map(ArcIntern::from);
+
+        // A simple LCG so that the table is large but deterministic
+        let mut seed = 12345_u64;
+        let mut next = |bound: usize| {
+            seed = seed.wrapping_mul(6_364_136_223_846_793_005).wrapping_add(1);
+            (seed >> 33) as usize % bound
+        };
+
+        let algs = (0..5000)
+            .map(|_| {
+                let len = next(20) + 1;
+                (0..len)
+                    .map(|_| ArcIntern::clone(&generators[next(generators.len())]))
+                    .collect_vec()
+            })
+            .collect_vec();
+
+        let encoded = encode_table_parallel(&algs).unwrap().0;
+        assert_eq!(decode_table(&mut encoded.iter().copied()).unwrap(), algs);
+        assert_eq!(decode_table_from_reader(encoded.as_slice()).unwrap(), algs);
+    }
+
     #[test]
     fn extensive_table_encoding_test() {
         // All the OLL PLL algs
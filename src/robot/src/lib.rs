@@ -1,8 +1,12 @@
 #![feature(gen_blocks)]
 
-use crate::{hardware::RobotHandle, qvis_app::QvisAppHandle, rob_twophase::solve_rob_twophase};
+use crate::{
+    hardware::RobotHandle,
+    qvis_app::QvisAppHandle,
+    rob_twophase::{RobTwophaseConfig, solve_rob_twophase_with},
+};
 use interpreter::puzzle_states::RobotLike;
-use log::trace;
+use log::{trace, warn};
 use puzzle_theory::{
     permutations::{Algorithm, Permutation, PermutationGroup},
     puzzle_geometry::parsing::puzzle,
@@ -20,10 +24,41 @@ pub mod rob_twophase;
 pub static CUBE3: LazyLock<Arc<PermutationGroup>> =
     LazyLock::new(|| puzzle("3x3").permutation_group());
 
+/// How many times to ask rob-twophase for a solution before giving up. The search is multithreaded, so a retry can find a solution that an earlier attempt missed. The bounds stay the same between attempts because changing them restarts rob-twophase and reloads its tables.
+const ROB_TWOPHASE_ATTEMPTS: u32 = 3;
+
 pub struct QterRobot<'a> {
     robot_handle: &'a mut RobotHandle,
     qvis_app_handle: &'a mut QvisAppHandle,
     cached_picture_state: Option<Permutation>,
+    rob_twophase_config: RobTwophaseConfig,
+}
+
+impl QterRobot<'_> {
+    /// Set the bounds on rob-twophase's search when solving the cube. If no solution is found within the bounds, the search is retried with the same bounds.
+    pub fn set_rob_twophase_config(&mut self, config: RobTwophaseConfig) {
+        self.rob_twophase_config = config;
+    }
+
+    fn solve_rob_twophase(&self, perm: &Permutation) -> Result<Algorithm, QterRobotError> {
+        let mut attempt = 1;
+
+        loop {
+            match solve_rob_twophase_with(perm, self.rob_twophase_config) {
+                Ok(alg) => return Ok(alg),
+                Err(e) if attempt < ROB_TWOPHASE_ATTEMPTS => {
+                    warn!("rob-twophase failed on attempt {attempt}, retrying: {e}");
+                    attempt += 1;
+                }
+                Err(e) => {
+                    return Err(QterRobotError {
+                        kind: ErrorKind::RobTwophase,
+                        message: e.to_string(),
+                    });
+                }
+            }
+        }
+    }
 }
 
 #[derive(Debug, Clone, Copy)]
@@ -104,6 +139,7 @@ impl<'a> RobotLike for QterRobot<'a> {
             robot_handle,
             qvis_app_handle,
             cached_picture_state: None,
+            rob_twophase_config: RobTwophaseConfig::default(),
         })
     }
 
@@ -123,9 +159,9 @@ impl<'a> RobotLike for QterRobot<'a> {
                 .qvis_app_handle
                 .take_picture()
                 .await
-                .map_err(|message| QterRobotError {
+                .map_err(|e| QterRobotError {
                     kind: ErrorKind::ComposeInto,
-                    message,
+                    message: e.to_string(),
                 })?;
             self.cached_picture_state = Some(ret);
         } else {
@@ -137,12 +173,23 @@ impl<'a> RobotLike for QterRobot<'a> {
     async fn compose_perm(&mut self, perm: &Permutation) -> Result<(), Self::Error> {
         let mut perm = perm.to_owned();
         perm.invert();
-        self.compose_into(&solve_rob_twophase(&perm).map_err(|e| QterRobotError {
-            kind: ErrorKind::RobTwophase,
-            message: e.to_string(),
-        })?)
-        .await?;
+        let solution = self.solve_rob_twophase(&perm)?;
+        self.compose_into(&solution).await?;
         self.robot_handle.await_moves()?.await?;
         Ok(())
     }
+
+    async fn calibrate(&mut self, reference: Permutation) -> Result<(), Self::Error> {
+        // The cube has to be still in the reference state before the camera looks at it
+        self.robot_handle.await_moves()?.await?;
+        self.cached_picture_state.take();
+
+        self.qvis_app_handle
+            .calibrate_permutation(reference)
+            .await
+            .map_err(|error| QterRobotError {
+                kind: ErrorKind::Calibration,
+                message: error.to_string(),
+            })
+    }
 }
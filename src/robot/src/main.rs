@@ -5,7 +5,7 @@ use chrono::Utc;
 use clap::{Parser, Subcommand};
 use env_logger::TimestampPrecision;
 use interpreter::puzzle_states::{
-    RobotLike, SimulatedPuzzle, WrapSimulatedPuzzle, run_robot_server,
+    DryRunRobot, RobotLike, SimulatedPuzzle, WrapSimulatedPuzzle, run_robot_server,
 };
 use log::{LevelFilter, debug, warn};
 use puzzle_theory::permutations::Algorithm;
@@ -70,6 +70,9 @@ enum Commands {
         server_port: u16,
         #[arg(long)]
         simulated: bool,
+        /// Log every command instead of moving the cube
+        #[arg(long, conflicts_with = "simulated")]
+        dry_run: bool,
     },
     Calibrate,
     Solve {
@@ -158,6 +161,7 @@ async fn main() -> color_eyre::Result<()> {
         Commands::Server {
             server_port,
             simulated,
+            dry_run,
         } => {
             let identity = Identity::self_signed(["10.42.0.1", "192.168.191.3"]).unwrap();
             let cert_digest = identity.certificate_chain().as_slice()[0].hash();
@@ -192,7 +196,7 @@ async fn main() -> color_eyre::Result<()> {
                 }
             });
 
-            let mut maybe_handles = if simulated {
+            let mut maybe_handles = if simulated || dry_run {
                 None
             } else {
                 let qvis_app_handle = QvisAppHandle::init(&robot_config.qvis_app_path)
@@ -218,6 +222,8 @@ async fn main() -> color_eyre::Result<()> {
 
                     let res = if simulated {
                         run_robot_server::<_, SimulatedPuzzle>(conn, ()).await
+                    } else if dry_run {
+                        run_robot_server::<_, DryRunRobot>(conn, ()).await
                     } else {
                         let (qvis_app_handle, robot_handle) = maybe_handles.as_mut().unwrap();
                         run_robot_server::<_, WrapSimulatedPuzzle<QterRobot>>(
@@ -1,6 +1,7 @@
-use log::{info, trace};
+use log::{info, trace, warn};
 use puzzle_theory::permutations::{Algorithm, Permutation};
 use std::{
+    fmt::Display,
     io,
     path::PathBuf,
     process::Stdio,
@@ -13,10 +14,59 @@ use tokio::{
 
 use crate::{CUBE3, ErrorKind, QterRobotError, hardware::RobotHandle};
 
+/// How many times to ask `qvis_app` for a picture before giving up on a bad frame
+pub const DEFAULT_PICTURE_ATTEMPTS: usize = 3;
+
 pub struct QvisAppHandle {
     _child: Child,
     stdin: ChildStdin,
     stdout: Lines<BufReader<ChildStdout>>,
+    picture_attempts: usize,
+}
+
+/// Why `qvis_app` failed to give us a picture of the cube
+#[derive(Debug)]
+pub enum PictureError {
+    /// Communicating with `qvis_app` failed, so retrying won't help
+    Io(io::Error),
+    /// `qvis_app` sent a frame that doesn't contain a permutation
+    Malformed { frame: String, reason: String },
+    /// `qvis_app` sent a permutation without a readable confidence, so we can't trust it
+    Partial {
+        frame: String,
+        permutation: Permutation,
+    },
+}
+
+impl PictureError {
+    /// Whether taking another picture could fix the error
+    #[must_use]
+    pub fn is_bad_frame(&self) -> bool {
+        !matches!(self, PictureError::Io(_))
+    }
+}
+
+impl Display for PictureError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            PictureError::Io(e) => e.fmt(f),
+            PictureError::Malformed { frame, reason } => {
+                write!(f, "Malformed frame `{frame}`: {reason}")
+            }
+            PictureError::Partial { frame, permutation } => write!(
+                f,
+                "Read {permutation} from frame `{frame}` but could not tell how confident it is"
+            ),
+        }
+    }
+}
+
+impl std::error::Error for PictureError {}
+
+impl From<io::Error> for PictureError {
+    fn from(value: io::Error) -> Self {
+        PictureError::Io(value)
+    }
 }
 
 impl QvisAppHandle {
@@ -46,10 +96,16 @@ impl QvisAppHandle {
             _child: child,
             stdin,
             stdout,
+            picture_attempts: DEFAULT_PICTURE_ATTEMPTS,
         })
     }
 
-    async fn calibrate_permutation(
+    /// Set how many times `take_picture` asks for a picture before giving up on bad frames. At least one attempt is always made.
+    pub fn set_picture_attempts(&mut self, attempts: usize) {
+        self.picture_attempts = attempts;
+    }
+
+    pub(crate) async fn calibrate_permutation(
         &mut self,
         calibration_permutation: Permutation,
     ) -> Result<(), io::Error> {
@@ -73,37 +129,60 @@ impl QvisAppHandle {
         ))
     }
 
-    pub async fn take_picture(&mut self) -> Result<Permutation, String> {
+    /// Take a picture of the cube, asking again if `qvis_app` sends back a frame that we can't read
+    pub async fn take_picture(&mut self) -> Result<Permutation, PictureError> {
+        let mut attempt = 1;
+
+        loop {
+            match self.take_picture_once().await {
+                Err(e) if e.is_bad_frame() && attempt < self.picture_attempts => {
+                    warn!("Bad picture on attempt {attempt}, retrying: {e}");
+                    attempt += 1;
+                }
+                result => return result,
+            }
+        }
+    }
+
+    async fn take_picture_once(&mut self) -> Result<Permutation, PictureError> {
         trace!("QvisAppHandle: taking picture");
-        self.stdin
-            .write_all(b"TAKE_PICTURE\n")
-            .await
-            .map_err(|e| e.to_string())?;
-        self.stdin.flush().await.map_err(|e| e.to_string())?;
+        self.stdin.write_all(b"TAKE_PICTURE\n").await?;
+        self.stdin.flush().await?;
         trace!("QvisAppHandle: sent take picture command");
 
-        while let Some(line) = self.stdout.next_line().await.map_err(|e| e.to_string())? {
-            if line.starts_with("DONE") {
-                let perm_str = line.trim_start_matches("DONE").trim();
-                let mut iter = perm_str.split(";");
-                let perm = iter
-                    .next()
-                    .unwrap()
+        while let Some(line) = self.stdout.next_line().await? {
+            if let Some(perm_str) = line.strip_prefix("DONE") {
+                let (perm_str, confidence_str) = match perm_str.trim().split_once(';') {
+                    Some((perm_str, confidence_str)) => (perm_str, Some(confidence_str)),
+                    None => (perm_str.trim(), None),
+                };
+
+                let perm = perm_str
                     .parse::<Permutation>()
-                    .map_err(|e| e.to_string());
-                let confidence = iter
-                    .next()
-                    .unwrap()
-                    .parse::<f64>()
-                    .map_err(|e| e.to_string())?;
+                    .map_err(|e| PictureError::Malformed {
+                        frame: line.clone(),
+                        reason: e.to_string(),
+                    })?;
+
+                let Some(confidence) = confidence_str.and_then(|v| v.trim().parse::<f64>().ok())
+                else {
+                    return Err(PictureError::Partial {
+                        frame: line,
+                        permutation: perm,
+                    });
+                };
+
                 info!("Taken picture of {perm:?} with confidence {confidence}");
-                return perm;
-            } else {
-                eprintln!("qvis_app: {line}");
+                return Ok(perm);
             }
+
+            eprintln!("qvis_app: {line}");
         }
 
-        Err("Process exited before sending DONE".into())
+        Err(PictureError::Io(io::Error::new(
+            io::ErrorKind::UnexpectedEof,
+            "Process exited before sending DONE",
+        )))
     }
 }
 
@@ -2,9 +2,10 @@ use std::{
     collections::HashMap,
     fs,
     io::{BufRead, BufReader, Error, Write},
-    process::{ChildStdin, ChildStdout, Command, Stdio},
+    process::{Child, ChildStdin, ChildStdout, Command, Stdio},
     sync::{Arc, LazyLock, Mutex},
     thread::available_parallelism,
+    time::Duration,
 };
 
 use internment::ArcIntern;
@@ -62,24 +63,72 @@ fn mk_rob_twophase_input(perm: &Permutation) -> String {
     .join("")
 }
 
+/// Bounds on rob-twophase's search. rob-twophase searches both phases together, so the depth limit applies to the length of the whole solution rather than to each phase.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct RobTwophaseConfig {
+    /// The longest solution to accept, or `None` to accept a solution of any length
+    pub max_length: Option<u8>,
+    /// How long to keep searching for shorter solutions after the first one is found
+    pub time_limit: Duration,
+}
+
+impl Default for RobTwophaseConfig {
+    fn default() -> Self {
+        RobTwophaseConfig {
+            max_length: None,
+            time_limit: Duration::from_millis(30),
+        }
+    }
+}
+
 pub fn solve_rob_twophase(perm: &Permutation) -> Result<Algorithm, std::io::Error> {
-    solve_rob_twophase_string(&mk_rob_twophase_input(perm))
+    solve_rob_twophase_with(perm, RobTwophaseConfig::default())
+}
+
+pub fn solve_rob_twophase_with(
+    perm: &Permutation,
+    config: RobTwophaseConfig,
+) -> Result<Algorithm, std::io::Error> {
+    solve_rob_twophase_string_with(&mk_rob_twophase_input(perm), config)
 }
 
-static ROB_TWOPHASE: Mutex<Option<(ChildStdin, BufReader<ChildStdout>)>> = Mutex::new(None);
+static ROB_TWOPHASE: Mutex<Option<(RobTwophaseConfig, Child, ChildStdin, BufReader<ChildStdout>)>> =
+    Mutex::new(None);
 
 pub fn init_rob_twophase() -> std::io::Result<()> {
+    init_rob_twophase_with(RobTwophaseConfig::default())
+}
+
+/// Start rob-twophase with the given search bounds. The bounds are passed on the command line, so if it is already running with different bounds, it is restarted.
+pub fn init_rob_twophase_with(config: RobTwophaseConfig) -> std::io::Result<()> {
     let mut maybe_rob_twophase = ROB_TWOPHASE.lock().unwrap();
 
+    if let Some((_, mut child, stdin, _)) =
+        maybe_rob_twophase.take_if(|(running_config, _, _, _)| *running_config != config)
+    {
+        // Dropping stdin closes the old process's input, which makes it exit
+        drop(stdin);
+        child.wait()?;
+    }
+
     if maybe_rob_twophase.is_none() {
         // rob-twophase will dump tables in its current directory; lets have it dump them in some cache
         let mut cache = dirs::cache_dir().unwrap();
         cache.push("rob-twophase-tables");
         fs::create_dir_all(&cache)?;
 
-        let child = Command::new("twophase")
+        let mut command = Command::new("twophase");
+        command
             .current_dir(cache)
-            .args(["-c", "-m", "30", "-t"])
+            .args(["-c", "-m"])
+            .arg(config.time_limit.as_millis().to_string());
+
+        if let Some(max_length) = config.max_length {
+            command.arg("-l").arg(max_length.to_string());
+        }
+
+        let mut child = command
+            .arg("-t")
             .arg(match available_parallelism() {
                 Ok(v) => v.to_string(),
                 Err(e) => {
@@ -95,8 +144,8 @@ pub fn init_rob_twophase() -> std::io::Result<()> {
             .stderr(Stdio::inherit())
             .spawn()?;
 
-        let stdin = child.stdin.unwrap();
-        let mut stdout = BufReader::new(child.stdout.unwrap());
+        let stdin = child.stdin.take().unwrap();
+        let mut stdout = BufReader::new(child.stdout.take().unwrap());
 
         /*
         Rob Twophase TUI looks like
@@ -130,15 +179,22 @@ pub fn init_rob_twophase() -> std::io::Result<()> {
                 break;
             }
         }
-        let _ = maybe_rob_twophase.insert((stdin, stdout));
+        let _ = maybe_rob_twophase.insert((config, child, stdin, stdout));
     }
     Ok(())
 }
 
 pub fn solve_rob_twophase_string(rob_twophase_string: &str) -> Result<Algorithm, std::io::Error> {
-    init_rob_twophase()?;
+    solve_rob_twophase_string_with(rob_twophase_string, RobTwophaseConfig::default())
+}
+
+pub fn solve_rob_twophase_string_with(
+    rob_twophase_string: &str,
+    config: RobTwophaseConfig,
+) -> Result<Algorithm, std::io::Error> {
+    init_rob_twophase_with(config)?;
     let mut rob_twophase = ROB_TWOPHASE.lock().unwrap();
-    let (twophase_stdin, twophase_stdout) = rob_twophase.as_mut().unwrap();
+    let (_, _, twophase_stdin, twophase_stdout) = rob_twophase.as_mut().unwrap();
 
     writeln!(twophase_stdin, "solve {}", rob_twophase_string)?;
     trace!("solve {rob_twophase_string}");
@@ -171,14 +227,19 @@ pub fn solve_rob_twophase_string(rob_twophase_string: &str) -> Result<Algorithm,
     }
 
     // Split the string and remove the final move count
-    Ok(Algorithm::new_from_move_seq(
+    Algorithm::new_from_move_seq(
         Arc::clone(&CUBE3),
         alg.split(' ')
             .filter(|v| v.chars().next().is_some_and(|v| !v.is_ascii_digit()))
             .map(ArcIntern::from)
             .collect(),
     )
-    .unwrap())
+    .map_err(|_| {
+        Error::other(format!(
+            "rob-twophase did not find a solution within the configured bounds: {}",
+            result.trim()
+        ))
+    })
 }
 
 #[cfg(test)]
@@ -1,4 +1,10 @@
-use puzzle_theory::{permutations::Permutation, puzzle_geometry::PuzzleGeometry};
+use std::collections::{BTreeMap, HashMap, VecDeque};
+use std::sync::Arc;
+
+use puzzle_theory::{
+    permutations::{Permutation, PermutationGroup},
+    puzzle_geometry::PuzzleGeometry,
+};
 use qter_core::architectures::{Architecture, decode};
 use serde::Serialize;
 use tsify::Tsify;
@@ -10,6 +16,8 @@ use crate::BigInt;
 pub struct CubeState {
     cube: CubeStateData,
     registers: Vec<RegisterState>,
+    perm: Permutation,
+    group: Arc<PermutationGroup>,
 }
 
 #[wasm_bindgen]
@@ -18,6 +26,8 @@ impl CubeState {
         Self {
             cube: CubeStateData::from_permutation(perm, puzzle),
             registers: RegisterState::decode(perm, arch),
+            perm: perm.clone(),
+            group: arch.group_arc(),
         }
     }
 
@@ -30,6 +40,13 @@ impl CubeState {
     pub fn registers(&self) -> JsValue {
         serde_wasm_bindgen::to_value(&self.registers).unwrap()
     }
+
+    /// The permutation and orientation of every piece, for renderers that draw pieces rather than facelets
+    #[wasm_bindgen(unchecked_return_type = "OrbitStateData[]")]
+    pub fn pieces(&self) -> JsValue {
+        serde_wasm_bindgen::to_value(&OrbitStateData::from_permutation(&self.perm, &self.group))
+            .unwrap()
+    }
 }
 
 #[derive(Tsify, Serialize)]
@@ -77,3 +94,156 @@ impl CubeStateData {
         }
     }
 }
+
+/// The state of every piece with the same number of stickers. For a 3x3, these are the corners and edges (and the centers, which never move).
+#[derive(Tsify, Serialize)]
+pub struct OrbitStateData {
+    stickers_per_piece: u8,
+    /// For every position in the orbit, the index of the piece occupying it
+    permutation: Vec<u16>,
+    /// For every position in the orbit, which sticker of the occupying piece is on the first sticker of the position, counting around the piece in the same direction for every piece. This is zero when the piece is solved.
+    orientation: Vec<u8>,
+}
+
+impl OrbitStateData {
+    fn from_permutation(perm: &Permutation, group: &PermutationGroup) -> Vec<Self> {
+        let piece_assignments = group.piece_assignments();
+
+        let mut piece_idxs = HashMap::<&str, usize>::new();
+        let mut sorted_stickers = Vec::<Vec<usize>>::new();
+        let mut piece_of_facelet = Vec::with_capacity(piece_assignments.len());
+        for (facelet, piece) in piece_assignments.iter().enumerate() {
+            let idx = *piece_idxs.entry(&**piece).or_insert_with(|| {
+                sorted_stickers.push(Vec::new());
+                sorted_stickers.len() - 1
+            });
+            sorted_stickers[idx].push(facelet);
+            piece_of_facelet.push(idx);
+        }
+
+        // The order of the stickers in increasing index doesn't go around every piece in the same direction, so the stickers of the first piece in each orbit are carried to the others by the puzzle's moves, which keep the stickers of a piece in the same cyclic order
+        let mut piece_stickers = vec![None::<Vec<usize>>; sorted_stickers.len()];
+        for (idx, stickers) in sorted_stickers.iter().enumerate() {
+            if piece_stickers[idx].is_some() {
+                continue;
+            }
+
+            piece_stickers[idx] = Some(stickers.clone());
+            let mut queue = VecDeque::from([idx]);
+
+            while let Some(from) = queue.pop_front() {
+                for (_, generator) in group.generators() {
+                    let stickers = piece_stickers[from]
+                        .as_ref()
+                        .unwrap()
+                        .iter()
+                        .map(|&facelet| generator.mapping().get(facelet))
+                        .collect::<Vec<_>>();
+                    let to = piece_of_facelet[stickers[0]];
+
+                    if piece_stickers[to].is_none() {
+                        piece_stickers[to] = Some(stickers);
+                        queue.push_back(to);
+                    }
+                }
+            }
+        }
+        let piece_stickers = piece_stickers.into_iter().map(Option::unwrap).collect::<Vec<_>>();
+
+        let mut orbits = BTreeMap::<usize, Vec<usize>>::new();
+        for (idx, stickers) in piece_stickers.iter().enumerate() {
+            orbits.entry(stickers.len()).or_default().push(idx);
+        }
+
+        let mut position_in_orbit = vec![0; piece_stickers.len()];
+        for pieces in orbits.values() {
+            for (position, &idx) in pieces.iter().enumerate() {
+                position_in_orbit[idx] = position;
+            }
+        }
+
+        // The piece that every facelet belongs to and which sticker of the piece it is
+        let mut facelet_locations = vec![(0, 0); piece_assignments.len()];
+        for (idx, stickers) in piece_stickers.iter().enumerate() {
+            for (sticker, &facelet) in stickers.iter().enumerate() {
+                facelet_locations[facelet] = (idx, sticker);
+            }
+        }
+
+        orbits
+            .into_iter()
+            .map(|(stickers_per_piece, pieces)| {
+                let (permutation, orientation) = pieces
+                    .iter()
+                    .map(|&idx| {
+                        let (piece, sticker) =
+                            facelet_locations[perm.mapping().get(piece_stickers[idx][0])];
+                        (position_in_orbit[piece] as u16, sticker as u8)
+                    })
+                    .unzip();
+
+                OrbitStateData {
+                    stickers_per_piece: stickers_per_piece as u8,
+                    permutation,
+                    orientation,
+                }
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use puzzle_theory::{
+        permutations::{Algorithm, Permutation},
+        puzzle_geometry::parsing::puzzle,
+    };
+
+    use super::OrbitStateData;
+
+    #[test]
+    fn corner_twists() {
+        let group = puzzle("3x3").permutation_group();
+
+        let corners_of = |perm: &Permutation| {
+            OrbitStateData::from_permutation(perm, &group)
+                .into_iter()
+                .find(|orbit| orbit.stickers_per_piece == 3)
+                .unwrap()
+        };
+        let corners = |alg: &str| {
+            corners_of(
+                Algorithm::parse_from_string(Arc::clone(&group), alg)
+                    .unwrap()
+                    .permutation(),
+            )
+        };
+
+        let solved = corners_of(&Permutation::identity());
+        assert!(solved.orientation.iter().all(|&twist| twist == 0));
+        assert!(solved.permutation.iter().enumerate().all(|(i, &piece)| piece as usize == i));
+
+        // R moves four corners and leaves the rest alone, and the twists of all of the corners always add up to a multiple of three
+        let r = corners("R");
+        let moved = r.permutation.iter().enumerate().filter(|&(i, &piece)| piece as usize != i);
+        assert_eq!(moved.count(), 4);
+        for (i, &piece) in r.permutation.iter().enumerate() {
+            if piece as usize == i {
+                assert_eq!(r.orientation[i], 0);
+            }
+        }
+        assert_eq!(r.orientation.iter().map(|&twist| u32::from(twist)).sum::<u32>() % 3, 0);
+
+        // Twists two corners on the U face in place in opposite directions
+        let twisted = corners(
+            "R' D' R D R' D' R D U R' D' R D R' D' R D R' D' R D R' D' R D U'",
+        );
+        assert!(twisted.permutation.iter().enumerate().all(|(i, &piece)| piece as usize == i));
+        let mut twists =
+            twisted.orientation.iter().copied().filter(|&twist| twist != 0).collect::<Vec<_>>();
+        twists.sort_unstable();
+        assert_eq!(twists, vec![1, 2]);
+    }
+}
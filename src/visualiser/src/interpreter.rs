@@ -2,10 +2,18 @@ use std::sync::Arc;
 
 use interpreter::{
     ExecutionState, PausedState,
-    puzzle_states::{RemoteRobot, RobotState, SimulatedPuzzle},
+    puzzle_states::{
+        PuzzleState, ReconnectPolicy, RemoteRobot, RobotLike, RobotState, SimulatedPuzzle,
+    },
+};
+use puzzle_theory::{
+    permutations::{Algorithm, Permutation},
+    puzzle_geometry::PuzzleGeometry,
+};
+use qter_core::{
+    PuzzleIdx, TheoreticalIdx,
+    architectures::{Architecture, decode},
 };
-use puzzle_theory::{permutations::Permutation, puzzle_geometry::PuzzleGeometry};
-use qter_core::architectures::Architecture;
 use serde::{Deserialize, Serialize};
 use tsify::Tsify;
 use wasm_bindgen::prelude::*;
@@ -41,6 +49,14 @@ impl From<&ExecutionState> for InterpreterState {
     }
 }
 
+#[derive(Tsify, Serialize)]
+#[tsify(into_wasm_abi)]
+pub struct RegisterValues {
+    theoretical: Vec<BigInt>,
+    /// `None` if the register cannot be decoded from the current puzzle state
+    puzzle: Vec<Option<BigInt>>,
+}
+
 #[derive(Tsify, Deserialize)]
 #[tsify(from_wasm_abi)]
 pub struct Callbacks {
@@ -59,6 +75,7 @@ type Robot = Either<RemoteRobot<Connection>, SimulatedPuzzle>;
 #[wasm_bindgen]
 pub struct Interpreter {
     inner: interpreter::Interpreter<RobotState<CaptureCubeState<Robot, CubeStateCb>>>,
+    arch: Arc<Architecture>,
     message_cb: Function,
 }
 
@@ -90,11 +107,16 @@ impl Interpreter {
         let interpreter = interpreter::Interpreter::new_only_one_puzzle(
             program.inner.clone(),
             (
-                connection.map_or(Either::Right(()), Either::Left),
-                mk_cube_state_cb(
-                    callbacks.cube_state,
-                    program.puzzle.clone(),
-                    program.arch.clone(),
+                None,
+                (
+                    connection.map_or(Either::Right(()), |connection| {
+                        Either::Left((connection, ReconnectPolicy::none()))
+                    }),
+                    mk_cube_state_cb(
+                        callbacks.cube_state,
+                        program.puzzle.clone(),
+                        program.arch.clone(),
+                    ),
                 ),
             ),
         )
@@ -102,6 +124,7 @@ impl Interpreter {
 
         let mut this = Self {
             inner: interpreter,
+            arch: program.arch.clone(),
             message_cb: callbacks.message,
         };
         this.send_queued_messages();
@@ -137,6 +160,88 @@ impl Interpreter {
         Ok(())
     }
 
+    pub fn queue_input(&mut self, input: i64) {
+        self.inner.queue_input(input.into());
+    }
+
+    pub async fn run_until_breakpoint(&mut self) -> Result<(), JsError> {
+        self.inner.run_until_breakpoint().await?;
+        self.send_queued_messages();
+        Ok(())
+    }
+
+    pub async fn run_to(&mut self, instruction_idx: usize) -> Result<(), JsError> {
+        self.inner.run_to(instruction_idx).await?;
+        self.send_queued_messages();
+        Ok(())
+    }
+
+    pub fn set_breakpoint(&mut self, instruction_idx: usize) -> bool {
+        self.inner.set_breakpoint(instruction_idx)
+    }
+
+    pub fn clear_breakpoint(&mut self, instruction_idx: usize) -> bool {
+        self.inner.clear_breakpoint(instruction_idx)
+    }
+
+    /// Apply an arbitrary algorithm to the puzzle without affecting the program counter
+    pub async fn apply_algorithm(&mut self, moves: &str) -> Result<(), JsError> {
+        let group = Arc::clone(&self.inner.program().puzzles[0]);
+        let Some(alg) = Algorithm::parse_from_string(group, moves) else {
+            return Err(JsError::new(&format!("Invalid algorithm: {moves}")));
+        };
+
+        let puzzle = self.inner.state_mut().puzzle_state_mut(PuzzleIdx(0));
+        puzzle.compose_into(&alg).await?;
+        // Taking a picture triggers the cube state callback
+        puzzle.robot_mut().take_picture().await?;
+
+        Ok(())
+    }
+
+    /// Solve the puzzle and restart the program from the beginning
+    pub async fn reset(&mut self) -> Result<(), JsError> {
+        self.inner.reset().await?;
+        // Taking a picture triggers the cube state callback
+        self.inner
+            .state_mut()
+            .puzzle_state_mut(PuzzleIdx(0))
+            .robot_mut()
+            .take_picture()
+            .await?;
+
+        Ok(())
+    }
+
+    /// The value of every register. Puzzle registers are decoded from the state that the puzzle should be in after the moves performed so far, so no picture is taken.
+    pub fn register_values(&self) -> RegisterValues {
+        let theoretical = (0..self.inner.program().theoretical.len())
+            .map(|idx| {
+                BigInt::from(
+                    self.inner
+                        .state()
+                        .theoretical_state(TheoreticalIdx(idx))
+                        .value(),
+                )
+            })
+            .collect();
+
+        let perm = self.inner.state().puzzle_state(PuzzleIdx(0)).robot().state();
+        let puzzle = self
+            .arch
+            .registers()
+            .iter()
+            .map(|reg| {
+                decode(perm, reg.signature_facelets().facelets(), reg.algorithm()).map(BigInt::from)
+            })
+            .collect();
+
+        RegisterValues {
+            theoretical,
+            puzzle,
+        }
+    }
+
     #[wasm_bindgen(getter)]
     pub fn program_counter(&self) -> usize {
         self.inner.state().program_counter()
@@ -1,9 +1,10 @@
 use std::sync::Arc;
 
-use ariadne::{Report as AriadneReport, ReportKind, Source as AriadneSource, Span as _};
+use ariadne::{ReportKind, Span as _};
+use compiler::RegisterInfo;
 use internment::ArcIntern;
 use puzzle_theory::{
-    puzzle_geometry::{PuzzleGeometry, parsing::puzzle},
+    puzzle_geometry::PuzzleGeometry,
     span::{File, Span},
 };
 use qter_core::architectures::Architecture;
@@ -31,89 +32,87 @@ impl Program {
         let mk_error = |msg: &str, span: Option<Span>| {
             let span = span.unwrap_or_else(|| Span::new(s.clone(), 0, s.inner().len()));
             vec![CompileError {
-                report: AriadneReport::build(ReportKind::Error, span)
+                error: compiler::CompileError::build(ReportKind::Error, span)
                     .with_message(msg)
                     .finish(),
                 source: s.clone(),
             }]
         };
 
-        let reporter = compiler::Reporter::default();
-
-        let (program, regs) =
-            match compiler::compile(&s, |_| Err("Imports are not allowed".to_owned()), &reporter) {
-                Some(v) => v,
-                None => {
-                    let reports = Arc::try_unwrap(reporter)
-                        .expect("reporter should be uniquely owned after compile")
-                        .into_iter();
-                    return Err(reports
-                        .map(|report| CompileError {
-                            report,
-                            source: s.clone(),
-                        })
-                        .collect::<Vec<_>>());
-                }
-            };
-
-        let Some(regs) = regs else {
+        let (compiled, q_text, instr_spans) = match compiler::compile_to_q(
+            &s,
+            |_| Err("Imports are not allowed".to_owned()),
+            "<output>".into(),
+        ) {
+            Ok(v) => v,
+            Err(errors) => {
+                return Err(errors
+                    .into_iter()
+                    .map(|error| CompileError {
+                        error,
+                        source: s.clone(),
+                    })
+                    .collect());
+            }
+        };
+
+        let Some(regs_span) = compiled.declaration_span() else {
             return Err(mk_error(
-                "No registers declaration supplied. There must be a registers declaration with exactly one 3x3 puzzle.",
+                "No registers declaration supplied. There must be a registers declaration with exactly one puzzle.",
                 None,
             ));
         };
-        let [compiler::Puzzle::Real { architectures }] = regs.puzzles() else {
+        let Some(first) = compiled.registers().first().and_then(RegisterInfo::puzzle) else {
             return Err(mk_error(
                 "You must supply exactly one puzzle",
-                Some(regs.span().clone()),
+                Some(regs_span.clone()),
             ));
         };
-        let [(names, arch, _)] = &**architectures else {
+        if compiled.registers().iter().any(|reg| reg.puzzle_idx() != 0) {
             return Err(mk_error(
-                "Unexpected error: architecture switching",
-                Some(regs.span().clone()),
+                "You must supply exactly one puzzle",
+                Some(regs_span.clone()),
+            ));
+        }
+        if compiled.registers().iter().any(|reg| {
+            reg.puzzle()
+                .is_none_or(|puzzle| !Arc::ptr_eq(puzzle.architecture(), first.architecture()))
+        }) {
+            return Err(mk_error(
+                "The visualiser doesn't support switching architectures yet",
+                Some(regs_span.clone()),
+            ));
+        }
+        let Ok(puzzle) = first.definition().slice().parse::<PuzzleGeometry>() else {
+            return Err(mk_error(
+                "The puzzle could not be visualised because its geometry is unknown",
+                Some(first.definition().clone()),
             ));
         };
 
-        let registers = arch
+        let registers = compiled
             .registers()
             .iter()
-            .enumerate()
-            .map(|(i, reg)| Register {
-                label: names[i].clone().into_inner(),
+            .map(|reg| Register {
+                label: ArcIntern::clone(reg.name()),
                 order: BigInt::from(reg.order()),
                 cycles: reg
-                    .unshared_cycles()
+                    .cycles()
                     .iter()
                     .map(|cycle| RegisterCycle {
-                        order: BigInt::from(cycle.chromatic_order()),
-                        facelets: cycle.facelet_cycle().iter().map(|&v| v as u8).collect(),
+                        order: BigInt::from(cycle.order()),
+                        facelets: cycle.facelets().iter().map(|&v| v as u8).collect(),
                     })
                     .collect(),
             })
             .collect();
-
-        let (q_text, instr_spans) =
-            match compiler::q_emitter::emit_q(&program, "<output>".into(), &reporter) {
-                Some(v) => v,
-                None => {
-                    let reports = Arc::try_unwrap(reporter)
-                        .expect("reporter should be uniquely owned after emit_q")
-                        .into_iter();
-                    return Err(reports
-                        .map(|report| CompileError {
-                            report,
-                            source: s.clone(),
-                        })
-                        .collect::<Vec<_>>());
-                }
-            };
+        let arch = Arc::clone(first.architecture());
 
         Ok(Self {
-            inner: Arc::new(program),
+            inner: Arc::new(compiled.into_program()),
             registers,
-            arch: arch.clone().into_inner(),
-            puzzle: puzzle("3x3"),
+            arch,
+            puzzle: Arc::new(puzzle),
             q_text,
             instr_spans,
         })
@@ -141,25 +140,14 @@ impl Program {
 
 #[wasm_bindgen]
 pub struct CompileError {
-    report: ariadne::Report<'static, puzzle_theory::span::Span>,
+    error: compiler::CompileError,
     source: File,
 }
 
 #[wasm_bindgen]
 impl CompileError {
-    // TODO: Fork ariadne to extract span info out of reports?
     pub fn render(&self) -> String {
-        let mut buf = std::io::Cursor::new(Vec::<u8>::new());
-        self.report
-            .write(
-                (
-                    self.source.name(),
-                    AriadneSource::from(&*self.source.inner()),
-                ),
-                &mut buf,
-            )
-            .unwrap();
-        String::from_utf8(buf.into_inner()).unwrap()
+        compiler::render_diagnostic(&self.error, &self.source)
     }
 }
 
@@ -3,7 +3,15 @@ use std::{error::Error, fmt::Display};
 use interpreter::puzzle_states::RobotLike;
 use puzzle_theory::permutations::{Algorithm, Permutation, PermutationGroup};
 
-pub struct CaptureCubeState<T, F>(T, F);
+/// Calls the callback with every picture taken, and keeps track of the state that the puzzle should be in so that it can be read without taking a picture
+pub struct CaptureCubeState<T, F>(T, F, Permutation);
+
+impl<T, F> CaptureCubeState<T, F> {
+    /// The state that the puzzle should be in according to the moves performed since the last picture
+    pub fn state(&self) -> &Permutation {
+        &self.2
+    }
+}
 
 impl<T: RobotLike, F: FnMut(&Permutation)> RobotLike for CaptureCubeState<T, F> {
     type InitializationArg = (T::InitializationArg, F);
@@ -13,27 +21,43 @@ impl<T: RobotLike, F: FnMut(&Permutation)> RobotLike for CaptureCubeState<T, F>
         perm_group: std::sync::Arc<PermutationGroup>,
         (args, cb): Self::InitializationArg,
     ) -> Result<Self, Self::Error> {
-        let mut this = Self(T::initialize(perm_group, args).await?, cb);
+        let mut this = Self(
+            T::initialize(perm_group, args).await?,
+            cb,
+            Permutation::identity(),
+        );
         this.1(&Permutation::identity());
         Ok(this)
     }
 
     async fn compose_into(&mut self, alg: &Algorithm) -> Result<(), Self::Error> {
-        self.0.compose_into(alg).await
+        self.0.compose_into(alg).await?;
+        self.2.compose_into(alg.permutation());
+        Ok(())
     }
 
     async fn take_picture(&mut self) -> Result<&Permutation, Self::Error> {
         let perm = self.0.take_picture().await?;
         self.1(perm);
+        self.2 = perm.clone();
         Ok(perm)
     }
 
     async fn solve(&mut self) -> Result<(), Self::Error> {
-        self.0.solve().await
+        self.0.solve().await?;
+        self.2 = Permutation::identity();
+        Ok(())
     }
 
     async fn compose_perm(&mut self, perm: &Permutation) -> Result<(), Self::Error> {
-        self.0.compose_perm(perm).await
+        self.0.compose_perm(perm).await?;
+        self.2.compose_into(perm);
+        Ok(())
+    }
+
+    async fn calibrate(&mut self, reference: Permutation) -> Result<(), Self::Error> {
+        self.2 = reference.clone();
+        self.0.calibrate(reference).await
     }
 }
 
@@ -92,6 +116,13 @@ impl<T: RobotLike, U: RobotLike> RobotLike for Either<T, U> {
             Self::Right(inner) => inner.compose_perm(perm).await.map_err(Either::Right),
         }
     }
+
+    async fn calibrate(&mut self, reference: Permutation) -> Result<(), Self::Error> {
+        match self {
+            Self::Left(inner) => inner.calibrate(reference).await.map_err(Either::Left),
+            Self::Right(inner) => inner.calibrate(reference).await.map_err(Either::Right),
+        }
+    }
 }
 
 impl<T: Display, U: Display> Display for Either<T, U> {